@@ -0,0 +1,70 @@
+// Lexes generated inputs of increasing size and reports the time per byte,
+// which should stay flat as the input grows if lexing is linear.
+//
+//     cargo bench --bench lexer
+
+#![allow(
+    dead_code,
+    clippy::needless_return,
+    clippy::redundant_field_names,
+)]
+
+#[path = "../src/span.rs"]
+mod span;
+#[path = "../src/diagnostic.rs"]
+mod diagnostic;
+#[path = "../src/lexer.rs"]
+mod lexer;
+
+use std::time::{Duration, Instant};
+
+const SNIPPET: &str = "int main() {
+  int a = 1 + 2;
+  int b_2 = a * 3 / (4 - 5);
+  b_2 = a && !b_2 || ~a <= 10 != (b_2 >= 20);
+  return a == b_2;
+}
+";
+
+const RUNS: usize = 5;
+
+fn input_of_size(size: usize) -> String {
+    let mut input = String::with_capacity(size + SNIPPET.len());
+    while input.len() < size {
+        input.push_str(SNIPPET);
+    }
+    return input;
+}
+
+fn time_lexing(input: &str) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut token_count = 0;
+    for _ in 0..RUNS {
+        // Only count the tokens so the timing isn't dominated by growing
+        // (and cache missing on) a vector of them
+        let start = Instant::now();
+        let mut count = 0;
+        for token in lexer::Lexer::new(input, "bench.c") {
+            token.expect("Input should lex");
+            count += 1;
+        }
+        best = best.min(start.elapsed());
+        token_count = count;
+    }
+    return (best, token_count);
+}
+
+fn main() {
+    println!("{:>10} {:>10} {:>12} {:>10}", "bytes", "tokens", "best time", "ns/byte");
+    for kilobytes in &[64, 128, 256, 512, 1024] {
+        let input = input_of_size(kilobytes * 1024);
+        let (elapsed, token_count) = time_lexing(&input);
+        println!(
+            "{:>10} {:>10} {:>12?} {:>10.2}",
+            input.len(),
+            token_count,
+            elapsed,
+            elapsed.as_nanos() as f64 / input.len() as f64,
+        );
+    }
+}
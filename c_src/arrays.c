@@ -0,0 +1,102 @@
+int putchar(int c);
+
+int primes[5] = {2, 3, 5};
+int grid[2][3] = {{1, 2, 3}, {4}};
+int zeros[4];
+int *null_pointers[2] = {0};
+static int counts[3] = {1, 2, 3,};
+int prime_count = sizeof(primes) / sizeof(primes[0]);
+int squares[] = {0, 1, 4, 9};
+
+int sum(int *values, int count) {
+    int total = 0;
+    for (int i = 0; i < count; i++) {
+        total += values[i];
+    }
+    return total;
+}
+
+int sum_rows(int rows[][3], int count) {
+    int total = 0;
+    for (int i = 0; i < count; i++) {
+        for (int j = 0; j < 3; j++) {
+            total += rows[i][j];
+        }
+    }
+    return total;
+}
+
+int first(int values[10]) {
+    return *values;
+}
+
+int main() {
+    int checks = 0;
+
+    int a[5] = {1, 2, 3};
+    if (a[0] == 1 && a[2] == 3 && a[3] == 0 && a[4] == 0) checks++;
+
+    a[4] = 10;
+    a[1] += 5;
+    a[3]++;
+    ++a[3];
+    if (sum(a, 5) == 1 + 7 + 3 + 2 + 10) checks++;
+
+    int *p = a;
+    if (p[1] == 7 && *(a + 4) == 10 && p + 5 == &a[5]) checks++;
+
+    int m[3][4];
+    for (int i = 0; i < 3; i++) {
+        for (int j = 0; j < 4; j++) {
+            m[i][j] = i * 4 + j;
+        }
+    }
+    if (m[2][3] == 11 && m[1][0] == 4 && *m[1] == 4 && **m == 0) checks++;
+
+    int *row;
+    row = m[2];
+    if (row[1] == 9) checks++;
+
+    if (sizeof a == 20 && sizeof(a) == 20 && sizeof m == 48 && sizeof m[0] == 16 && sizeof(int[2][3]) == 24) checks++;
+    if (sizeof(int) == 4 && sizeof(int *) == 8 && sizeof p == 8 && sizeof a[0] == 4 && sizeof &a == 8) checks++;
+
+    if (primes[2] == 5 && primes[3] == 0 && sum(primes, 5) == 10) checks++;
+    if (grid[0][2] == 3 && grid[1][0] == 4 && grid[1][2] == 0 && sum_rows(grid, 2) == 10) checks++;
+    if (zeros[3] == 0 && null_pointers[1] == 0 && counts[2] == 3) checks++;
+
+    zeros[2] = 8;
+    grid[1][1] = 6;
+    if (sum(zeros, 4) == 8 && sum_rows(grid, 2) == 16 && first(primes) == 2) checks++;
+
+    int b[2][2] = {{1, 2}, {3, 4}};
+    int *q = &b[0][0];
+    if (q[3] == 4 && &b[1][0] - &b[0][0] == 2 && b[1] - b[0] == 2) checks++;
+
+    int many[100] = {1, 2};
+    if (many[1] == 2 && many[2] == 0 && many[99] == 0 && sum(many, 100) == 3) checks++;
+
+    int scalar = {5};
+    int c[3] = {scalar, scalar + 1};
+    if (c[0] + c[1] + c[2] == 11) checks++;
+
+    int i = 0;
+    int d[3] = {0, 0, 0};
+    d[i++] = 7;
+    if (d[0] == 7 && i == 1) checks++;
+
+    // Subscripting is commutative, as `a[b]` is `*(a + b)`
+    2[d] = 9;
+    if (1[b][1] == 4 && d[2] == 9 && i[d] == 0) checks++;
+
+    // `sizeof` an expression is a constant, so it can size an array
+    long copy[sizeof(grid) / sizeof(grid[0][0])];
+    if (sizeof copy == 48 && prime_count == 5) checks++;
+
+    // A size left out is counted from the initialiser
+    long pairs[][2] = {{1, 2}, {3, 4}, {5, 6}};
+    if (sizeof squares == 16 && squares[3] == 9 && sizeof pairs == 48 && pairs[2][1] == 6) checks++;
+
+    putchar(48 + checks % 10);
+    putchar(10);
+    return checks;
+}
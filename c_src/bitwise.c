@@ -0,0 +1,38 @@
+int putchar(int);
+
+int check(int condition) {
+  // Prints 'y' or 'n'
+  putchar(condition ? 121 : 110);
+  return condition;
+}
+
+// Needs folding at compile time
+int flags = 1 << 4 | 1 << 1;
+
+int main() {
+  int twelve = 12;
+  int ten = 10;
+  int minus_sixteen = -16;
+  int passed = 0;
+
+  passed = passed + check((twelve & ten) == 8);
+  passed = passed + check((twelve | ten) == 14);
+  passed = passed + check((twelve ^ ten) == 6);
+  passed = passed + check(1 << ten == 1024);
+  passed = passed + check(twelve >> 2 == 3);
+  // Right shifts of negative numbers keep the sign
+  passed = passed + check(minus_sixteen >> 2 == -4);
+
+  // & binds tighter than ^, which binds tighter than |
+  passed = passed + check((1 | 2 ^ 3 & 6) == 1);
+  // Shifts bind looser than + and tighter than <
+  passed = passed + check(1 << 2 + 1 == 8);
+  passed = passed + check(1 << 2 < 5 == 1);
+  // == binds tighter than &
+  passed = passed + check((twelve & 4 == 4) == 0);
+
+  passed = passed + check(flags == 18);
+  putchar(10);
+
+  return passed;
+}
@@ -0,0 +1,25 @@
+int main() {
+  int a = 1;
+  int b = 0;
+  {
+    // Shadows the outer a until the end of the block
+    int a = 10;
+    b = b + a;
+    {
+      int a = 100;
+      b = b + a;
+    }
+    b = b + a;
+  }
+  {
+    // Reuses the stack slots of the block above
+    int c = 2;
+    b = b + c;
+  }
+  if (b > 100) {
+    int d = 3;
+    b = b + d;
+  } else
+    b = 0;
+  return b + a;
+}
@@ -0,0 +1,103 @@
+int putchar(int c);
+int puts(char *s);
+
+char letter = 'A';
+char name[8] = "crate";
+char exact[3] = "abc";
+char counted[] = "crate";
+char *message = "from a global" " pointer";
+unsigned char high = 200;
+signed char low = -5;
+char wrapped = 300;
+char table[2][4] = {"ab", {'c', 'd'}};
+char *null_string = 0;
+
+int length(char *s) {
+    int n = 0;
+    while (s[n]) {
+        n++;
+    }
+    return n;
+}
+
+char next(char c) {
+    return c + 1;
+}
+
+int print(char *s) {
+    for (; *s; s++) {
+        putchar(*s);
+    }
+    putchar(10);
+    return 0;
+}
+
+int many(char a, int b, char c, unsigned char d, signed char e, char f, char g, unsigned char h) {
+    return a + b + c + d + e + f + g + h;
+}
+
+int main() {
+    int checks = 0;
+
+    char c = 'a';
+    if (c == 97 && 'b' - c == 1 && sizeof c == 1 && sizeof 'a' == 4) checks++;
+
+    if ('\n' == 10 && '\t' == 9 && '\0' == 0 && '\\' == 92 && '\'' == 39 && '"' == 34 && '\?' == 63) checks++;
+    if ('\a' == 7 && '\b' == 8 && '\f' == 12 && '\v' == 11 && '\r' == 13) checks++;
+    if ('\101' == 65 && '\x41' == 65 && '\7' == 7 && '\xff' == -1 && '\377' == -1) checks++;
+
+    char s[10] = "hello";
+    if (length(s) == 5 && s[5] == 0 && s[9] == 0 && sizeof s == 10) checks++;
+    if (sizeof "abc" == 4 && length("abc" "de") == 5 && "xyz"[1] == 'y') checks++;
+
+    // A string literal gives an array declared with `[]` its size
+    char word[] = "hi";
+    if (sizeof word == 3 && word[2] == 0 && sizeof counted == 6 && length(counted) == 5) checks++;
+
+    char *p = "pointer";
+    if (p[0] == 'p' && length(p) == 7 && *(p + 7) == 0) checks++;
+
+    unsigned char u = 255;
+    u++;
+    signed char sc = 127;
+    sc++;
+    char plain = 200;
+    if (u == 0 && sc == -128 && plain == -56) checks++;
+
+    unsigned char big = 250;
+    int sum = big + big;
+    char x = 100;
+    x = x + x;
+    if (sum == 500 && x == -56 && (x = 300) == 44) checks++;
+
+    if (letter == 'A' && length(name) == 5 && name[7] == 0 && exact[2] == 'c') checks++;
+    if (high == 200 && low == -5 && wrapped == 44 && null_string == 0) checks++;
+    if (table[0][1] == 'b' && table[0][2] == 0 && table[1][1] == 'd' && table[1][3] == 0) checks++;
+
+    if (next('a') == 'b' && next(127) == -128) checks++;
+    if (many(1, 2, 3, 255, -1, 4, 5, 6) == 275) checks++;
+
+    char buffer[4];
+    buffer[0] = 'o';
+    buffer[1] = 'k';
+    buffer[2] = '\0';
+    char *q = buffer;
+    *q += 1;
+    if (buffer[0] == 'p' && length(buffer) == 2) checks++;
+
+    int i = 0;
+    char *r = &c;
+    *r = 'z';
+    i = *r;
+    if (i == 122 && c == 'z') checks++;
+
+    print(message);
+    print("tab\there, quote \" and backslash \\");
+    puts("\x41\102C\n");
+    print(name);
+
+    putchar(48 + checks / 10);
+    putchar(48 + checks % 10);
+    putchar(10);
+    return checks;
+}
@@ -0,0 +1,11 @@
+/*
+ * Comments are skipped by the lexer
+ */
+int main() {
+  // Line comments run to the end of the line
+  int a = 4; /* block comments can sit between tokens */
+  int b = a /* or inside expressions */ * 2;
+  /* a '/*' inside a comment doesn't nest */
+  int c = b*/* so this is still a multiplication */2;
+  return c - b - a; // 4
+}
@@ -0,0 +1,69 @@
+int putchar(int);
+
+int check(int condition) {
+  // Prints 'y' or 'n'
+  putchar(condition ? 121 : 110);
+  return condition;
+}
+
+int counter = 0;
+
+int next() {
+  return ++counter;
+}
+
+int main() {
+  int passed = 0;
+  int a = 10;
+  int b;
+  int i;
+
+  a += 5;
+  passed += check(a == 15);
+  a -= 20;
+  passed += check(a == -5);
+  a *= -3;
+  passed += check(a == 15);
+  a /= 4;
+  passed += check(a == 3);
+  a %= 2;
+  passed += check(a == 1);
+  a <<= 4;
+  passed += check(a == 16);
+  a >>= 2;
+  passed += check(a == 4);
+  a |= 3;
+  passed += check(a == 7);
+  a &= 5;
+  passed += check(a == 5);
+  a ^= 6;
+  passed += check(a == 3);
+
+  // Assignments are expressions, and group right to left
+  a = b = 4;
+  a += b -= 1;
+  passed += check(a == 7 && b == 3);
+
+  // Prefix gives the new value, postfix the old one
+  passed += check(a++ == 7 && a == 8);
+  passed += check(++a == 9 && a == 9);
+  passed += check(a-- == 9 && a == 8);
+  passed += check(--a == 7 && a == 7);
+  passed += check(-a++ == -7 && a == 8);
+
+  // Globals too
+  next();
+  counter++;
+  passed += check(next() == 3);
+
+  // The comma operator evaluates left to right and takes the last value
+  b = (a = 1, a + 1);
+  passed += check(a == 1 && b == 2);
+  for (i = 0, b = 0; i < 5; i++, b += 2) {
+  }
+  passed += check(i == 5 && b == 10);
+  passed += check((next(), next()) == 5);
+
+  putchar(10);
+  return passed;
+}
@@ -0,0 +1,27 @@
+int putchar(int);
+
+int check(int condition) {
+  // Prints 'y' or 'n'
+  putchar(condition ? 121 : 110);
+  return condition;
+}
+
+int main() {
+  int seven = 7;
+  int minus_seven = -7;
+  int passed = 0;
+
+  // Division rounds towards zero, and the remainder takes the sign of the
+  // dividend
+  passed = passed + check(minus_seven / 2 == -3);
+  passed = passed + check(minus_seven % 2 == -1);
+  passed = passed + check(seven / -2 == -3);
+  passed = passed + check(seven % -2 == 1);
+  passed = passed + check(minus_seven / -2 == 3);
+  passed = passed + check(minus_seven % -2 == -1);
+  passed = passed + check(100 % 7 * 2 == 4);
+  passed = passed + check((minus_seven / 2) * 2 + minus_seven % 2 == minus_seven);
+  putchar(10);
+
+  return passed;
+}
@@ -0,0 +1,98 @@
+typedef unsigned long size_t;
+typedef char *string;
+
+int printf(string format, ...);
+
+enum color {
+    RED,
+    GREEN,
+    BLUE = 10,
+    PURPLE,
+    MIXED = RED + PURPLE * 2,
+};
+
+enum {
+    FLAG = 1 << 30,
+    SIZE = sizeof(size_t),
+    LETTER = 'a',
+};
+
+typedef struct node {
+    int value;
+    struct node *next;
+} Node, *NodeList;
+
+typedef int Row[3];
+typedef enum color Color;
+
+Color favourite = BLUE;
+int sizes[PURPLE];
+
+size_t count(NodeList list) {
+    size_t total = 0;
+    for (Node *node = list; node; node = node->next) {
+        total++;
+    }
+    return total;
+}
+
+Color next(Color c) {
+    return c == RED ? GREEN : c == GREEN ? BLUE : RED;
+}
+
+int main() {
+    int checks = 0;
+
+    if (RED == 0 && GREEN == 1 && BLUE == 10 && PURPLE == 11 && MIXED == 22) checks++;
+    if (FLAG == 1073741824 && -FLAG < 0 && SIZE == 8 && LETTER == 97) checks++;
+    if (sizeof(enum color) == 4 && sizeof(Color) == sizeof(int) && sizeof sizes == 44) checks++;
+    if (sizeof(size_t) == 8 && sizeof(string) == 8 && sizeof(Row) == 12 && sizeof(NodeList) == 8) checks++;
+
+    Node last = {3, 0};
+    Node first = {1, &last};
+    NodeList list = &first;
+    if (count(list) == 2 && list->next->value == 3) checks++;
+
+    Row row = {1, 2, 3};
+    Row *rows = &row;
+    if ((*rows)[2] == 3) checks++;
+
+    Color c = RED;
+    c = next(next(c));
+    if (c == BLUE && next(c) == RED && favourite == BLUE) checks++;
+
+    // A cast to a typedef name, and a parenthesised variable
+    size_t big = (size_t)-1;
+    int value = 6;
+    int halved = (value) / 2;
+    if (big == 18446744073709551615ul && halved == 3 && (string)0 == 0) checks++;
+
+    {
+        // Variables hiding a typedef name and an enum constant, so these
+        // are expressions rather than declarations
+        int size_t = 4;
+        int RED = 5;
+        int product = size_t * RED;
+        if (product == 20 && sizeof(size_t) == 4) checks++;
+
+        // Typedef names and enums in an inner block go out of scope with it
+        typedef short size;
+        enum { TEN = BLUE };
+        size small = TEN;
+        if (sizeof(size) == 2 && small == 10) checks++;
+    }
+    if (sizeof(size_t) == 8 && RED == 0) checks++;
+
+    // A name can be a typedef name in one scope and a variable in another
+    typedef int T;
+    T x = 1;
+    {
+        T T = 2;
+        x = x + T;
+    }
+    T y = x * 2;
+    if (y == 6) checks++;
+
+    printf("%d %d %lu %d\n", PURPLE, MIXED, count(list), checks);
+    return checks;
+}
@@ -0,0 +1,64 @@
+int printf(char *format, ...);
+
+double third = 1.0 / 3;
+float tenth = 0.1f;
+double from_int = 7;
+int from_double = 2.9;
+unsigned long huge = 1e19;
+double weights[3] = {0.5, 2, 1.5f};
+
+double average(double a, double b) {
+    return (a + b) / 2;
+}
+
+float scale(float x, int factor) {
+    return x * factor;
+}
+
+// Nine doubles, so the last one is passed on the stack
+double sum(int a, double b, double c, double d, double e, double f, double g, double h, double i, double j, long k) {
+    return a + b + c + d + e + f + g + h + i + j + k;
+}
+
+int main() {
+    int checks = 0;
+
+    double x = 0.1;
+    double y = .2;
+    float f = 1.0f / 3;
+    int i = 10;
+    unsigned long ul = 18446744073709551615ul;
+    unsigned int u = 4000000000u;
+    double nan = 0.0 / 0.0;
+    double zero = 0.0;
+
+    if (x + y != 0.3 && x + y > 0.3 && x < y && !(x >= y) && x <= 0.1) checks++;
+    if (f != third && f == (float)third && (double)f > third) checks++;
+    if (!(nan == nan) && nan != nan && !(nan < 1) && !(nan >= 1) && nan && !zero) checks++;
+    if (-zero == 0 && 1 / -zero < 0 && -x == -0.1 && 1e300 * 1e10 > 1e308) checks++;
+
+    if ((int)-2.7 == -2 && (long)1e15 == 1000000000000000 && (unsigned char)200.5 == 200) checks++;
+    if ((unsigned long)1e19 == 10000000000000000000ul && (unsigned int)3e9 == 3000000000u) checks++;
+    if ((double)ul == 18446744073709551616.0 && (double)u == 4000000000.0 && (float)i == 10) checks++;
+    if (i / 4 == 2 && i / 4.0 == 2.5 && 'a' + 0.5 == 97.5 && 1.5e2 == 150 && 2.5e-1f == 0.25) checks++;
+
+    if (third * 3 == 1 && tenth == (float)0.1 && from_int == 7 && from_double == 2 && huge == 1e19) checks++;
+    if (weights[0] + weights[1] + weights[2] == 4 && sizeof(double) == 8 && sizeof tenth == 4) checks++;
+    if (average(1, 2.5) == 1.75 && scale(1.5f, 3) == 4.5 && sum(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11) == 66) checks++;
+
+    x += 1;
+    i += 2.7;
+    i *= 1.5;
+    f -= 0.5;
+    if (x == 1.1 && i == 18 && f == 1.0f / 3 - 0.5f) checks++;
+
+    double before = x++;
+    ++f;
+    double *p = &x;
+    *p *= 2;
+    weights[0] /= 4;
+    if (before == 1.1 && x == 4.2 && f > 0.83 && f < 0.84 && weights[0] == 0.125) checks++;
+
+    printf("%f %.3f %g %e %d\n", third, f, x, 1e-5, checks);
+    return checks;
+}
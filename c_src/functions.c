@@ -0,0 +1,34 @@
+int putchar(int);
+int fib(int n);
+
+// The seventh and eighth arguments are passed on the stack
+int weighted_sum(int a, int b, int c, int d, int e, int f, int g, int h) {
+  return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int print_digit(int digit) {
+  putchar(48 + digit);
+  return digit;
+}
+
+int main() {
+  int total = 0;
+  for (int i = 0; i < 10; i = i + 1)
+    total = total + print_digit(i);
+  putchar(10);
+
+  {
+    // Calls made with an odd number of slots on the stack still get an
+    // aligned stack
+    int odd = weighted_sum(1, 1, 1, 1, 1, 1, 1, 1);
+    total = total + odd + fib(weighted_sum(0, 0, 0, 0, 0, 1, 0, 0));
+  }
+
+  return total;
+}
+
+int fib(int n) {
+  if (n < 2)
+    return n;
+  return fib(n - 1) + fib(n - 2);
+}
@@ -0,0 +1,34 @@
+// Declared extern first, then defined further down
+extern int total;
+
+// Tentative definitions, merged into the initialised definition below
+int counter;
+int counter;
+int counter = 2 * 3 - 1;
+
+static int calls;
+int zero;
+int negative = -(1 + 1);
+
+static int count(int amount) {
+  calls = calls + 1;
+  counter = counter + amount;
+  return counter;
+}
+
+int main() {
+  int i;
+  for (i = 0; i < 4; i = i + 1)
+    count(i);
+
+  {
+    // Shadows the global until the end of the block
+    int counter = 100;
+    total = total + counter;
+  }
+
+  total = total + counter + calls + zero + negative;
+  return total;
+}
+
+int total = 1;
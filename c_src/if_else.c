@@ -0,0 +1,21 @@
+int main() {
+  int a = 3;
+  int b = 0;
+
+  if (a > 2)
+    b = 1;
+  else
+    b = 2;
+
+  // The else binds to the nearest if
+  if (a < 2)
+    if (a < 1)
+      b = b + 10;
+    else
+      b = b + 20;
+
+  if (b == 1)
+    b = b + 4;
+
+  return b;
+}
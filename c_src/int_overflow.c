@@ -0,0 +1,44 @@
+int putchar(int);
+
+int smallest = -2147483647 - 1;
+
+// Overflowing arithmetic is done in functions, so that it happens at run time
+// with gcc too rather than being folded away
+int add(int a, int b) {
+  return a + b;
+}
+
+int multiply(int a, int b) {
+  return a * b;
+}
+
+int negate(int a) {
+  return -a;
+}
+
+// The last two arguments are passed on the stack
+int last_of(int a, int b, int c, int d, int e, int f, int g, int h) {
+  return g + h;
+}
+
+int check(int condition) {
+  // Prints 'y' or 'n'
+  putchar(condition ? 121 : 110);
+  return condition;
+}
+
+int main() {
+  int largest = 2147483647;
+  int passed = 0;
+
+  passed = passed + check(add(largest, 1) < 0);
+  passed = passed + check(add(largest, 1) == smallest);
+  passed = passed + check(add(smallest, -1) == largest);
+  passed = passed + check(multiply(65536, 65536) == 0);
+  passed = passed + check(negate(smallest) == smallest);
+  passed = passed + check(~0 == -1);
+  passed = passed + check(last_of(0, 0, 0, 0, 0, 0, largest, 1) == smallest);
+  putchar(10);
+
+  return passed;
+}
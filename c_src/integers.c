@@ -0,0 +1,81 @@
+int putchar(int c);
+
+long big = 5000000000;
+unsigned short max_short = 65535;
+short negative_short = -2;
+unsigned int all_ones = 0xffffffff;
+long long longs[3] = {1, 2L, -3000000000};
+unsigned long array_size = sizeof(long[4]);
+int cut = (unsigned char)300;
+
+long add(long a, long b) {
+    return a + b;
+}
+
+short narrow(long x) {
+    return x;
+}
+
+unsigned long many(int a, long b, short c, unsigned char d, long e, unsigned int f, long g, short h) {
+    return a + b + c + d + e + f + g + h;
+}
+
+int main() {
+    int checks = 0;
+
+    long l = 5000000000;
+    unsigned u = 4294967295u;
+    int i = -1;
+    short s = -3;
+    unsigned short us = 65535;
+    unsigned long ul = 18446744073709551615ul;
+
+    if (l / 3 == 1666666666 && -l == -5000000000 && ~l == -5000000001) checks++;
+    if (u + 1 == 0 && u / 2 == 2147483647 && u % 10 == 5 && u >> 1 == 2147483647) checks++;
+    if (!(i < u) && i < (long)u && i < 1 && !(i < 1u)) checks++;
+    if (s * us == -196605 && us + 1 == 65536 && i >> 1 == -1) checks++;
+    if (ul == -1 && ul > 0 && ul / 2 == 9223372036854775807 && ul >> 63 == 1) checks++;
+    if (1L << 40 == 1099511627776 && 0x10 == 16 && 010 == 8 && 0XfF == 255 && ~0ul == ul) checks++;
+
+    if (sizeof(short) == 2 && sizeof(long long) == 8 && sizeof 1L == 8 && sizeof(s + s) == 4) checks++;
+    if (sizeof 4294967295 == 8 && sizeof 0xffffffff == 4 && sizeof 2147483648 == 8 && sizeof(i + l) == 8) checks++;
+
+    if (add(3000000000, 3000000000) == 6000000000 && narrow(70000) == 4464) checks++;
+    if (many(-1, -2, -3, 255, -5, 6, -7, -8) == 235) checks++;
+    if (big == 5000000000 && max_short == 65535 && negative_short == -2 && all_ones == 4294967295) checks++;
+    if (longs[1] == 2 && longs[2] == -3000000000 && array_size == 32 && cut == 44) checks++;
+
+    unsigned char c = 200;
+    c += 100;
+    short t = 32767;
+    t++;
+    int x = 10;
+    x *= 5000000000;
+    if (c == 44 && t == -32768 && x == -1539607552) checks++;
+
+    unsigned long r = 100;
+    r -= 101;
+    long q = -9;
+    q %= 4;
+    if (r == 18446744073709551615ul && q == -1) checks++;
+
+    long values[4] = {10, 20, 30, 40};
+    long *p = values;
+    p += 2;
+    unsigned long index = 3;
+    if (*p == 30 && p - values == 2 && values[index] == 40 && values[s + 4] == 20) checks++;
+
+    short shorts[2];
+    shorts[0] = -1;
+    shorts[1] = 40000;
+    unsigned short *unsigned_shorts = (unsigned short *)shorts;
+    if (shorts[0] == -1 && shorts[1] == -25536 && unsigned_shorts[0] == 65535) checks++;
+
+    if ((char)l == 0 && (unsigned char)-1 == 255 && (long)(int)u == -1 && (unsigned long)i == ul) checks++;
+    if ((i ? l : u) == 5000000000 && (1 ? i : u) == 4294967295) checks++;
+
+    putchar(48 + checks / 10);
+    putchar(48 + checks % 10);
+    putchar(10);
+    return checks;
+}
@@ -0,0 +1,40 @@
+int main() {
+  int sum = 0;
+
+  int i = 0;
+  while (i < 10) {
+    i = i + 1;
+    if (i == 3)
+      continue;
+    sum = sum + i;
+  }
+
+  do {
+    sum = sum - 1;
+  } while (sum > 40);
+
+  for (int j = 0; j < 5; j = j + 1) {
+    // Slots declared in the body are popped by break and continue
+    int doubled = j * 2;
+    if (doubled == 6)
+      break;
+    sum = sum + doubled;
+  }
+
+  int k;
+  for (k = 0; ; k = k + 1) {
+    int n = 0;
+    while (1) {
+      n = n + 1;
+      if (n > k)
+        break;
+    }
+    if (k == 4)
+      break;
+  }
+
+  for (;;)
+    break;
+
+  return sum + k;
+}
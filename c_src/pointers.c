@@ -0,0 +1,94 @@
+int putchar(int c);
+
+int g = 7;
+int *gp;
+int **gpp = 0;
+
+int swap(int *a, int *b) {
+    int t = *a;
+    *a = *b;
+    *b = t;
+    return 0;
+}
+
+int *pick(int *a, int *b, int first) {
+    return first ? a : b;
+}
+
+int sum(int *p, int n) {
+    int total = 0;
+    int *end = p + n;
+    for (; p < end; p++) {
+        total += *p;
+    }
+    return total;
+}
+
+int many(int a, int b, int c, int d, int e, int f, int *g, int *h) {
+    return *g + *h + a + f;
+}
+
+int main() {
+    int x = 1;
+    int y = 2;
+    int *p = &x;
+    int **pp = &p;
+    int checks = 0;
+
+    swap(&x, &y);
+    if (x == 2 && y == 1) checks++;
+
+    **pp = 10;
+    if (x == 10) checks++;
+
+    *p += 5;
+    (*p)++;
+    ++*p;
+    if (x == 17) checks++;
+
+    p = &y;
+    if (*pick(&x, &y, 1) == 17 && pick(&x, &y, 0) == p) checks++;
+
+    gp = &g;
+    gpp = &gp;
+    if (**gpp == 7 && gp != 0 && !(gp == 0)) checks++;
+
+    int *q = 0;
+    if (!q && q == 0 && 0 == q) checks++;
+    if (q) checks = 0;
+
+    int a = 3;
+    int b = 4;
+    int c = 5;
+    int *pa = &c;
+    int *pb = pa + 1;
+    if (pb - pa == 1 && pb > pa && pa <= pb && pb != pa) checks++;
+    if (pa + 2 - 2 == pa && 1 + pa == pa + 1) checks++;
+
+    int *r = &a;
+    r--;
+    r++;
+    if (r == &a) checks++;
+
+    if (many(1, 2, 3, 4, 5, 6, &a, &b) == 14) checks++;
+
+    int *e = &*&a;
+    if (e == &a) checks++;
+
+    int **z = &r;
+    **z = 42;
+    if (a == 42) checks++;
+
+    int v = 9;
+    int *pv = &v;
+    *pv = *pv * 2;
+    *pv -= 3;
+    *pv <<= 1;
+    if (v == 30) checks++;
+
+    if (sum(&v, 1) == 30) checks++;
+
+    putchar(48 + checks % 10);
+    putchar(10);
+    return checks;
+}
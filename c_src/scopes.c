@@ -0,0 +1,37 @@
+int x = 5;
+
+int many(int a, int b, int c, int d, int e, int f, int g, int h) {
+    int t = a - h;
+    {
+        int u = g * 2;
+        t = t + u;
+    }
+    {
+        int v = b;
+        t = t + v;
+    }
+    return t;
+}
+
+int main() {
+    int total = x;
+    int x = 2;
+    for (int i = 0; i < 10; i++) {
+        int y = i;
+        {
+            int x = y * 3;
+            if (x > 20) break;
+            total += x;
+        }
+        if (y == 1) {
+            int z = 7;
+            total += z;
+            continue;
+        }
+    }
+    {
+        int x = 9;
+        total += x;
+    }
+    return total + x + many(1, 2, 3, 4, 5, 6, 7, 8);
+}
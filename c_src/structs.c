@@ -0,0 +1,186 @@
+int printf(char *format, ...);
+
+struct point {
+    int x, y;
+};
+
+// Padded after `tag` so `value` is 8-byte aligned, and at the end so an
+// array of them keeps it aligned
+struct padded {
+    char tag;
+    long value;
+    short count;
+};
+
+union number {
+    int i;
+    double d;
+    char bytes[8];
+};
+
+struct node {
+    int value;
+    struct node *next;
+};
+
+struct rectangle {
+    struct point corners[2];
+    char *name;
+};
+
+struct mixed {
+    double weight;
+    int id;
+};
+
+struct pair {
+    double first;
+    double second;
+};
+
+struct big {
+    long values[4];
+};
+
+struct huge {
+    long values[12];
+    char name[5];
+};
+
+struct odd {
+    char bytes[3];
+};
+
+struct point origin;
+struct point unit = {1, 1};
+struct padded globals[2] = {{'a', 10, 1}, {'b'}};
+union number global_number = {7};
+
+struct point add(struct point a, struct point b) {
+    struct point sum;
+    sum.x = a.x + b.x;
+    sum.y = a.y + b.y;
+    return sum;
+}
+
+struct mixed heavier(struct mixed m, double extra) {
+    m.weight = m.weight + extra;
+    m.id++;
+    return m;
+}
+
+struct pair swap(struct pair p) {
+    struct pair swapped = {p.second, p.first};
+    return swapped;
+}
+
+long last(struct huge h) {
+    return h.values[11] + h.name[0];
+}
+
+struct big doubled(struct big b) {
+    for (int i = 0; i < 4; i++) {
+        b.values[i] *= 2;
+    }
+    return b;
+}
+
+struct odd shift(struct odd o) {
+    struct odd shifted = {{o.bytes[1], o.bytes[2], o.bytes[0]}};
+    return shifted;
+}
+
+// Six ints fill the argument registers, so the struct goes on the stack
+long spill(int a, int b, int c, int d, int e, struct point p, int f) {
+    return a + b + c + d + e + p.x * 10 + p.y * 100 + f;
+}
+
+int length(struct node *list) {
+    int count = 0;
+    for (struct node *node = list; node; node = node->next) {
+        count++;
+    }
+    return count;
+}
+
+int main() {
+    int checks = 0;
+
+    if (sizeof(struct point) == 8 && sizeof(struct padded) == 24 && sizeof(union number) == 8) checks++;
+    if (sizeof(struct node) == 16 && sizeof(struct rectangle) == 24 && sizeof(struct odd) == 3) checks++;
+
+    struct point p = {3, 4};
+    struct point *pp = &p;
+    pp->x += 10;
+    (*pp).y *= 2;
+    if (p.x == 13 && p.y == 8 && origin.x == 0 && unit.y == 1) checks++;
+
+    struct point copy = p;
+    copy.x = 0;
+    struct point assigned;
+    assigned = copy;
+    if (p.x == 13 && copy.x == 0 && assigned.x == 0 && assigned.y == 8) checks++;
+
+    union number n;
+    n.d = 1.0;
+    n.i = 0;
+    if (n.bytes[7] == 63 && global_number.i == 7 && global_number.bytes[0] == 7) checks++;
+
+    struct node third = {3, 0};
+    struct node second = {2, &third};
+    struct node first = {1, &second};
+    if (length(&first) == 3 && first.next->next->value == 3) checks++;
+
+    struct rectangle r = {{{0, 0}, {4, 2}}, "box"};
+    struct rectangle *rp = &r;
+    if (rp->corners[1].x * r.corners[1].y == 8 && rp->name[0] == 'b') checks++;
+
+    struct padded local[2] = {{'x'}};
+    if (globals[0].value == 10 && globals[1].tag == 'b' && globals[1].count == 0 && local[1].value == 0) checks++;
+
+    struct {
+        int a;
+        char b;
+    } anonymous = {5, 6};
+    if (anonymous.a + anonymous.b == 11 && sizeof anonymous == 8) checks++;
+
+    struct point sum = add(p, unit);
+    if (sum.x == 14 && sum.y == 9 && add(sum, sum).x == 28) checks++;
+
+    struct mixed m = {1.5, 7};
+    m = heavier(m, 2);
+    struct pair pair = {1.25, 2.5};
+    struct pair swapped;
+    swapped = swap(pair);
+    if (m.weight == 3.5 && m.id == 8 && swapped.first == 2.5 && swapped.second == 1.25) checks++;
+
+    struct big b = {{1, 2, 3, 4}};
+    struct big d = doubled(b);
+    if (d.values[0] + d.values[3] == 10 && b.values[3] == 4 && doubled(d).values[1] == 8) checks++;
+
+    struct odd o = {{'a', 'b', 'c'}};
+    o = shift(o);
+    if (o.bytes[0] == 'b' && o.bytes[2] == 'a' && spill(1, 2, 3, 4, 5, p, 6) == 13 * 10 + 8 * 100 + 21) checks++;
+
+    // Big structs are zeroed and copied a whole run of eightbytes at a time
+    struct huge h = {{1}, "huge"};
+    struct huge h_copy;
+    h_copy = h;
+    h.values[11] = 5;
+    if (h_copy.values[11] == 0 && h_copy.name[3] == 'e' && last(h) == 5 + 'h' && last(h_copy) == 'h') checks++;
+
+    int pick = 1;
+    struct point chosen = pick ? p : unit;
+    if (chosen.x == 13 && (pick ? unit : p).y == 1) checks++;
+
+    {
+        // A new struct with the same tag hides the outer one
+        struct point {
+            char c;
+        };
+        if (sizeof(struct point) == 1) checks++;
+    }
+
+    printf("%d %d %.2f %ld %c\n", sum.x, length(&second), m.weight, d.values[2], o.bytes[1]);
+    return checks;
+}
@@ -0,0 +1,7 @@
+int main() {
+  int a = 5;
+  int b = a > 4 ? 10 : 20;
+  int c = a ? b ? 1 : 2 : 3;
+  a = 0;
+  return b + c + (a ? 100 : 7);
+}
@@ -1,12 +1,198 @@
 use std::fmt;
+use target::Target;
+use types::Type;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Register {
     Rax,
     Rcx,
     Rdx,
     Rbp,
     Rsp,
+    Rdi,
+    Rsi,
+    R8,
+    R9,
+    Eax,
+    Ecx,
+    Edx,
+    Edi,
+    Esi,
+    R8d,
+    R9d,
+    Ax,
+    Cx,
+    Dx,
+    Di,
+    Si,
+    R8w,
+    R9w,
     Al,
+    Cl,
+    Dl,
+    Dil,
+    Sil,
+    R8b,
+    R9b,
+    // Floating point values are in the low 4 or 8 bytes of these
+    Xmm0,
+    Xmm1,
+    Xmm2,
+    Xmm3,
+    Xmm4,
+    Xmm5,
+    Xmm6,
+    Xmm7,
+}
+
+// Where the first six integer arguments of a call go, in the System V
+// AMD64 calling convention
+pub const ARGUMENT_REGISTERS: [Register; 6] = [
+    Register::Rdi,
+    Register::Rsi,
+    Register::Rdx,
+    Register::Rcx,
+    Register::R8,
+    Register::R9,
+];
+
+// The low 32 bits of the argument registers, where `int` arguments are
+pub const INT_ARGUMENT_REGISTERS: [Register; 6] = [
+    Register::Edi,
+    Register::Esi,
+    Register::Edx,
+    Register::Ecx,
+    Register::R8d,
+    Register::R9d,
+];
+
+// The low 16 bits of the argument registers, where `short` arguments are
+pub const WORD_ARGUMENT_REGISTERS: [Register; 6] = [
+    Register::Di,
+    Register::Si,
+    Register::Dx,
+    Register::Cx,
+    Register::R8w,
+    Register::R9w,
+];
+
+// The low bytes of the argument registers, where `char` arguments are
+pub const BYTE_ARGUMENT_REGISTERS: [Register; 6] = [
+    Register::Dil,
+    Register::Sil,
+    Register::Dl,
+    Register::Cl,
+    Register::R8b,
+    Register::R9b,
+];
+
+// Where the first eight floating point arguments go
+pub const FLOAT_ARGUMENT_REGISTERS: [Register; 8] = [
+    Register::Xmm0,
+    Register::Xmm1,
+    Register::Xmm2,
+    Register::Xmm3,
+    Register::Xmm4,
+    Register::Xmm5,
+    Register::Xmm6,
+    Register::Xmm7,
+];
+
+// The kind of register each eightbyte of a value is passed in, following
+// the System V classification. An eightbyte with only floating point in it
+// is SSE, and goes in an %xmm register, and any other is INTEGER.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Integer,
+    Sse,
+}
+
+// The class of each of the type's eightbytes, or `None` if it's passed in
+// memory instead, as a struct over 16 bytes is
+pub fn classify(ty: &Type) -> Option<Vec<Class>> {
+    if ty.size() > 16 {
+        return None;
+    }
+
+    let mut classes = vec![Class::Sse; (ty.size() as usize).div_ceil(8)];
+    let mut scalars: Vec<(i64, Type)> = Vec::new();
+    flatten(ty, 0, &mut scalars);
+    for (offset, scalar) in scalars {
+        if !scalar.is_floating() {
+            classes[offset as usize / 8] = Class::Integer;
+        }
+    }
+    return Some(classes);
+}
+
+// The scalars that make up a value of the type, with their offsets
+fn flatten(ty: &Type, offset: i64, scalars: &mut Vec<(i64, Type)>) {
+    match ty {
+        Type::Array(element, length) => for index in 0..*length {
+            flatten(element, offset + index * element.size(), scalars);
+        },
+        Type::Struct(struct_type) => for member in struct_type.members() {
+            flatten(&member.ty, offset + member.offset, scalars);
+        },
+        _ => scalars.push((offset, ty.clone())),
+    }
+}
+
+// Where each of the arguments of a call is passed: for each of its
+// eightbytes, its class and the index of its register in
+// `ARGUMENT_REGISTERS`, or in `FLOAT_ARGUMENT_REGISTERS` for an SSE one.
+// `None` if it's passed on the stack, because it's a struct passed in
+// memory or there aren't enough registers of its kind left, as a struct
+// is never split between registers and the stack. A function that returns
+// a struct in memory takes the address to put it at in %rdi, before any of
+// its arguments.
+pub fn argument_registers(return_type: &Type, types: &[Type]) -> Vec<Option<Vec<(Class, usize)>>> {
+    let mut registers: Vec<Option<Vec<(Class, usize)>>> = Vec::new();
+    let mut int_count = if classify(return_type).is_none() { 1 } else { 0 };
+    let mut float_count = 0;
+    for ty in types {
+        let classes = classify(ty).unwrap_or_default();
+        let ints = classes.iter().filter(|class| **class == Class::Integer).count();
+        let floats = classes.len() - ints;
+        let fits = int_count + ints <= ARGUMENT_REGISTERS.len() && float_count + floats <= FLOAT_ARGUMENT_REGISTERS.len();
+        if classes.is_empty() || !fits {
+            registers.push(None);
+            continue;
+        }
+
+        let mut eightbytes: Vec<(Class, usize)> = Vec::new();
+        for class in classes {
+            match class {
+                Class::Integer => {
+                    eightbytes.push((class, int_count));
+                    int_count += 1;
+                },
+                Class::Sse => {
+                    eightbytes.push((class, float_count));
+                    float_count += 1;
+                },
+            }
+        }
+        registers.push(Some(eightbytes));
+    }
+    return registers;
+}
+
+// The register each of the eightbytes of a returned value is in: %rax then
+// %rdx for INTEGER ones, and %xmm0 then %xmm1 for SSE ones. `None` if it's
+// returned in memory.
+pub fn return_registers(ty: &Type) -> Option<Vec<Register>> {
+    let mut int_registers = vec![Register::Rax, Register::Rdx].into_iter();
+    let mut float_registers = vec![Register::Xmm0, Register::Xmm1].into_iter();
+    let mut registers: Vec<Register> = Vec::new();
+    for class in classify(ty)? {
+        let register = match class {
+            Class::Integer => int_registers.next(),
+            Class::Sse => float_registers.next(),
+        };
+        registers.push(register.expect("More than two eightbytes of a class"));
+    }
+    return Some(registers);
 }
 
 impl fmt::Display for Register {
@@ -17,11 +203,51 @@ impl fmt::Display for Register {
             Register::Rdx => write!(f, "%rdx"),
             Register::Rbp => write!(f, "%rbp"),
             Register::Rsp => write!(f, "%rsp"),
+            Register::Rdi => write!(f, "%rdi"),
+            Register::Rsi => write!(f, "%rsi"),
+            Register::R8 => write!(f, "%r8"),
+            Register::R9 => write!(f, "%r9"),
+            Register::Eax => write!(f, "%eax"),
+            Register::Ecx => write!(f, "%ecx"),
+            Register::Edx => write!(f, "%edx"),
+            Register::Edi => write!(f, "%edi"),
+            Register::Esi => write!(f, "%esi"),
+            Register::R8d => write!(f, "%r8d"),
+            Register::R9d => write!(f, "%r9d"),
+            Register::Ax => write!(f, "%ax"),
+            Register::Cx => write!(f, "%cx"),
+            Register::Dx => write!(f, "%dx"),
+            Register::Di => write!(f, "%di"),
+            Register::Si => write!(f, "%si"),
+            Register::R8w => write!(f, "%r8w"),
+            Register::R9w => write!(f, "%r9w"),
             Register::Al => write!(f, "%al"),
+            Register::Cl => write!(f, "%cl"),
+            Register::Dl => write!(f, "%dl"),
+            Register::Dil => write!(f, "%dil"),
+            Register::Sil => write!(f, "%sil"),
+            Register::R8b => write!(f, "%r8b"),
+            Register::R9b => write!(f, "%r9b"),
+            Register::Xmm0 => write!(f, "%xmm0"),
+            Register::Xmm1 => write!(f, "%xmm1"),
+            Register::Xmm2 => write!(f, "%xmm2"),
+            Register::Xmm3 => write!(f, "%xmm3"),
+            Register::Xmm4 => write!(f, "%xmm4"),
+            Register::Xmm5 => write!(f, "%xmm5"),
+            Register::Xmm6 => write!(f, "%xmm6"),
+            Register::Xmm7 => write!(f, "%xmm7"),
         }
     }
 }
 
+pub struct Immediate(pub i64);
+
+impl fmt::Display for Immediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
 pub struct RegisterOffset {
     pub register: Register,
     pub offset: i64,
@@ -33,179 +259,906 @@ impl fmt::Display for RegisterOffset {
     }
 }
 
+// A piece of a global variable's initial value, in the order they're laid
+// out in memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    // A 1 byte value
+    Byte(i64),
+    // A 2 byte value
+    Short(i64),
+    // A 4 byte value
+    Long(i64),
+    // An 8 byte value
+    Quad(i64),
+    // Some number of zero bytes
+    Zero(i64),
+    // The bytes of a string, with no terminating zero
+    String(Vec<u8>),
+    // The address of a string literal, which is put in read-only memory
+    // with a terminating zero
+    StringAddress(Vec<u8>),
+}
+
+// The bytes as the contents of a quoted assembler string. Anything that
+// isn't printable ASCII is written as an octal escape.
+fn escape_string(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for byte in bytes {
+        match *byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(*byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    return escaped;
+}
+
+// A global variable, addressed relative to the instruction pointer. The
+// symbol is already in the target's naming convention.
+pub struct RipRelative {
+    pub symbol: String,
+}
+
+impl fmt::Display for RipRelative {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(%rip)", self.symbol)
+    }
+}
+
 #[derive(Default)]
 pub struct Clause {
     pub state: ClauseState,
     pub count: i64, 
 }
 
+#[derive(Default)]
 pub enum ClauseState {
+    #[default]
     New,
     Started,
     Ended,
 }
 
-impl Default for ClauseState {
-    fn default() -> Self { ClauseState::New }
-}
-
 impl Clause {
     pub fn start_id(&self) -> String {
-        return format!("_clause_{}", self.count);
+        return format!("clause_{}", self.count);
     }
 
     pub fn end_id(&self) -> String {
-        return format!("_end_{}", self.count);
+        return format!("end_{}", self.count);
+    }
+}
+
+// Labels for a loop being generated, so `break` and `continue` know where
+// to jump
+#[derive(Clone)]
+pub struct Loop {
+    pub count: i64,
+}
+
+impl Loop {
+    pub fn start_id(&self) -> String {
+        return format!("loop_{}", self.count);
+    }
+
+    pub fn continue_id(&self) -> String {
+        return format!("continue_{}", self.count);
+    }
+
+    pub fn break_id(&self) -> String {
+        return format!("break_{}", self.count);
     }
 }
 
+// Label ids from clauses and loops are turned into local labels for the
+// target as they're emitted
 #[derive(Default)]
 pub struct Asm {
+    pub target: Target,
     pub clause_count: i64,
+    pub loop_count: i64,
+    pub loops: Vec<Loop>,
+    // String literals, which are put in read-only memory at the end of the
+    // program
+    pub strings: Vec<Vec<u8>>,
+    // Bytes between the frame pointer and the stack pointer, which calls
+    // need to know to keep the stack 16-byte aligned. Kept up to date by
+    // `push`, `pop`, `allocate_stack` and `free_stack`.
+    pub stack_depth: i64,
+    // What the function being generated returns, and the slot in its frame
+    // holding the address to put it at if it's a struct returned in memory
+    pub return_type: Option<Type>,
+    pub return_address: Option<i64>,
     pub source: String,
 }
 
 impl Asm {
-    pub fn declare_function(&mut self, name: String) {
-        self.source.push_str(
-            &format!("\t.globl\t_{}\n", name)
-        );
+    pub fn declare_function(&mut self, name: &str, is_static: bool) {
+        let symbol = self.target.symbol(name);
+        self.source.push_str("\t.text\n");
+        if !is_static {
+            self.source.push_str(
+                &format!("\t.globl\t{}\n", symbol)
+            );
+        }
+        if self.target.is_elf() {
+            self.source.push_str(
+                &format!("\t.type\t{}, @function\n", symbol)
+            );
+        }
         self.source.push_str(
-            &format!("_{}:\n", name)
+            &format!("{}:\n", symbol)
         );
         // Function prologue (new stack frame)
         self.push(&Register::Rbp);
-        self.mov(&Register::Rsp, &Register::Rbp);
+        self.movq(&Register::Rsp, &Register::Rbp);
+        self.stack_depth = 0;
+    }
+
+    pub fn end_function(&mut self, name: &str) {
+        if self.target.is_elf() {
+            let symbol = self.target.symbol(name);
+            self.source.push_str(
+                &format!("\t.size\t{}, .-{}\n", symbol, symbol)
+            );
+        }
+    }
+
+    // Zero-initialised variables go in .bss, which takes no space in the
+    // object file. `alignment` is a power of two.
+    pub fn declare_variable(&mut self, name: &str, size: i64, alignment: i64, data: &[Data], is_static: bool) {
+        let symbol = self.target.symbol(name);
+        let is_zero = data.iter().all(|data| matches!(data, Data::Zero(_)));
+        let alignment_log2 = alignment.trailing_zeros();
+
+        match (self.target, is_zero) {
+            // Mach-O reserves .bss space with a directive of its own
+            (Target::AppleDarwin, true) => {
+                if !is_static {
+                    self.source.push_str(
+                        &format!("\t.globl\t{}\n", symbol)
+                    );
+                }
+                self.source.push_str(
+                    &format!("\t.zerofill\t__DATA,__bss,{},{},{}\n", symbol, size, alignment_log2)
+                );
+                return;
+            },
+            (_, true) => self.source.push_str("\t.bss\n"),
+            (_, false) => self.source.push_str("\t.data\n"),
+        }
+
+        if !is_static {
+            self.source.push_str(
+                &format!("\t.globl\t{}\n", symbol)
+            );
+        }
+        if self.target.is_elf() {
+            self.source.push_str(
+                &format!("\t.type\t{}, @object\n", symbol)
+            );
+            self.source.push_str(
+                &format!("\t.size\t{}, {}\n", symbol, size)
+            );
+        }
+        self.source.push_str(
+            &format!("\t.p2align\t{}\n", alignment_log2)
+        );
+        self.source.push_str(
+            &format!("{}:\n", symbol)
+        );
+        for data in data {
+            let directive: String;
+            match data {
+                Data::Byte(value) => directive = format!(".byte\t{}", value),
+                Data::Short(value) => directive = format!(".short\t{}", value),
+                Data::Long(value) => directive = format!(".long\t{}", value),
+                Data::Quad(value) => directive = format!(".quad\t{}", value),
+                Data::Zero(size) => directive = format!(".zero\t{}", size),
+                Data::String(bytes) => directive = format!(".ascii\t\"{}\"", escape_string(bytes)),
+                Data::StringAddress(bytes) => directive = format!(".quad\t{}", self.string_literal(bytes).symbol),
+            }
+            self.source.push_str(
+                &format!("\t{}\n", directive)
+            );
+        }
+    }
+
+    // Where a copy of the string will be. It's only written out by
+    // `end_program`.
+    pub fn string_literal(&mut self, bytes: &[u8]) -> RipRelative {
+        let label = self.target.local_label(&format!("string_{}", self.strings.len()));
+        self.strings.push(bytes.to_vec());
+        return RipRelative { symbol: label };
+    }
+
+    // Anything that has to come after all the code
+    pub fn end_program(&mut self) {
+        if !self.strings.is_empty() {
+            match self.target {
+                Target::AppleDarwin => self.source.push_str("\t.section\t__TEXT,__const\n"),
+                Target::LinuxGnu => self.source.push_str("\t.section\t.rodata\n"),
+            }
+        }
+        for (index, bytes) in self.strings.iter().enumerate() {
+            self.source.push_str(
+                &format!("{}:\n", self.target.local_label(&format!("string_{}", index)))
+            );
+            self.source.push_str(
+                &format!("\t.asciz\t\"{}\"\n", escape_string(bytes))
+            );
+        }
+
+        if self.target.is_elf() {
+            // Without the note, the linker assumes the stack needs to be
+            // executable
+            self.source.push_str("\t.section\t.note.GNU-stack,\"\",@progbits\n");
+        }
     }
 
     pub fn function_return(&mut self) {
+        // Whatever follows a return is reached by a jump from elsewhere, so
+        // popping the frame doesn't change the depth it sees
+        let stack_depth = self.stack_depth;
         // Function epilogue (clear stack frame)
-        self.mov(&Register::Rbp, &Register::Rsp);
+        self.movq(&Register::Rbp, &Register::Rsp);
         self.pop(&Register::Rbp);
         // Return
         self.ret();
+        self.stack_depth = stack_depth;
     }
 
-    pub fn mov(&mut self, src: &fmt::Display, dest: &fmt::Display) {
+    pub fn call(&mut self, name: &str) {
         self.source.push_str(
-            &format!("\tmov\t{}, {}\n", src, dest)
+            &format!("\tcall\t{}\n", self.target.call_target(name))
         );
     }
 
-    pub fn push(&mut self, src: &fmt::Display) {
+    pub fn allocate_stack(&mut self, bytes: i64) {
+        self.subq(&Immediate(bytes), &Register::Rsp);
+        self.stack_depth += bytes;
+    }
+
+    pub fn free_stack(&mut self, bytes: i64) {
+        self.addq(&Immediate(bytes), &Register::Rsp);
+        self.stack_depth -= bytes;
+    }
+
+
+    pub fn movq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tpush\t{}\n", src)
+            &format!("\tmovq\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn pop(&mut self, src: &fmt::Display) {
+    pub fn movl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tpop\t{}\n", src)
+            &format!("\tmovl\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn ret(&mut self) {
+    pub fn movb(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            "\tret\n",
+            &format!("\tmovb\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn movw(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovw\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Load any 64-bit immediate, which `movq` can't
+    pub fn movabsq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovabsq\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Sign-extend a byte into a 32-bit register
+    pub fn movsbl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovsbl\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Zero-extend a byte into a 32-bit register
+    pub fn movzbl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovzbl\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Sign-extend 16 bits into a 32-bit register
+    pub fn movswl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovswl\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Zero-extend 16 bits into a 32-bit register
+    pub fn movzwl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovzwl\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Address of the memory operand, without loading anything from it
+    pub fn leaq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tleaq\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Sign-extend a 32-bit register into a 64-bit one
+    pub fn movslq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovslq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addw(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddw\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addb(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddb\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subb(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubb\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subw(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubw\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn imull(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\timull\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn imulq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\timulq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn idivl(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tidivl\t{}\n", src)
+        );
+    }
+
+    pub fn idivq(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tidivq\t{}\n", src)
+        );
+    }
+
+    // Unsigned division of %edx:%eax
+    pub fn divl(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tdivl\t{}\n", src)
+        );
+    }
+
+    // Unsigned division of %rdx:%rax
+    pub fn divq(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tdivq\t{}\n", src)
+        );
+    }
+
+    // Sign-extend %eax into %edx, ahead of a division
+    pub fn cltd(&mut self) {
+        self.source.push_str(
+            "\tcltd\n",
+        );
+    }
+
+    // Sign-extend %rax into %rdx, ahead of a division
+    pub fn cqto(&mut self) {
+        self.source.push_str(
+            "\tcqto\n",
+        );
+    }
+
+    // Copy %rcx eightbytes from the address in %rsi to the one in %rdi
+    pub fn rep_movsq(&mut self) {
+        self.source.push_str(
+            "\trep movsq\n",
+        );
+    }
+
+    // Store %rax to %rcx eightbytes from the address in %rdi
+    pub fn rep_stosq(&mut self) {
+        self.source.push_str(
+            "\trep stosq\n",
+        );
+    }
+
+    pub fn negl(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tnegl\t{}\n", src)
+        );
+    }
+
+    pub fn negq(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tnegq\t{}\n", src)
+        );
+    }
+
+    pub fn notl(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tnotl\t{}\n", src)
+        );
+    }
+
+    pub fn notq(&mut self, src: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tnotq\t{}\n", src)
+        );
+    }
+
+    pub fn andl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tandl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn andq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tandq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn orl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\torl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn orq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\torq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn xorl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\txorl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn andb(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tandb\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn orb(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\torb\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn xorq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\txorq\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Flip the bit numbered `bit`
+    pub fn btcl(&mut self, bit: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tbtcl\t{}, {}\n", bit, dest)
+        );
+    }
+
+    pub fn btcq(&mut self, bit: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tbtcq\t{}, {}\n", bit, dest)
+        );
+    }
+
+    // Shifts take their count in %cl or as an immediate
+    pub fn sall(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsall\t{}, {}\n", count, dest)
+        );
+    }
+
+    pub fn salq(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsalq\t{}, {}\n", count, dest)
+        );
+    }
+
+    pub fn sarl(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsarl\t{}, {}\n", count, dest)
+        );
+    }
+
+    pub fn sarq(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsarq\t{}, {}\n", count, dest)
+        );
+    }
+
+    // Logical shift right, which shifts in zeros rather than copies of the
+    // sign bit
+    pub fn shrl(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tshrl\t{}, {}\n", count, dest)
+        );
+    }
+
+    pub fn shrq(&mut self, count: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tshrq\t{}, {}\n", count, dest)
+        );
+    }
+
+    pub fn cmpl(&mut self, src_a: &dyn fmt::Display, src_b: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcmpl\t{}, {}\n", src_a, src_b)
+        );
+    }
+
+    pub fn cmpq(&mut self, src_a: &dyn fmt::Display, src_b: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcmpq\t{}, {}\n", src_a, src_b)
+        );
+    }
+
+    pub fn testq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\ttestq\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Scalar floating point moves, between %xmm registers and memory
+    pub fn movss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn movsd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovsd\t{}, {}\n", src, dest)
+        );
+    }
+
+    // The low 32 bits of a general purpose register to or from an %xmm one.
+    // `movq` moves all 64.
+    pub fn movd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovd\t{}, {}\n", src, dest)
+        );
+    }
+
+    // A whole %xmm register to another
+    pub fn movaps(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmovaps\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn addsd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\taddsd\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn subsd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsubsd\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn mulss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmulss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn mulsd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tmulsd\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn divss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tdivss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn divsd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tdivsd\t{}, {}\n", src, dest)
+        );
+    }
+
+    // Compare floating point values, setting the flags like an unsigned
+    // comparison. Either being NaN sets the parity flag.
+    pub fn ucomiss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tucomiss\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn ucomisd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tucomisd\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn xorps(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\txorps\t{}, {}\n", src, dest)
+        );
+    }
+
+    // `float` to `double` and back
+    pub fn cvtss2sd(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcvtss2sd\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn cvtsd2ss(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcvtsd2ss\t{}, {}\n", src, dest)
+        );
+    }
+
+    // A signed 32 or 64-bit integer to floating point
+    pub fn cvtsi2ssl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcvtsi2ssl\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn cvtsi2ssq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcvtsi2ssq\t{}, {}\n", src, dest)
+        );
+    }
+
+    pub fn cvtsi2sdl(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tcvtsi2sdl\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn add(&mut self, src: &fmt::Display, dest: &fmt::Display) {
+    pub fn cvtsi2sdq(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tadd\t{}, {}\n", src, dest)
+            &format!("\tcvtsi2sdq\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn sub(&mut self, src: &fmt::Display, dest: &fmt::Display) {
+    // Floating point to a signed integer the size of `dest`, truncating
+    // towards zero
+    pub fn cvttss2si(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tsub\t{}, {}\n", src, dest)
+            &format!("\tcvttss2si\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn imul(&mut self, src: &fmt::Display, dest: &fmt::Display) {
+    pub fn cvttsd2si(&mut self, src: &dyn fmt::Display, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\timul\t{}, {}\n", src, dest)
+            &format!("\tcvttsd2si\t{}, {}\n", src, dest)
         );
     }
 
-    pub fn idiv(&mut self, src: &fmt::Display) {
+    pub fn push(&mut self, src: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tidiv\t{}\n", src)
+            &format!("\tpush\t{}\n", src)
         );
+        self.stack_depth += 8;
     }
 
-    pub fn neg(&mut self, src: &fmt::Display) {
+    pub fn pop(&mut self, src: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tneg\t{}\n", src)
+            &format!("\tpop\t{}\n", src)
         );
+        self.stack_depth -= 8;
     }
 
-    pub fn cmp(&mut self, src_a: &fmt::Display, src_b: &fmt::Display) {
+    pub fn ret(&mut self) {
         self.source.push_str(
-            &format!("\tcmp\t{}, {}\n", src_a, src_b)
+            "\tret\n",
         );
     }
 
-    pub fn sete(&mut self, dest: &fmt::Display) {
+
+
+
+
+
+
+    pub fn sete(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsete\t{}\n", dest)
         );
     }
 
-    pub fn setne(&mut self, dest: &fmt::Display) {
+    pub fn setne(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsetne\t{}\n", dest)
         );
     }
 
-    pub fn setl(&mut self, dest: &fmt::Display) {
+    pub fn setl(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsetl\t{}\n", dest)
         );
     }
 
-    pub fn setle(&mut self, dest: &fmt::Display) {
+    pub fn setle(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsetle\t{}\n", dest)
         );
     }
 
-    pub fn setg(&mut self, dest: &fmt::Display) {
+    pub fn setg(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsetg\t{}\n", dest)
         );
     }
 
-    pub fn setge(&mut self, dest: &fmt::Display) {
+    pub fn setge(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
             &format!("\tsetge\t{}\n", dest)
         );
     }
 
-    pub fn not(&mut self, src: &fmt::Display) {
+    pub fn setb(&mut self, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsetb\t{}\n", dest)
+        );
+    }
+
+    pub fn setbe(&mut self, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsetbe\t{}\n", dest)
+        );
+    }
+
+    pub fn seta(&mut self, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tseta\t{}\n", dest)
+        );
+    }
+
+    pub fn setae(&mut self, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsetae\t{}\n", dest)
+        );
+    }
+
+
+    // Set if a floating point comparison was unordered, or not
+    pub fn setp(&mut self, dest: &dyn fmt::Display) {
         self.source.push_str(
-            &format!("\tnot\t{}\n", src)
+            &format!("\tsetp\t{}\n", dest)
+        );
+    }
+
+    pub fn setnp(&mut self, dest: &dyn fmt::Display) {
+        self.source.push_str(
+            &format!("\tsetnp\t{}\n", dest)
         );
     }
 
     pub fn jmp(&mut self, clause_id: String) {
         self.source.push_str(
-            &format!("\tjmp\t{}\n", clause_id)
+            &format!("\tjmp\t{}\n", self.target.local_label(&clause_id))
         );
     }
 
     pub fn je(&mut self, clause_id: String) {
         self.source.push_str(
-            &format!("\tje\t{}\n", clause_id)
+            &format!("\tje\t{}\n", self.target.local_label(&clause_id))
         );
     }
 
     pub fn jne(&mut self, clause_id: String) {
         self.source.push_str(
-            &format!("\tjne\t{}\n", clause_id)
+            &format!("\tjne\t{}\n", self.target.local_label(&clause_id))
+        );
+    }
+
+    // Jump if the result was negative
+    pub fn js(&mut self, clause_id: String) {
+        self.source.push_str(
+            &format!("\tjs\t{}\n", self.target.local_label(&clause_id))
+        );
+    }
+
+    // Jump if above or equal, as unsigned
+    pub fn jae(&mut self, clause_id: String) {
+        self.source.push_str(
+            &format!("\tjae\t{}\n", self.target.local_label(&clause_id))
+        );
+    }
+
+    pub fn label(&mut self, id: String) {
+        self.source.push_str(
+            &format!("{}:\n", self.target.local_label(&id))
         );
     }
 
+    // `stack_offset` is where the stack pointer is, relative to the frame,
+    // at the top of each iteration
+    pub fn enter_loop(&mut self) -> Loop {
+        let new_loop = Loop { count: self.loop_count };
+        self.loop_count += 1;
+        self.loops.push(new_loop.clone());
+        return new_loop;
+    }
+
+    pub fn exit_loop(&mut self) {
+        self.loops.pop().expect("No loop to exit");
+    }
+
+    pub fn current_loop(&self) -> Option<&Loop> {
+        return self.loops.last();
+    }
+
     pub fn new_clause(&mut self) -> Clause {
         let clause = Clause { count: self.clause_count, ..Default::default() };
         self.clause_count += 1;
@@ -220,7 +1173,7 @@ impl Asm {
         }
 
         self.source.push_str(
-            &format!("{}:\n", clause.start_id())
+            &format!("{}:\n", self.target.local_label(&clause.start_id()))
         );
         clause.state = ClauseState::Started;
     }
@@ -233,7 +1186,7 @@ impl Asm {
         }
 
         self.source.push_str(
-            &format!("{}:\n", clause.end_id())
+            &format!("{}:\n", self.target.local_label(&clause.end_id()))
         );
         clause.state = ClauseState::Ended;
     }
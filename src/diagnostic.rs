@@ -0,0 +1,135 @@
+use std::fmt;
+use span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// Boxed so that a `Result<T, Diagnostic>` is no bigger than it has to be when
+// nothing went wrong
+#[derive(Debug, Clone)]
+pub struct Diagnostic(Box<Details>);
+
+#[derive(Debug, Clone)]
+struct Details {
+    severity: Severity,
+    message: String,
+    span: Span,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error<S: Into<String>>(message: S, span: Span) -> Diagnostic {
+        return Diagnostic(Box::new(Details {
+            severity: Severity::Error,
+            message: message.into(),
+            span: span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }));
+    }
+
+    pub fn warning<S: Into<String>>(message: S, span: Span) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(message, span);
+        diagnostic.0.severity = Severity::Warning;
+        return diagnostic;
+    }
+
+    pub fn is_error(&self) -> bool {
+        return self.0.severity == Severity::Error;
+    }
+
+    pub fn span(&self) -> &Span {
+        return &self.0.span;
+    }
+
+    pub fn with_label<S: Into<String>>(mut self, span: Span, message: S) -> Diagnostic {
+        self.0.labels.push(Label { span: span, message: message.into() });
+        return self;
+    }
+
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Diagnostic {
+        self.0.notes.push(note.into());
+        return self;
+    }
+
+    // Render in the style of rustc, e.g.
+    //
+    //   error: Expecting ';', found 'return'
+    //    --> main.c:3:3
+    //     |
+    //   3 |   return a;
+    //     |   ^^^^^^
+    //
+    // `source` is the text the spans' byte offsets point into.
+    pub fn render(&self, source: &str) -> String {
+        let details = &self.0;
+        let mut output = format!("{}: {}\n", details.severity, details.message);
+
+        let gutter_width = details.labels.iter()
+            .map(|label| label.span.line)
+            .chain(Some(details.span.line))
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+        let gutter = " ".repeat(gutter_width);
+
+        output.push_str(&format!("{}--> {}\n", gutter, details.span));
+        output.push_str(&format!("{} |\n", gutter));
+        output.push_str(&snippet(source, &details.span, '^', "", gutter_width));
+        for label in &details.labels {
+            output.push_str(&format!("{} |\n", gutter));
+            output.push_str(&snippet(source, &label.span, '-', &label.message, gutter_width));
+        }
+        for note in &details.notes {
+            output.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+
+        return output;
+    }
+}
+
+// The source line containing `span`, followed by an underline of the span
+fn snippet(source: &str, span: &Span, marker: char, message: &str, gutter_width: usize) -> String {
+    let offset = span.offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let line_end = source[offset..].find('\n').map(|index| offset + index).unwrap_or(source.len());
+    let line = &source[line_start..line_end];
+
+    // Keep tabs in the padding so the underline lines up with the source
+    let padding: String = source[line_start..offset].chars()
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+    let underline_length = source[offset..(offset + span.length).min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let underline: String = std::iter::repeat_n(marker, underline_length).collect();
+
+    let mut output = format!("{:>width$} | {}\n", span.line, line, width = gutter_width);
+    output.push_str(&format!("{} | {}{}", " ".repeat(gutter_width), padding, underline));
+    if !message.is_empty() {
+        output.push_str(&format!(" {}", message));
+    }
+    output.push('\n');
+    return output;
+}
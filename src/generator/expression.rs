@@ -1,166 +1,383 @@
+use std::fmt;
 use asm::Asm;
 use generator;
-use generator::term;
-use parser::StackFrame;
-use asm::Register::{Rax, Rcx, Al, Rbp};
-use asm::RegisterOffset;
+use generator::factor;
+use sema::SymbolTable;
+use asm::{Immediate, RegisterOffset};
+use asm::Register::{Rax, Rcx, Rdx, Rsp, Eax, Ecx, Edx, Al, Cl, Xmm0, Xmm1};
+use types::Type;
 use parser::expression::{
     Expression,
-    LogicalOrExpression,
-    LogicalOrOperator,
-    LogicalAndExpression,
-    LogicalAndOperator,
-    EqualityExpression,
-    EqualityOperator,
-    RelationalExpression,
-    RelationalOperator,
-    AdditiveExpression,
-    AdditiveOperator,
+    ExpressionKind,
+    ConditionalExpression,
+    BinaryExpression,
+    BinaryOperator,
     Assignment,
 };
 
-pub fn asm(asm: &mut Asm, expression: Expression, stack_frame: &StackFrame) {
-    match expression {
-        Expression::Assignment(assignment) => {
-            assignment_asm(asm, assignment, stack_frame);
+pub fn asm(asm: &mut Asm, expression: Expression, symbols: &SymbolTable) {
+    match expression.kind {
+        ExpressionKind::Assignment(assignment) => {
+            assignment_asm(asm, *assignment, symbols);
         },
-        Expression::LogicalOrExpression(expression) => {
-            logical_or_asm(asm, expression, stack_frame);
+        ExpressionKind::Conditional(conditional) => {
+            conditional_asm(asm, *conditional, symbols);
+        },
+        ExpressionKind::Binary(binary) => {
+            binary_asm(asm, *binary, symbols);
+        },
+        ExpressionKind::Factor(factor) => {
+            factor::asm(asm, factor, symbols);
+        },
+    }
+}
+
+fn conditional_asm(asm: &mut Asm, conditional: ConditionalExpression, symbols: &SymbolTable) {
+    let mut clause = asm.new_clause();
+
+    condition_asm(asm, conditional.condition, symbols);
+    asm.je(clause.start_id());
+    generator::expression::asm(asm, conditional.expression, symbols);
+    asm.jmp(clause.end_id());
+
+    asm.start_clause(&mut clause);
+    generator::expression::asm(asm, conditional.else_expression, symbols);
+    asm.end_clause(&mut clause);
+}
+
+fn binary_asm(asm: &mut Asm, binary: BinaryExpression, symbols: &SymbolTable) {
+    match binary.operator {
+        BinaryOperator::Comma => {
+            generator::expression::asm(asm, binary.left, symbols);
+            generator::expression::asm(asm, binary.right, symbols);
+        },
+        BinaryOperator::LogicalOr => {
+            let mut clause = asm.new_clause();
+
+            // e1
+            condition_asm(asm, binary.left, symbols);
+            asm.je(clause.start_id());
+            asm.movl(&Immediate(1), &Eax);
+            asm.jmp(clause.end_id());
+
+            asm.start_clause(&mut clause);
+            // e2
+            condition_asm(asm, binary.right, symbols);
+            asm.movl(&Immediate(0), &Eax);
+            asm.setne(&Al);
+
+            asm.end_clause(&mut clause);
+        },
+        BinaryOperator::LogicalAnd => {
+            let mut clause = asm.new_clause();
+
+            // e1
+            condition_asm(asm, binary.left, symbols);
+            asm.jne(clause.start_id());
+            asm.jmp(clause.end_id());
+
+            asm.start_clause(&mut clause);
+            // e2
+            condition_asm(asm, binary.right, symbols);
+            asm.movl(&Immediate(0), &Eax);
+            asm.setne(&Al);
+
+            asm.end_clause(&mut clause);
+        },
+        operator => {
+            let left_type = binary.left.ty().clone();
+            let right_type = binary.right.ty().clone();
+            generator::expression::asm(asm, binary.left, symbols);
+            generator::push(asm, &left_type);
+            generator::expression::asm(asm, binary.right, symbols);
+            asm.pop(&Rcx);
+            operation_asm(asm, operator, &left_type, &right_type);
         },
     }
 }
 
-pub fn logical_or_asm(asm: &mut Asm, expression: LogicalOrExpression, stack_frame: &StackFrame) {
-    // e1
-    logical_and_asm(asm, expression.expression, stack_frame);
-
-    for binary_expression in expression.binary_expressions {
-        match binary_expression.operator {
-            LogicalOrOperator::Or => {
-                let mut clause = asm.new_clause();
-
-		asm.cmp(&0, &Rax);
-                asm.je(clause.start_id());
-                asm.mov(&1, &Rax);
-                asm.jmp(clause.end_id());
-
-                asm.start_clause(&mut clause);
-                // e2
-                logical_and_asm(asm, binary_expression.right_expression, stack_frame);
-                asm.cmp(&0, &Rax);
-                asm.mov(&0, &Rax);
-                asm.setne(&Al);
-
-                asm.end_clause(&mut clause);
-            },
-        }
+// Evaluate a condition, setting the flags from comparing it with zero
+pub fn condition_asm(asm: &mut Asm, expression: Expression, symbols: &SymbolTable) {
+    let ty = expression.ty().clone();
+    generator::expression::asm(asm, expression, symbols);
+    generator::compare_with_zero(asm, &ty);
+}
+
+// Combine the left operand in %rcx with the right one in `rax`, leaving the
+// result in `rax` for its type. Semantic analysis has converted both
+// operands to the type the operation is done in, unless it's arithmetic on
+// a pointer.
+fn operation_asm(asm: &mut Asm, operator: BinaryOperator, left_type: &Type, right_type: &Type) {
+    let is_arithmetic = operator == BinaryOperator::Addition || operator == BinaryOperator::Subtraction;
+    if is_arithmetic && (left_type.is_pointer() || right_type.is_pointer()) {
+        pointer_operation_asm(asm, operator, left_type, right_type);
+    } else if left_type.is_floating() {
+        floating_operation_asm(asm, operator, left_type);
+    } else {
+        binary_operation_asm(asm, operator, left_type);
+    }
+}
+
+// Adding an integer to a pointer moves it on by that many elements, so the
+// integer is scaled by the element size first. Subtracting two pointers
+// divides the difference in bytes back down to elements.
+fn pointer_operation_asm(asm: &mut Asm, operator: BinaryOperator, left_type: &Type, right_type: &Type) {
+    match operator {
+        BinaryOperator::Addition if left_type.is_pointer() => {
+            scale_asm(asm, &Rax, left_type);
+            asm.addq(&Rcx, &Rax);
+        },
+        BinaryOperator::Addition => {
+            scale_asm(asm, &Rcx, right_type);
+            asm.addq(&Rcx, &Rax);
+        },
+        BinaryOperator::Subtraction if right_type.is_pointer() => {
+            let size = left_type.pointee().expect("Subtracting a pointer from an integer").size();
+            asm.subq(&Rax, &Rcx);
+            asm.movq(&Rcx, &Rax);
+            asm.cqto();
+            asm.movq(&Immediate(size), &Rcx);
+            asm.idivq(&Rcx);
+        },
+        BinaryOperator::Subtraction => {
+            scale_asm(asm, &Rax, left_type);
+            asm.subq(&Rax, &Rcx);
+            asm.movq(&Rcx, &Rax);
+        },
+        _ => panic!("{:?} isn't pointer arithmetic", operator),
+    }
+}
+
+// Multiply a `long` index by the size of what `pointer` points to
+pub fn scale_asm(asm: &mut Asm, register: &dyn fmt::Display, pointer: &Type) {
+    let size = pointer.pointee().expect("Scaling by a non-pointer").size();
+    asm.imulq(&Immediate(size), register);
+}
+
+// Combine the left operand in %rcx with the right one in %rax, both of
+// type `ty`, leaving the result in %rax. Only 8 byte values need all of the
+// registers. The logical operators and comma need their operands evaluated
+// in order, so they're generated by `binary_asm`.
+fn binary_operation_asm(asm: &mut Asm, operator: BinaryOperator, ty: &Type) {
+    let is_quad = ty.size() == 8;
+    let is_signed = ty.is_signed();
+
+    match operator {
+        BinaryOperator::Comma | BinaryOperator::LogicalOr | BinaryOperator::LogicalAnd => {
+            panic!("{:?} has no single instruction", operator);
+        },
+        BinaryOperator::BitwiseOr if is_quad => asm.orq(&Rcx, &Rax),
+        BinaryOperator::BitwiseOr => asm.orl(&Ecx, &Eax),
+        BinaryOperator::BitwiseXor if is_quad => asm.xorq(&Rcx, &Rax),
+        BinaryOperator::BitwiseXor => asm.xorl(&Ecx, &Eax),
+        BinaryOperator::BitwiseAnd if is_quad => asm.andq(&Rcx, &Rax),
+        BinaryOperator::BitwiseAnd => asm.andl(&Ecx, &Eax),
+        // Unsigned values, including pointers, are compared with the
+        // above/below set instructions
+        BinaryOperator::Equal => {
+            comparison_asm(asm, ty);
+            asm.sete(&Al);
+        },
+        BinaryOperator::NotEqual => {
+            comparison_asm(asm, ty);
+            asm.setne(&Al);
+        },
+        BinaryOperator::LessThan => {
+            comparison_asm(asm, ty);
+            if is_signed { asm.setl(&Al) } else { asm.setb(&Al) }
+        },
+        BinaryOperator::LessThanOrEqual => {
+            comparison_asm(asm, ty);
+            if is_signed { asm.setle(&Al) } else { asm.setbe(&Al) }
+        },
+        BinaryOperator::GreaterThan => {
+            comparison_asm(asm, ty);
+            if is_signed { asm.setg(&Al) } else { asm.seta(&Al) }
+        },
+        BinaryOperator::GreaterThanOrEqual => {
+            comparison_asm(asm, ty);
+            if is_signed { asm.setge(&Al) } else { asm.setae(&Al) }
+        },
+        BinaryOperator::LeftShift => {
+            swap_operands_asm(asm);
+            if is_quad { asm.salq(&Cl, &Rax) } else { asm.sall(&Cl, &Eax) }
+        },
+        // Unsigned values shift in zeros at the top
+        BinaryOperator::RightShift => {
+            swap_operands_asm(asm);
+            match (is_quad, is_signed) {
+                (true, true) => asm.sarq(&Cl, &Rax),
+                (true, false) => asm.shrq(&Cl, &Rax),
+                (false, true) => asm.sarl(&Cl, &Eax),
+                (false, false) => asm.shrl(&Cl, &Eax),
+            }
+        },
+        BinaryOperator::Addition if is_quad => asm.addq(&Rcx, &Rax),
+        BinaryOperator::Addition => asm.addl(&Ecx, &Eax),
+        BinaryOperator::Subtraction => {
+            if is_quad { asm.subq(&Rax, &Rcx) } else { asm.subl(&Eax, &Ecx) }
+            asm.movq(&Rcx, &Rax);
+        },
+        // The low half of the product is the same whether it's signed or not
+        BinaryOperator::Multiplication if is_quad => asm.imulq(&Rcx, &Rax),
+        BinaryOperator::Multiplication => asm.imull(&Ecx, &Eax),
+        BinaryOperator::Division => {
+            division_asm(asm, ty);
+        },
+        BinaryOperator::Modulo => {
+            division_asm(asm, ty);
+            asm.movq(&Rdx, &Rax);
+        },
     }
 }
 
-fn logical_and_asm(asm: &mut Asm, expression: LogicalAndExpression, stack_frame: &StackFrame) {
-    // e1
-    equality_asm(asm, expression.expression, stack_frame);
-
-    for binary_expression in expression.binary_expressions {
-        match binary_expression.operator {
-            LogicalAndOperator::And => {
-                let mut clause = asm.new_clause();
-
-		asm.cmp(&0, &Rax);
-                asm.jne(clause.start_id());
-                asm.jmp(clause.end_id());
-
-                asm.start_clause(&mut clause);
-                // e2
-                equality_asm(asm, binary_expression.right_expression, stack_frame);
-                asm.cmp(&0, &Rax);
-                asm.mov(&0, &Rax);
-                asm.setne(&Al);
-
-                asm.end_clause(&mut clause);
-            },
-        }
+// Combine the left operand in %rcx with the right one in %xmm0, both of
+// type `ty`, leaving the result of arithmetic in %xmm0. The left one is
+// moved over to %xmm1 first. Comparisons leave 0 or 1 in %eax, as they do
+// for integers.
+fn floating_operation_asm(asm: &mut Asm, operator: BinaryOperator, ty: &Type) {
+    let is_double = *ty == Type::Double;
+    asm.movq(&Rcx, &Xmm1);
+
+    match operator {
+        BinaryOperator::Addition => if is_double { asm.addsd(&Xmm1, &Xmm0) } else { asm.addss(&Xmm1, &Xmm0) },
+        BinaryOperator::Subtraction => {
+            if is_double { asm.subsd(&Xmm0, &Xmm1) } else { asm.subss(&Xmm0, &Xmm1) }
+            asm.movaps(&Xmm1, &Xmm0);
+        },
+        BinaryOperator::Multiplication => if is_double { asm.mulsd(&Xmm1, &Xmm0) } else { asm.mulss(&Xmm1, &Xmm0) },
+        BinaryOperator::Division => {
+            if is_double { asm.divsd(&Xmm0, &Xmm1) } else { asm.divss(&Xmm0, &Xmm1) }
+            asm.movaps(&Xmm1, &Xmm0);
+        },
+        // NaN isn't equal to anything, even itself
+        BinaryOperator::Equal => {
+            floating_comparison_asm(asm, ty, false);
+            asm.sete(&Al);
+            asm.setnp(&Cl);
+            asm.andb(&Cl, &Al);
+        },
+        BinaryOperator::NotEqual => {
+            floating_comparison_asm(asm, ty, false);
+            asm.setne(&Al);
+            asm.setp(&Cl);
+            asm.orb(&Cl, &Al);
+        },
+        BinaryOperator::GreaterThan => {
+            floating_comparison_asm(asm, ty, false);
+            asm.seta(&Al);
+        },
+        BinaryOperator::GreaterThanOrEqual => {
+            floating_comparison_asm(asm, ty, false);
+            asm.setae(&Al);
+        },
+        BinaryOperator::LessThan => {
+            floating_comparison_asm(asm, ty, true);
+            asm.seta(&Al);
+        },
+        BinaryOperator::LessThanOrEqual => {
+            floating_comparison_asm(asm, ty, true);
+            asm.setae(&Al);
+        },
+        _ => panic!("{:?} doesn't take floating point operands", operator),
     }
 }
 
-fn equality_asm(asm: &mut Asm, expression: EqualityExpression, stack_frame: &StackFrame) {
-    relational_asm(asm, expression.expression, stack_frame);
-
-    for binary_expression in expression.binary_expressions {
-        asm.push(&Rax);
-        relational_asm(asm, binary_expression.right_expression, stack_frame);
-        asm.pop(&Rcx);
-        asm.cmp(&Rax, &Rcx);
-        asm.mov(&0, &Rax);
-
-        match binary_expression.operator {
-            EqualityOperator::Equal => {
-                asm.sete(&Al);
-            },
-            EqualityOperator::NotEqual => {
-                asm.setne(&Al);
-            },
-        }
+// Compare the left operand in %xmm1 with the right one in %xmm0, clearing
+// %eax ready for a set instruction. Either being NaN sets the carry flag as
+// if the left one was below, so `<` and `<=` swap the operands to use
+// `seta` and `setae` like `>` and `>=`, which come out false for NaN.
+fn floating_comparison_asm(asm: &mut Asm, ty: &Type, is_swapped: bool) {
+    let (left, right) = if is_swapped { (&Xmm0, &Xmm1) } else { (&Xmm1, &Xmm0) };
+    if *ty == Type::Double { asm.ucomisd(right, left) } else { asm.ucomiss(right, left) }
+    asm.movl(&Immediate(0), &Eax);
+}
+
+// Compare the operands, clearing %eax ready for a set instruction
+fn comparison_asm(asm: &mut Asm, ty: &Type) {
+    match ty.size() {
+        8 => asm.cmpq(&Rax, &Rcx),
+        _ => asm.cmpl(&Eax, &Ecx),
     }
+    asm.movl(&Immediate(0), &Eax);
+}
+
+// Put the left operand in %rax and the right one in %rcx, as shifts need
+// their count in %cl
+fn swap_operands_asm(asm: &mut Asm) {
+    asm.movq(&Rcx, &Rdx);
+    asm.movq(&Rax, &Rcx);
+    asm.movq(&Rdx, &Rax);
 }
 
-fn relational_asm(asm: &mut Asm, expression: RelationalExpression, stack_frame: &StackFrame) {
-    additive_asm(asm, expression.expression, stack_frame);
-
-    for binary_expression in expression.binary_expressions {
-        asm.push(&Rax);
-        additive_asm(asm, binary_expression.right_expression, stack_frame);
-        asm.pop(&Rcx);
-        asm.cmp(&Rax, &Rcx);
-        asm.mov(&0, &Rax);
-
-        match binary_expression.operator {
-            RelationalOperator::LessThan => {
-                asm.setl(&Al);
-            },
-            RelationalOperator::LessThanOrEqual => {
-                asm.setle(&Al);
-            },
-            RelationalOperator::GreaterThan => {
-                asm.setg(&Al);
-            },
-            RelationalOperator::GreaterThanOrEqual => {
-                asm.setge(&Al);
-            },
-        }
+// Divide the left operand in %rcx by the right one in %rax, leaving the
+// quotient in %rax and the remainder in %rdx. Both round towards zero, as
+// in C. Signed division takes the dividend sign-extended into %rdx, and
+// unsigned division takes it with %rdx cleared.
+fn division_asm(asm: &mut Asm, ty: &Type) {
+    swap_operands_asm(asm);
+    match (ty.size(), ty.is_signed()) {
+        (8, true) => {
+            asm.cqto();
+            asm.idivq(&Rcx);
+        },
+        (8, false) => {
+            asm.xorl(&Edx, &Edx);
+            asm.divq(&Rcx);
+        },
+        (_, true) => {
+            asm.cltd();
+            asm.idivl(&Ecx);
+        },
+        (_, false) => {
+            asm.xorl(&Edx, &Edx);
+            asm.divl(&Ecx);
+        },
     }
 }
 
-fn additive_asm(asm: &mut Asm, expression: AdditiveExpression, stack_frame: &StackFrame) {
-    term::asm(asm, expression.term, stack_frame);
-
-    for binary_term in expression.binary_terms {
-        asm.push(&Rax);
-        term::asm(asm, binary_term.right_term, stack_frame);
-        asm.pop(&Rcx);
-
-        match binary_term.operator {
-            AdditiveOperator::Addition => {
-                asm.add(&Rcx, &Rax);
-            },
-            AdditiveOperator::Subtraction => {
-                asm.sub(&Rax, &Rcx);
-                asm.mov(&Rcx, &Rax);
-            },
-        }
+// A variable's address is known up front. Anything else, like `*pointer`,
+// has its address worked out first and kept on the stack while the right
+// hand side is evaluated.
+fn assignment_asm(asm: &mut Asm, assignment: Assignment, symbols: &SymbolTable) {
+    let ty = assignment.target.ty().clone();
+    let value_type = assignment.expression.ty().clone();
+
+    match factor::var_operand(&asm.target, &assignment.target, symbols) {
+        Some(address) => {
+            generator::expression::asm(asm, assignment.expression, symbols);
+            if let Some(operator) = assignment.operator {
+                compound_asm(asm, operator, &ty, &value_type, &*address);
+            }
+            generator::store(asm, &ty, &*address);
+        },
+        None => {
+            factor::address_asm(asm, assignment.target, symbols);
+            asm.push(&Rax);
+            generator::expression::asm(asm, assignment.expression, symbols);
+
+            if let Some(operator) = assignment.operator {
+                asm.movq(&RegisterOffset { offset: 0, register: Rsp }, &Rcx);
+                compound_asm(asm, operator, &ty, &value_type, &RegisterOffset { offset: 0, register: Rcx });
+            }
+            asm.pop(&Rdx);
+            generator::store(asm, &ty, &RegisterOffset { offset: 0, register: Rdx });
+        },
     }
 }
 
-fn assignment_asm(asm: &mut Asm, assignment: Box<Assignment>, stack_frame: &StackFrame) {
-    let offset = stack_frame.vars.get(&assignment.var.name)
-        .expect(&format!(
-            "Var '{}' has not been declared",
-            &assignment.var.name,
-        ));
-    generator::expression::asm(asm, assignment.expression, stack_frame);
-    let reg_offset = RegisterOffset {
-        offset: *offset,
-        register: Rbp,
-    };
-    asm.mov(&Rax, &reg_offset);
+// Combine the target's current value with the right hand side in
+// `rax(value_type)`, which semantic analysis has converted to the type the
+// operation is done in. The target is converted to that type too, and the
+// result back to the target's type, ready to be stored.
+fn compound_asm(asm: &mut Asm, operator: BinaryOperator, ty: &Type, value_type: &Type, address: &dyn fmt::Display) {
+    let operation_type = if ty.is_pointer() { ty } else { value_type };
+
+    asm.movq(&generator::value_register(value_type), &Rdx);
+    generator::load(asm, ty, address, &generator::rax(ty));
+    generator::convert(asm, ty, operation_type);
+    asm.movq(&generator::value_register(operation_type), &Rcx);
+    asm.movq(&Rdx, &generator::value_register(value_type));
+    operation_asm(asm, operator, operation_type, value_type);
+    generator::convert(asm, operation_type, ty);
 }
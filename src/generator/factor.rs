@@ -1,53 +1,346 @@
+use std::fmt;
 use asm::Asm;
-use asm::Register::{Rax, Al, Rbp};
-use asm::RegisterOffset;
+use asm::{Immediate, RegisterOffset};
+use asm::Register::{Rax, Rcx, Rdx, Rdi, Rbp, Rsp, Eax, Al, Xmm0, Xmm1, Xmm2};
+use asm::{ARGUMENT_REGISTERS, FLOAT_ARGUMENT_REGISTERS, Class, argument_registers, return_registers};
+use generator;
 use generator::factor;
-use parser::StackFrame;
+use sema::SymbolTable;
 use generator::expression;
-use parser::factor::Factor;
+use parser::factor::{Factor, FactorKind, FunctionCall, IncrementOperator, Subscript, MemberAccess};
+use parser::expression::ExpressionKind;
+use target::Target;
+use types::Type;
 use parser::factor::UnaryOperator;
 use parser::factor::UnaryOperation;
 
-pub fn asm(asm: &mut Asm, factor: Factor, stack_frame: &StackFrame) {
-    match factor {
-        Factor::Expression(expression) => {
-            expression::asm(asm, *expression, stack_frame);
+pub fn asm(asm: &mut Asm, factor: Factor, symbols: &SymbolTable) {
+    let ty = factor.ty().clone();
+
+    // An array's value is a pointer to its first element
+    if ty.is_array() {
+        address_asm(asm, factor, symbols);
+        return;
+    }
+
+    match factor.kind {
+        FactorKind::Expression(expression) => {
+            expression::asm(asm, *expression, symbols);
+        },
+        FactorKind::UnaryOperation(operation) => {
+            unary_operation_asm(asm, *operation, &ty, symbols);
+        },
+        // `movq` only takes a sign-extended 32-bit immediate
+        FactorKind::Constant(value, _) => match ty.size() {
+            8 if value != i64::from(value as i32) => asm.movabsq(&Immediate(value), &Rax),
+            8 => asm.movq(&Immediate(value), &Rax),
+            _ => asm.movl(&Immediate(value), &Eax),
+        },
+        FactorKind::FloatingConstant(value, _) => {
+            generator::load_floating_constant(asm, &ty, value, &Xmm0);
+        },
+        FactorKind::StringLiteral(_) => panic!("String literal isn't an array"),
+        FactorKind::Identifier(var) => {
+            let address = generator::var_address(&asm.target, symbols, &var);
+            generator::load(asm, &ty, &*address, &generator::rax(&ty));
+        },
+        // Whatever was returned is cut down to the return type, as the
+        // function may have left more in %eax
+        FactorKind::FunctionCall(call) => {
+            function_call_asm(asm, call, &ty, symbols);
+            if !ty.is_struct() {
+                generator::truncate(asm, &ty);
+            }
+        },
+        FactorKind::PrefixIncrement(operation) => {
+            let operator = operation.operator;
+            let address = lvalue_operand(asm, operation.operand, symbols);
+            increment_asm(asm, &operator, &ty, &*address);
+            generator::load(asm, &ty, &*address, &generator::rax(&ty));
         },
-        Factor::UnaryOperation(operation) => {
-            unary_operation_asm(asm, *operation, stack_frame);
+        FactorKind::PostfixIncrement(operation) => {
+            let operator = operation.operator;
+            let address = lvalue_operand(asm, operation.operand, symbols);
+            generator::load(asm, &ty, &*address, &generator::rax(&ty));
+            increment_asm(asm, &operator, &ty, &*address);
         },
-        Factor::Constant(value) => {
-            asm.mov(&value, &Rax);
+        FactorKind::Subscript(subscript) => {
+            subscript_address_asm(asm, *subscript, symbols);
+            generator::load(asm, &ty, &RegisterOffset { offset: 0, register: Rax }, &generator::rax(&ty));
         },
-        Factor::Identifier(name) => {
-            let offset = stack_frame.vars.get(&name)
-                .expect(&format!(
-                    "Var '{}' has not been declared",
-                    name,
-                ));
-            let reg_offset = RegisterOffset {
-                offset: *offset,
-                register: Rbp,
-            };
-            asm.mov(&reg_offset, &Rax);
+        FactorKind::Member(access) => {
+            member_address_asm(asm, *access, symbols);
+            generator::load(asm, &ty, &RegisterOffset { offset: 0, register: Rax }, &generator::rax(&ty));
+        },
+        FactorKind::SizeOf(operand) => {
+            asm.movl(&Immediate(operand.ty().size()), &Eax);
+        },
+        FactorKind::SizeOfType(operand_type) => {
+            asm.movl(&Immediate(operand_type.size()), &Eax);
+        },
+        FactorKind::Cast(cast) => {
+            let operand_type = cast.expression.ty().clone();
+            expression::asm(asm, cast.expression, symbols);
+            generator::convert(asm, &operand_type, &ty);
         },
     }
 }
 
-pub fn unary_operation_asm(asm: &mut Asm, operation: UnaryOperation, stack_frame: &StackFrame) {
-    factor::asm(asm, operation.factor.clone(), stack_frame);
+// `ty` is the type of the whole operation. The operand is already widened
+// to 32 bits if it's narrower, so it only has to be promoted to `ty` in
+// name.
+pub fn unary_operation_asm(asm: &mut Asm, operation: UnaryOperation, ty: &Type, symbols: &SymbolTable) {
+    if operation.operator == UnaryOperator::AddressOf {
+        address_asm(asm, operation.factor, symbols);
+        return;
+    }
+
+    let operand_type = operation.factor.ty().decay();
+    factor::asm(asm, operation.factor, symbols);
 
     match operation.operator {
+        // Flipping the sign bit, so `-0.0` comes out right
+        UnaryOperator::Negation if *ty == Type::Double => {
+            asm.movq(&Xmm0, &Rax);
+            asm.btcq(&Immediate(63), &Rax);
+            asm.movq(&Rax, &Xmm0);
+        },
+        UnaryOperator::Negation if *ty == Type::Float => {
+            asm.movd(&Xmm0, &Eax);
+            asm.btcl(&Immediate(31), &Eax);
+            asm.movd(&Eax, &Xmm0);
+        },
+        UnaryOperator::Negation if ty.size() == 8 => {
+            asm.negq(&Rax);
+        },
         UnaryOperator::Negation => {
-            asm.neg(&Rax);
+            asm.negl(&Eax);
         },
         UnaryOperator::LogicalNegation => {
-            asm.cmp(&0, &Rax);
-            asm.mov(&0, &Rax);
+            generator::compare_with_zero(asm, &operand_type);
+            asm.movl(&Immediate(0), &Eax);
             asm.sete(&Al);
         },
+        UnaryOperator::BitwiseComplement if ty.size() == 8 => {
+            asm.notq(&Rax);
+        },
         UnaryOperator::BitwiseComplement => {
-            asm.not(&Rax);
+            asm.notl(&Eax);
+        },
+        UnaryOperator::Dereference => {
+            generator::load(asm, ty, &RegisterOffset { offset: 0, register: Rax }, &generator::rax(ty));
+        },
+        UnaryOperator::AddressOf => panic!("Address taken after evaluating the operand"),
+    }
+}
+
+// Pointers move on by a whole element. Floating point values can't be
+// added to in memory, so they're moved on by `1.0` in %xmm1, leaving
+// %xmm0 as it was.
+fn increment_asm(asm: &mut Asm, operator: &IncrementOperator, ty: &Type, address: &dyn fmt::Display) {
+    if ty.is_floating() {
+        generator::load(asm, ty, address, &Xmm1);
+        generator::load_floating_constant(asm, ty, 1.0, &Xmm2);
+        match (operator, *ty == Type::Double) {
+            (IncrementOperator::Increment, true) => asm.addsd(&Xmm2, &Xmm1),
+            (IncrementOperator::Increment, false) => asm.addss(&Xmm2, &Xmm1),
+            (IncrementOperator::Decrement, true) => asm.subsd(&Xmm2, &Xmm1),
+            (IncrementOperator::Decrement, false) => asm.subss(&Xmm2, &Xmm1),
+        }
+        if *ty == Type::Double { asm.movsd(&Xmm1, address) } else { asm.movss(&Xmm1, address) }
+        return;
+    }
+
+    let step: i64;
+    match ty.pointee() {
+        Some(pointee) => step = pointee.size(),
+        None => step = 1,
+    }
+
+    match (operator, ty.size()) {
+        (IncrementOperator::Increment, 8) => asm.addq(&Immediate(step), address),
+        (IncrementOperator::Increment, 2) => asm.addw(&Immediate(step), address),
+        (IncrementOperator::Increment, 1) => asm.addb(&Immediate(step), address),
+        (IncrementOperator::Increment, _) => asm.addl(&Immediate(step), address),
+        (IncrementOperator::Decrement, 8) => asm.subq(&Immediate(step), address),
+        (IncrementOperator::Decrement, 2) => asm.subw(&Immediate(step), address),
+        (IncrementOperator::Decrement, 1) => asm.subb(&Immediate(step), address),
+        (IncrementOperator::Decrement, _) => asm.subl(&Immediate(step), address),
+    }
+}
+
+// Leave the address of an lvalue in %rax
+pub fn address_asm(asm: &mut Asm, factor: Factor, symbols: &SymbolTable) {
+    match factor.kind {
+        FactorKind::Identifier(var) => {
+            let address = generator::var_address(&asm.target, symbols, &var);
+            asm.leaq(&*address, &Rax);
+        },
+        // `&*pointer` is just the pointer
+        FactorKind::UnaryOperation(operation) => {
+            factor::asm(asm, operation.factor, symbols);
+        },
+        FactorKind::Subscript(subscript) => {
+            subscript_address_asm(asm, *subscript, symbols);
+        },
+        FactorKind::Member(access) => {
+            member_address_asm(asm, *access, symbols);
+        },
+        FactorKind::StringLiteral(bytes) => {
+            let address = asm.string_literal(&bytes);
+            asm.leaq(&address, &Rax);
+        },
+        FactorKind::Expression(expression) => match expression.kind {
+            ExpressionKind::Factor(factor) => address_asm(asm, factor, symbols),
+            _ => panic!("Not an lvalue"),
+        },
+        _ => panic!("Not an lvalue"),
+    }
+}
+
+// `array[index]` is at `array + index * element size`
+fn subscript_address_asm(asm: &mut Asm, subscript: Subscript, symbols: &SymbolTable) {
+    let pointer = subscript.array.ty().decay();
+    factor::asm(asm, subscript.array, symbols);
+    asm.push(&Rax);
+    expression::asm(asm, subscript.index, symbols);
+    expression::scale_asm(asm, &Rax, &pointer);
+    asm.pop(&Rcx);
+    asm.addq(&Rcx, &Rax);
+}
+
+// A member is at its offset from the start of the struct. Both the value of
+// a struct and a pointer to one are its address, so `.` and `->` come to
+// the same thing.
+fn member_address_asm(asm: &mut Asm, access: MemberAccess, symbols: &SymbolTable) {
+    let struct_type: Type;
+    match access.operand.ty().decay() {
+        Type::Pointer(pointee) if access.is_arrow => struct_type = *pointee,
+        operand_type => struct_type = operand_type,
+    }
+    let member = struct_type.struct_type()
+        .and_then(|struct_type| struct_type.member(&access.name))
+        .expect("Member wasn't resolved");
+    factor::asm(asm, access.operand, symbols);
+    if member.offset > 0 {
+        asm.addq(&Immediate(member.offset), &Rax);
+    }
+}
+
+// The memory operand for an lvalue that's a variable, which can be used
+// without working anything out first
+pub fn var_operand(target: &Target, factor: &Factor, symbols: &SymbolTable) -> Option<Box<dyn fmt::Display>> {
+    match factor.kind {
+        FactorKind::Identifier(ref var) => return Some(generator::var_address(target, symbols, var)),
+        FactorKind::Expression(ref expression) => match expression.kind {
+            ExpressionKind::Factor(ref factor) => return var_operand(target, factor, symbols),
+            _ => return None,
+        },
+        _ => return None,
+    }
+}
+
+// The memory operand for any lvalue. Unless it's a variable, its address is
+// worked out into %rcx, so it's only good until %rcx is next used.
+fn lvalue_operand(asm: &mut Asm, factor: Factor, symbols: &SymbolTable) -> Box<dyn fmt::Display> {
+    match var_operand(&asm.target, &factor, symbols) {
+        Some(address) => return address,
+        None => (),
+    }
+
+    address_asm(asm, factor, symbols);
+    asm.movq(&Rax, &Rcx);
+    return Box::new(RegisterOffset { offset: 0, register: Rcx });
+}
+
+// Arguments passed on the stack are evaluated right to left onto it, so
+// they're in order, above enough padding to have the stack 16-byte aligned
+// at the call. A struct passed on the stack is copied there whole, rounded
+// up to a multiple of 8 bytes. The ones passed in registers are evaluated
+// onto the stack after them, a struct an eightbyte at a time, then popped
+// into their registers. `ty` is the type the function returns.
+pub fn function_call_asm(asm: &mut Asm, call: FunctionCall, ty: &Type, symbols: &SymbolTable) {
+    let types: Vec<Type> = call.arguments.iter().map(|argument| argument.ty().clone()).collect();
+    let registers = argument_registers(ty, &types);
+    let float_count = registers.iter().flatten().flatten()
+        .filter(|(class, _)| *class == Class::Sse)
+        .count();
+    let (in_registers, on_stack): (Vec<_>, Vec<_>) = call.arguments.into_iter()
+        .zip(registers)
+        .partition(|(_, registers)| registers.is_some());
+    let stack_bytes: i64 = on_stack.iter().map(|(argument, _)| (argument.ty().size() + 7) / 8 * 8).sum();
+
+    let padding = (16 - (asm.stack_depth + stack_bytes) % 16) % 16;
+    if padding > 0 {
+        asm.allocate_stack(padding);
+    }
+
+    for (argument, _) in on_stack.into_iter().rev() {
+        let ty = argument.ty().clone();
+        expression::asm(asm, argument, symbols);
+        if ty.is_struct() {
+            asm.allocate_stack((ty.size() + 7) / 8 * 8);
+            generator::store(asm, &ty, &RegisterOffset { offset: 0, register: Rsp });
+        } else {
+            generator::push(asm, &ty);
+        }
+    }
+    let mut destinations: Vec<(Class, usize)> = Vec::new();
+    for (argument, registers) in in_registers.into_iter().rev() {
+        let ty = argument.ty().clone();
+        let eightbytes = registers.expect("Argument isn't in registers");
+        expression::asm(asm, argument, symbols);
+        if ty.is_struct() {
+            asm.movq(&Rax, &Rdx);
+            for (index, eightbyte) in eightbytes.into_iter().enumerate().rev() {
+                let offset = 8 * index as i64;
+                generator::load_eightbyte(asm, &Rdx, offset, (ty.size() - offset).min(8));
+                asm.push(&Rax);
+                destinations.push(eightbyte);
+            }
+        } else {
+            generator::push(asm, &ty);
+            destinations.push(eightbytes[0]);
+        }
+    }
+    for (class, index) in destinations.into_iter().rev() {
+        match class {
+            Class::Integer => asm.pop(&ARGUMENT_REGISTERS[index]),
+            Class::Sse => {
+                asm.pop(&Rax);
+                asm.movq(&Rax, &FLOAT_ARGUMENT_REGISTERS[index]);
+            },
+        }
+    }
+
+    // A struct returned in memory is put at the address passed in %rdi
+    let return_registers = return_registers(ty);
+    match call.return_slot {
+        Some(offset) if return_registers.is_none() => {
+            asm.leaq(&RegisterOffset { offset: offset, register: Rbp }, &Rdi);
+        },
+        _ => (),
+    }
+
+    // Variadic functions like printf take the number of vector registers
+    // used in %al
+    asm.movl(&Immediate(float_count as i64), &Eax);
+    asm.call(&call.name);
+
+    if stack_bytes + padding > 0 {
+        asm.free_stack(stack_bytes + padding);
+    }
+
+    // A struct returned in registers is stored in its slot, a whole
+    // eightbyte at a time, as the slot's rounded up to a multiple of 8
+    // bytes. Either way the value is the slot's address.
+    match call.return_slot {
+        Some(offset) => {
+            for (index, register) in return_registers.unwrap_or_default().iter().enumerate() {
+                asm.movq(register, &RegisterOffset { offset: offset + 8 * index as i64, register: Rbp });
+            }
+            asm.leaq(&RegisterOffset { offset: offset, register: Rbp }, &Rax);
         },
+        None => (),
     }
 }
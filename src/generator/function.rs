@@ -1,25 +1,77 @@
 use asm::Asm;
-use asm::Register::Rax;
+use asm::{RegisterOffset, Immediate, Class, argument_registers};
+use asm::{ARGUMENT_REGISTERS, INT_ARGUMENT_REGISTERS, WORD_ARGUMENT_REGISTERS, BYTE_ARGUMENT_REGISTERS, FLOAT_ARGUMENT_REGISTERS};
+use asm::Register::{Eax, Rbp, Rdi};
 use generator::statement;
-use parser::function::Function;
-use parser::statement::Statement;
+use sema::{FunctionDefinition, SymbolTable, Storage};
+use types::Type;
+use parser::statement::{BlockItem, StatementKind};
 
-pub fn asm(asm: &mut Asm, function: Function) {
+pub fn asm(asm: &mut Asm, function: FunctionDefinition, symbols: &SymbolTable) {
     let has_return: bool;
-    match function.statements.last() {
-        Some(Statement::Return(_)) => has_return = true,
+    match function.body.last() {
+        Some(BlockItem::Statement(statement)) => match statement.kind {
+            StatementKind::Return(_) => has_return = true,
+            _ => has_return = false,
+        },
         _ => has_return = false,
     }
 
-    asm.declare_function(function.name);
-    for statement in function.statements {
-        statement::asm(asm, statement, &function.stack_frame);
+    asm.declare_function(&function.name, function.is_static);
+    if function.frame_size > 0 {
+        asm.allocate_stack(function.frame_size);
     }
 
+    // The address to return a struct at is kept for the return statements
+    match function.return_address {
+        Some(offset) => asm.movq(&Rdi, &RegisterOffset { offset: offset, register: Rbp }),
+        None => (),
+    }
+    asm.return_type = Some(function.return_type.clone());
+    asm.return_address = function.return_address;
+
+    // Parameters passed in registers are copied into their slots in the
+    // frame, a whole eightbyte at a time for a struct, as its slot is
+    // rounded up to a multiple of 8 bytes. The rest are already on the
+    // stack.
+    let types: Vec<Type> = function.params.iter().map(|param| symbols.get(*param).ty.clone()).collect();
+    for (param, registers) in function.params.iter().zip(argument_registers(&function.return_type, &types)) {
+        let eightbytes: Vec<(Class, usize)>;
+        match registers {
+            Some(registers) => eightbytes = registers,
+            None => continue,
+        }
+        let symbol = symbols.get(*param);
+        let offset: i64;
+        match symbol.storage {
+            Storage::Stack(stack_offset) => offset = stack_offset,
+            Storage::Static(_) => panic!("Parameter isn't on the stack"),
+        }
+        let address = RegisterOffset { offset: offset, register: Rbp };
+        let index = eightbytes[0].1;
+        match symbol.ty.size() {
+            _ if symbol.ty.is_struct() => for (position, (class, index)) in eightbytes.into_iter().enumerate() {
+                let address = RegisterOffset { offset: offset + 8 * position as i64, register: Rbp };
+                match class {
+                    Class::Integer => asm.movq(&ARGUMENT_REGISTERS[index], &address),
+                    Class::Sse => asm.movq(&FLOAT_ARGUMENT_REGISTERS[index], &address),
+                }
+            },
+            _ if symbol.ty == Type::Float => asm.movss(&FLOAT_ARGUMENT_REGISTERS[index], &address),
+            _ if symbol.ty == Type::Double => asm.movsd(&FLOAT_ARGUMENT_REGISTERS[index], &address),
+            8 => asm.movq(&ARGUMENT_REGISTERS[index], &address),
+            2 => asm.movw(&WORD_ARGUMENT_REGISTERS[index], &address),
+            1 => asm.movb(&BYTE_ARGUMENT_REGISTERS[index], &address),
+            _ => asm.movl(&INT_ARGUMENT_REGISTERS[index], &address),
+        }
+    }
+
+    statement::block_asm(asm, function.body, symbols);
+
     // Return 0 if no return statement
     if !has_return {
-        asm.mov(&0, &Rax);
+        asm.movl(&Immediate(0), &Eax);
+        asm.function_return();
     }
-
-    asm.function_return();
+    asm.end_function(&function.name);
 }
@@ -1,6 +1,301 @@
+use std::fmt;
+use sema::{SymbolTable, Storage};
+use asm::{Asm, Immediate, RegisterOffset, RipRelative, Register};
+use asm::Register::{Rax, Rcx, Rdx, Rsi, Rdi, Eax, Ecx, Cx, Al, Cl, Xmm0, Xmm1, Rbp};
+use target::Target;
+use parser::expression::Var;
+use types::Type;
+
 pub mod program;
 pub mod function;
 pub mod statement;
 pub mod expression;
-pub mod term;
 pub mod factor;
+
+// Anything bigger is copied or zeroed with a `rep` string instruction,
+// rather than an instruction for every eightbyte
+pub const MAX_UNROLLED_SIZE: i64 = 64;
+
+// The memory operand for a variable: a slot in the stack frame, or a global
+pub fn var_address(target: &Target, symbols: &SymbolTable, var: &Var) -> Box<dyn fmt::Display> {
+    let symbol = symbols.get(var.symbol.expect("Var wasn't resolved"));
+    match symbol.storage {
+        Storage::Stack(offset) => return Box::new(RegisterOffset { offset: offset, register: Rbp }),
+        Storage::Static(ref name) => return Box::new(RipRelative { symbol: target.symbol(name) }),
+    }
+}
+
+// Where a value of the type is left: %xmm0 for floating point, all of %rax
+// for a pointer or an 8 byte integer, and %eax for anything else. Anything
+// smaller than an `int` is widened to 32 bits as it's loaded, by sign or
+// zero extension depending on its type, so it's in %eax too. A struct's
+// value is its address, in %rax, as it won't fit in a register.
+pub fn rax(ty: &Type) -> Register {
+    match ty.size() {
+        _ if ty.is_floating() => return Register::Xmm0,
+        _ if ty.is_struct() => return Register::Rax,
+        8 => return Register::Rax,
+        _ => return Register::Eax,
+    }
+}
+
+// The whole register `rax(ty)` is in, which `movq` can move the value to
+// and from whatever its size
+pub fn value_register(ty: &Type) -> Register {
+    if ty.is_floating() {
+        return Register::Xmm0;
+    }
+    return Register::Rax;
+}
+
+// Push the value of the type, which there's no `push` for if it's in %xmm0
+pub fn push(asm: &mut Asm, ty: &Type) {
+    if ty.is_floating() {
+        asm.movq(&Xmm0, &Rax);
+    }
+    asm.push(&Rax);
+}
+
+// Read a value of the type from memory into `dest`, which is `rax(ty)` or
+// the same part of another register
+pub fn load(asm: &mut Asm, ty: &Type, src: &dyn fmt::Display, dest: &Register) {
+    match ty.size() {
+        _ if ty.is_struct() => asm.leaq(src, dest),
+        _ if *ty == Type::Float => asm.movss(src, dest),
+        _ if *ty == Type::Double => asm.movsd(src, dest),
+        8 => asm.movq(src, dest),
+        2 if ty.is_signed() => asm.movswl(src, dest),
+        2 => asm.movzwl(src, dest),
+        1 if ty.is_signed() => asm.movsbl(src, dest),
+        1 => asm.movzbl(src, dest),
+        _ => asm.movl(src, dest),
+    }
+}
+
+// Write the value in `rax(ty)` to memory as the type, keeping only as many
+// bytes as it has. A struct is copied from the address in %rax, by way of
+// %rdx, so %rax still points at the value after.
+pub fn store(asm: &mut Asm, ty: &Type, dest: &dyn fmt::Display) {
+    match ty.size() {
+        size if ty.is_struct() => {
+            asm.leaq(dest, &Rdx);
+            copy(asm, size);
+        },
+        _ if *ty == Type::Float => asm.movss(&Register::Xmm0, dest),
+        _ if *ty == Type::Double => asm.movsd(&Register::Xmm0, dest),
+        8 => asm.movq(&Register::Rax, dest),
+        2 => asm.movw(&Register::Ax, dest),
+        1 => asm.movb(&Register::Al, dest),
+        _ => asm.movl(&Register::Eax, dest),
+    }
+}
+
+// Copy `size` bytes from the address in %rax to the one in %rdx through
+// %rcx, 8 at a time while there are that many. A big copy has its
+// eightbytes copied by `rep movsq`, through %rsi and %rdi as well.
+pub fn copy(asm: &mut Asm, size: i64) {
+    let mut copied = 0;
+    if size > MAX_UNROLLED_SIZE {
+        asm.movq(&Rax, &Rsi);
+        asm.movq(&Rdx, &Rdi);
+        asm.movq(&Immediate(size / 8), &Rcx);
+        asm.rep_movsq();
+        copied = size / 8 * 8;
+    }
+    while copied < size {
+        let src = RegisterOffset { offset: copied, register: Rax };
+        let dest = RegisterOffset { offset: copied, register: Rdx };
+        match size - copied {
+            remaining if remaining >= 8 => {
+                asm.movq(&src, &Rcx);
+                asm.movq(&Rcx, &dest);
+                copied += 8;
+            },
+            remaining if remaining >= 4 => {
+                asm.movl(&src, &Ecx);
+                asm.movl(&Ecx, &dest);
+                copied += 4;
+            },
+            remaining if remaining >= 2 => {
+                asm.movw(&src, &Cx);
+                asm.movw(&Cx, &dest);
+                copied += 2;
+            },
+            _ => {
+                asm.movb(&src, &Cl);
+                asm.movb(&Cl, &dest);
+                copied += 1;
+            },
+        }
+    }
+}
+
+// Read the `size` bytes of a struct's eightbyte at `offset` from `base`
+// into %rax, zero extended, without reading past the end of the struct.
+// Sizes there's no single instruction for are built up a byte at a time,
+// from the top.
+pub fn load_eightbyte(asm: &mut Asm, base: &Register, offset: i64, size: i64) {
+    let src = RegisterOffset { offset: offset, register: *base };
+    match size {
+        8 => asm.movq(&src, &Rax),
+        4 => asm.movl(&src, &Eax),
+        2 => asm.movzwl(&src, &Eax),
+        1 => asm.movzbl(&src, &Eax),
+        _ => {
+            asm.movl(&Immediate(0), &Eax);
+            for index in (0..size).rev() {
+                asm.salq(&Immediate(8), &Rax);
+                asm.movb(&RegisterOffset { offset: offset + index, register: *base }, &Al);
+            }
+        },
+    }
+}
+
+// Cut the value in %eax down to the type, as if it had been stored and
+// loaded again
+pub fn truncate(asm: &mut Asm, ty: &Type) {
+    match ty.size() {
+        2 if ty.is_signed() => asm.movswl(&Register::Ax, &Register::Eax),
+        2 => asm.movzwl(&Register::Ax, &Register::Eax),
+        1 if ty.is_signed() => asm.movsbl(&Register::Al, &Register::Eax),
+        1 => asm.movzbl(&Register::Al, &Register::Eax),
+        _ => (),
+    }
+}
+
+// There are no immediate operands for %xmm registers, so the constant is
+// put in `dest` by way of %rax
+pub fn load_floating_constant(asm: &mut Asm, ty: &Type, value: f64, dest: &Register) {
+    if *ty == Type::Float {
+        asm.movl(&Immediate(i64::from((value as f32).to_bits())), &Eax);
+        asm.movd(&Eax, dest);
+    } else {
+        asm.movabsq(&Immediate(value.to_bits() as i64), &Rax);
+        asm.movq(&Rax, dest);
+    }
+}
+
+// Convert the value in `rax(from)` to the type, leaving it in `rax(to)`.
+// Between integers and pointers, widening to 8 bytes extends by the sign of
+// the type it's from, and anything narrower than 8 bytes only needs %eax,
+// so converting to an `int` or an `unsigned int` leaves it as it is.
+pub fn convert(asm: &mut Asm, from: &Type, to: &Type) {
+    match (from.is_floating(), to.is_floating()) {
+        (false, false) => (),
+        (true, true) => {
+            if from != to && *to == Type::Double {
+                asm.cvtss2sd(&Xmm0, &Xmm0);
+            } else if from != to {
+                asm.cvtsd2ss(&Xmm0, &Xmm0);
+            }
+            return;
+        },
+        (false, true) => {
+            integer_to_floating(asm, from, to);
+            return;
+        },
+        (true, false) => {
+            floating_to_integer(asm, from, to);
+            return;
+        },
+    }
+
+    match (from.size(), to.size()) {
+        (8, 8) => (),
+        (_, 8) if from.is_signed() => asm.movslq(&Register::Eax, &Register::Rax),
+        // Writing %eax clears the top half of %rax
+        (_, 8) => asm.movl(&Register::Eax, &Register::Eax),
+        _ => truncate(asm, to),
+    }
+}
+
+// The conversion instructions only take signed integers. An `unsigned int`
+// fits in a signed 64-bit one. An 8 byte unsigned value too big for that is
+// halved first and doubled again after, keeping its lowest bit so it still
+// rounds the right way.
+fn integer_to_floating(asm: &mut Asm, from: &Type, to: &Type) {
+    let is_double = *to == Type::Double;
+    match (from.size(), from.is_signed()) {
+        (8, false) => {
+            let mut clause = asm.new_clause();
+            asm.testq(&Rax, &Rax);
+            asm.js(clause.start_id());
+            if is_double { asm.cvtsi2sdq(&Rax, &Xmm0) } else { asm.cvtsi2ssq(&Rax, &Xmm0) }
+            asm.jmp(clause.end_id());
+
+            asm.start_clause(&mut clause);
+            asm.movq(&Rax, &Rcx);
+            asm.shrq(&Immediate(1), &Rcx);
+            asm.andl(&Immediate(1), &Eax);
+            asm.orq(&Rax, &Rcx);
+            if is_double {
+                asm.cvtsi2sdq(&Rcx, &Xmm0);
+                asm.addsd(&Xmm0, &Xmm0);
+            } else {
+                asm.cvtsi2ssq(&Rcx, &Xmm0);
+                asm.addss(&Xmm0, &Xmm0);
+            }
+            asm.end_clause(&mut clause);
+        },
+        (8, true) | (4, false) => {
+            if from.size() == 4 {
+                asm.movl(&Eax, &Eax);
+            }
+            if is_double { asm.cvtsi2sdq(&Rax, &Xmm0) } else { asm.cvtsi2ssq(&Rax, &Xmm0) }
+        },
+        _ => if is_double { asm.cvtsi2sdl(&Eax, &Xmm0) } else { asm.cvtsi2ssl(&Eax, &Xmm0) },
+    }
+}
+
+// Truncated towards zero. Anything narrower than an 8 byte unsigned value
+// fits in a signed 64-bit one. Values too big for that have 2^63 taken off
+// before converting, and the top bit set again after.
+fn floating_to_integer(asm: &mut Asm, from: &Type, to: &Type) {
+    let is_double = *from == Type::Double;
+    let truncate_into = |asm: &mut Asm, dest: &Register| {
+        if is_double { asm.cvttsd2si(&Xmm0, dest) } else { asm.cvttss2si(&Xmm0, dest) }
+    };
+
+    match (to.size(), to.is_signed()) {
+        (8, false) => {
+            let mut clause = asm.new_clause();
+            load_floating_constant(asm, from, 9223372036854775808.0, &Xmm1);
+            if is_double { asm.ucomisd(&Xmm1, &Xmm0) } else { asm.ucomiss(&Xmm1, &Xmm0) }
+            asm.jae(clause.start_id());
+            truncate_into(asm, &Rax);
+            asm.jmp(clause.end_id());
+
+            asm.start_clause(&mut clause);
+            if is_double { asm.subsd(&Xmm1, &Xmm0) } else { asm.subss(&Xmm1, &Xmm0) }
+            truncate_into(asm, &Rax);
+            asm.btcq(&Immediate(63), &Rax);
+            asm.end_clause(&mut clause);
+        },
+        (8, true) | (4, false) => truncate_into(asm, &Rax),
+        _ => {
+            truncate_into(asm, &Eax);
+            truncate(asm, to);
+        },
+    }
+}
+
+// Set the flags from comparing the value in `rax(ty)` with zero. For
+// floating point, whether it's non-zero is worked out into %eax first, and
+// NaN counts as non-zero, as it's unordered.
+pub fn compare_with_zero(asm: &mut Asm, ty: &Type) {
+    if ty.is_floating() {
+        asm.xorps(&Xmm1, &Xmm1);
+        if *ty == Type::Double { asm.ucomisd(&Xmm1, &Xmm0) } else { asm.ucomiss(&Xmm1, &Xmm0) }
+        asm.setne(&Al);
+        asm.setp(&Cl);
+        asm.orb(&Cl, &Al);
+        asm.movzbl(&Al, &Eax);
+        asm.cmpl(&Immediate(0), &Eax);
+        return;
+    }
+
+    match ty.size() {
+        8 => asm.cmpq(&Immediate(0), &Register::Rax),
+        _ => asm.cmpl(&Immediate(0), &Register::Eax),
+    }
+}
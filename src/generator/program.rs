@@ -1,7 +1,35 @@
-use asm::Asm;
+use asm::{Asm, Data};
 use generator::function;
-use parser::program::Program;
+use parser::StorageClass;
+use sema::{Program, StaticVariable};
 
 pub fn asm(asm: &mut Asm, program: Program) {
-    function::asm(asm, program.function);
+    for variable in program.variables {
+        variable_asm(asm, variable);
+    }
+    for function in program.functions {
+        function::asm(asm, function, &program.symbols);
+    }
+    asm.end_program();
+}
+
+fn variable_asm(asm: &mut Asm, variable: StaticVariable) {
+    let data: Vec<Data>;
+    match variable.storage_class {
+        // Defined in another file
+        Some(StorageClass::Extern) => return,
+        _ => (),
+    }
+    match variable.initializer {
+        Some(initializer) => data = initializer.data,
+        None => data = vec![Data::Zero(variable.ty.size())],
+    }
+
+    asm.declare_variable(
+        &variable.name,
+        variable.ty.size(),
+        variable.ty.alignment(),
+        &data,
+        variable.storage_class == Some(StorageClass::Static),
+    );
 }
@@ -1,28 +1,231 @@
 use asm::Asm;
-use parser::StackFrame;
-use asm::Register::Rax;
+use generator;
+use sema::{SymbolTable, Storage};
+use sema::initializer;
+use asm::{Immediate, RegisterOffset, Register, return_registers};
+use asm::Register::{Rbp, Rax, Rcx, Rdx, Rsi, Rdi, Eax};
 use generator::expression;
-use parser::statement::Statement;
+use parser::statement::{Statement, StatementKind, BlockItem, ForInit, VariableDeclaration};
+use parser::initializer::Initializer;
+use types::Type;
 
-pub fn asm(asm: &mut Asm, statement: Statement, stack_frame: &StackFrame) {
-    match statement {
-        Statement::Return(expression) => {
-            expression::asm(asm, expression, stack_frame);
+pub fn asm(asm: &mut Asm, statement: Statement, symbols: &SymbolTable) {
+    match statement.kind {
+        StatementKind::Return(expression) => {
+            let ty = expression.ty().clone();
+            expression::asm(asm, expression, symbols);
+            if ty.is_struct() {
+                return_struct_asm(asm, &ty);
+            }
+            asm.function_return();
+        },
+        StatementKind::If(if_statement) => {
+            let mut clause = asm.new_clause();
+
+            expression::condition_asm(asm, if_statement.condition, symbols);
+            asm.je(clause.start_id());
+            generator::statement::asm(asm, *if_statement.statement, symbols);
+            asm.jmp(clause.end_id());
+
+            asm.start_clause(&mut clause);
+            match if_statement.else_statement {
+                Some(else_statement) => generator::statement::asm(asm, *else_statement, symbols),
+                None => (),
+            }
+            asm.end_clause(&mut clause);
+        },
+        StatementKind::Compound(block_items) => {
+            block_asm(asm, block_items, symbols);
+        },
+        StatementKind::Expression(expression) => {
+            expression::asm(asm, expression, symbols);
+        },
+        StatementKind::While(while_statement) => {
+            let current_loop = asm.enter_loop();
+
+            asm.label(current_loop.continue_id());
+            expression::condition_asm(asm, while_statement.condition, symbols);
+            asm.je(current_loop.break_id());
+            generator::statement::asm(asm, *while_statement.statement, symbols);
+            asm.jmp(current_loop.continue_id());
+            asm.label(current_loop.break_id());
+
+            asm.exit_loop();
+        },
+        StatementKind::DoWhile(while_statement) => {
+            let current_loop = asm.enter_loop();
+
+            asm.label(current_loop.start_id());
+            generator::statement::asm(asm, *while_statement.statement, symbols);
+            asm.label(current_loop.continue_id());
+            expression::condition_asm(asm, while_statement.condition, symbols);
+            asm.jne(current_loop.start_id());
+            asm.label(current_loop.break_id());
+
+            asm.exit_loop();
+        },
+        StatementKind::For(for_statement) => {
+            match for_statement.init {
+                Some(ForInit::Declaration(declaration)) => declaration_asm(asm, declaration, symbols),
+                Some(ForInit::Expression(expression)) => expression::asm(asm, expression, symbols),
+                None => (),
+            }
+
+            let current_loop = asm.enter_loop();
+            asm.label(current_loop.start_id());
+            match for_statement.condition {
+                Some(condition) => {
+                    expression::condition_asm(asm, condition, symbols);
+                    asm.je(current_loop.break_id());
+                },
+                None => (),
+            }
+            generator::statement::asm(asm, *for_statement.statement, symbols);
+            asm.label(current_loop.continue_id());
+            match for_statement.post {
+                Some(post) => expression::asm(asm, post, symbols),
+                None => (),
+            }
+            asm.jmp(current_loop.start_id());
+            asm.label(current_loop.break_id());
+            asm.exit_loop();
+        },
+        StatementKind::Break => {
+            let break_id = asm.current_loop().expect("'break' outside of a loop").break_id();
+            asm.jmp(break_id);
+        },
+        StatementKind::Continue => {
+            let continue_id = asm.current_loop().expect("'continue' outside of a loop").continue_id();
+            asm.jmp(continue_id);
+        },
+    }
+}
+
+pub fn block_asm(asm: &mut Asm, block_items: Vec<BlockItem>, symbols: &SymbolTable) {
+    for block_item in block_items {
+        match block_item {
+            BlockItem::Statement(statement) => generator::statement::asm(asm, statement, symbols),
+            BlockItem::Declaration(declaration) => declaration_asm(asm, declaration, symbols),
+        }
+    }
+}
+
+// A struct returned in memory is copied to the address the caller gave, which
+// is returned in %rax. One returned in registers has its eightbytes loaded
+// into %rax and %rdx, or %xmm0 and %xmm1 for SSE ones, from the address
+// moved to %rsi, with the one for %rax loaded last.
+fn return_struct_asm(asm: &mut Asm, ty: &Type) {
+    match asm.return_address {
+        Some(offset) => {
+            let address = RegisterOffset { offset: offset, register: Rbp };
+            asm.movq(&address, &Rdx);
+            generator::copy(asm, ty.size());
+            asm.movq(&address, &Rax);
+            return;
         },
-        Statement::Expression(expression) => {
-            expression::asm(asm, expression, stack_frame);
+        None => (),
+    }
+
+    let mut destinations: Vec<(i64, Register)> = return_registers(ty)
+        .expect("Struct is returned in memory")
+        .into_iter()
+        .enumerate()
+        .map(|(index, register)| (8 * index as i64, register))
+        .collect();
+    destinations.sort_by_key(|(_, register)| *register == Rax);
+
+    asm.movq(&Rax, &Rsi);
+    for (offset, register) in destinations {
+        generator::load_eightbyte(asm, &Rsi, offset, (ty.size() - offset).min(8));
+        if register != Rax {
+            asm.movq(&Rax, &register);
+        }
+    }
+}
+
+// The variable's slot was set aside in the frame by the prologue. A scalar
+// without an initialiser starts out as zero, but an array or a struct is
+// left as it is.
+fn declaration_asm(asm: &mut Asm, declaration: VariableDeclaration, symbols: &SymbolTable) {
+    let offset: i64;
+    match symbols.get(declaration.var.symbol.expect("Var wasn't resolved")).storage {
+        Storage::Stack(stack_offset) => offset = stack_offset,
+        Storage::Static(_) => panic!("Local variable isn't on the stack"),
+    }
+
+    match declaration.initializer {
+        Some(initializer) => initializer_asm(asm, initializer, &declaration.ty, offset, symbols),
+        None if !declaration.ty.is_scalar() => (),
+        None => zero_asm(asm, offset, declaration.ty.size()),
+    }
+}
+
+// Store the initialiser for a `ty` at `offset` in the frame. Array elements
+// and struct members it leaves out are zeroed, as is the padding between
+// members.
+fn initializer_asm(asm: &mut Asm, initializer: Initializer, ty: &Type, offset: i64, symbols: &SymbolTable) {
+    match initializer.string_literal() {
+        Some(bytes) if ty.is_array() => {
+            for (index, byte) in bytes.iter().enumerate() {
+                asm.movb(&Immediate(i64::from(*byte)), &RegisterOffset { offset: offset + index as i64, register: Rbp });
+            }
+            zero_asm(asm, offset + bytes.len() as i64, ty.size() - bytes.len() as i64);
+            return;
+        },
+        _ => (),
+    }
+
+    match (ty, initializer) {
+        (Type::Array(element, length), Initializer::List(elements, _)) => {
+            let count = elements.len() as i64;
+            for (index, initializer) in elements.into_iter().enumerate() {
+                initializer_asm(asm, initializer, element, offset + index as i64 * element.size(), symbols);
+            }
+            zero_asm(asm, offset + count * element.size(), (length - count) * element.size());
         },
-        Statement::VariableDeclaration(declaration) => {
-            match declaration.expression {
-                Some(expression) => expression::asm(
-                    asm,
-                    expression,
-                    stack_frame,
-                ),
-                None => asm.mov(&0, &Rax),
+        (Type::Struct(_), Initializer::List(elements, _)) => {
+            let members = initializer::initialized_members(ty);
+            let mut end = 0;
+            for (initializer, member) in elements.into_iter().zip(members.iter()) {
+                zero_asm(asm, offset + end, member.offset - end);
+                initializer_asm(asm, initializer, &member.ty, offset + member.offset, symbols);
+                end = member.offset + member.ty.size();
             }
-            asm.push(&Rax);
+            zero_asm(asm, offset + end, ty.size() - end);
+        },
+        (_, Initializer::List(elements, _)) => {
+            let initializer = elements.into_iter().next().expect("Empty scalar initializer");
+            initializer_asm(asm, initializer, ty, offset, symbols);
+        },
+        (_, Initializer::Expression(expression)) => {
+            expression::asm(asm, expression, symbols);
+            generator::store(asm, ty, &RegisterOffset { offset: offset, register: Rbp });
         },
     }
 }
 
+// Zero `size` bytes of the frame, 8 at a time while there are that many. A
+// big area has its eightbytes zeroed by `rep stosq`, through %rax, %rcx and
+// %rdi.
+fn zero_asm(asm: &mut Asm, offset: i64, size: i64) {
+    let mut zeroed = 0;
+    if size > generator::MAX_UNROLLED_SIZE {
+        asm.leaq(&RegisterOffset { offset: offset, register: Rbp }, &Rdi);
+        asm.movl(&Immediate(0), &Eax);
+        asm.movq(&Immediate(size / 8), &Rcx);
+        asm.rep_stosq();
+        zeroed = size / 8 * 8;
+    }
+    while size - zeroed >= 8 {
+        asm.movq(&Immediate(0), &RegisterOffset { offset: offset + zeroed, register: Rbp });
+        zeroed += 8;
+    }
+    if size - zeroed >= 4 {
+        asm.movl(&Immediate(0), &RegisterOffset { offset: offset + zeroed, register: Rbp });
+        zeroed += 4;
+    }
+    while zeroed < size {
+        asm.movb(&Immediate(0), &RegisterOffset { offset: offset + zeroed, register: Rbp });
+        zeroed += 1;
+    }
+}
@@ -1,24 +1,81 @@
-use regex::Regex;
+use std::fmt;
+use std::rc::Rc;
+use span::Span;
+use diagnostic::Diagnostic;
 
 #[derive(Debug, Clone)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+// The value of an integer literal and what decides its type
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerLiteral {
+    pub value: u64,
+    // Octal and hex literals can be unsigned without a `u`
+    pub is_decimal: bool,
+    // From a `u` suffix
+    pub is_unsigned: bool,
+    // 1 for an `l` suffix and 2 for `ll`
+    pub long_count: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
     OpenBrace,
     CloseBrace,
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
     Semicolon,
     KeywordInt,
+    KeywordChar,
+    KeywordShort,
+    KeywordLong,
+    KeywordFloat,
+    KeywordDouble,
+    KeywordSigned,
+    KeywordUnsigned,
     KeywordReturn,
+    KeywordIf,
+    KeywordElse,
+    KeywordWhile,
+    KeywordDo,
+    KeywordFor,
+    KeywordBreak,
+    KeywordContinue,
+    KeywordStatic,
+    KeywordExtern,
+    KeywordSizeof,
+    KeywordStruct,
+    KeywordUnion,
+    KeywordEnum,
+    KeywordTypedef,
     Identifier(String),
-    IntegerLiteral(i64),
+    IntegerLiteral(IntegerLiteral),
+    // The value, and whether an `f` suffix makes it a `float` rather than a
+    // `double`
+    FloatingLiteral(f64, bool),
+    // The value of the character as a `char`, which is signed
+    CharacterLiteral(i32),
+    // The bytes of the string, without the terminating zero
+    StringLiteral(Vec<u8>),
     BitwiseComplement,
     LogicalNegation,
     MinusSign,
     PlusSign,
     MultiplicationSign,
     DivisionSign,
+    ModuloSign,
     And,
     Or,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
     Equal,
     NotEqual,
     LessThan,
@@ -26,105 +83,747 @@ pub enum Token {
     GreaterThan,
     GreaterThanOrEqual,
     Assignment,
+    PlusAssignment,
+    MinusAssignment,
+    MultiplicationAssignment,
+    DivisionAssignment,
+    ModuloAssignment,
+    ShiftLeftAssignment,
+    ShiftRightAssignment,
+    BitwiseAndAssignment,
+    BitwiseOrAssignment,
+    BitwiseXorAssignment,
+    Increment,
+    Decrement,
+    QuestionMark,
+    Colon,
+    Comma,
+    Ellipsis,
+    // `.` and `->`, for members
+    Dot,
+    Arrow,
+    Eof,
 }
 
-impl Token {
-    fn from_string(string: &str) -> Option<(Token, &str)> {
-        if Regex::new(r"^\{").unwrap().is_match(string) {
-            return Some((Token::OpenBrace, &string[1..]));
-        }
-        if Regex::new(r"^\}").unwrap().is_match(string) {
-            return Some((Token::CloseBrace, &string[1..]));
-        }
-        if Regex::new(r"^\(").unwrap().is_match(string) {
-            return Some((Token::OpenParen, &string[1..]));
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::OpenBrace => write!(f, "'{{'"),
+            TokenKind::CloseBrace => write!(f, "'}}'"),
+            TokenKind::OpenParen => write!(f, "'('"),
+            TokenKind::CloseParen => write!(f, "')'"),
+            TokenKind::OpenBracket => write!(f, "'['"),
+            TokenKind::CloseBracket => write!(f, "']'"),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::KeywordInt => write!(f, "'int'"),
+            TokenKind::KeywordChar => write!(f, "'char'"),
+            TokenKind::KeywordShort => write!(f, "'short'"),
+            TokenKind::KeywordLong => write!(f, "'long'"),
+            TokenKind::KeywordFloat => write!(f, "'float'"),
+            TokenKind::KeywordDouble => write!(f, "'double'"),
+            TokenKind::KeywordSigned => write!(f, "'signed'"),
+            TokenKind::KeywordUnsigned => write!(f, "'unsigned'"),
+            TokenKind::KeywordReturn => write!(f, "'return'"),
+            TokenKind::KeywordIf => write!(f, "'if'"),
+            TokenKind::KeywordElse => write!(f, "'else'"),
+            TokenKind::KeywordWhile => write!(f, "'while'"),
+            TokenKind::KeywordDo => write!(f, "'do'"),
+            TokenKind::KeywordFor => write!(f, "'for'"),
+            TokenKind::KeywordBreak => write!(f, "'break'"),
+            TokenKind::KeywordContinue => write!(f, "'continue'"),
+            TokenKind::KeywordStatic => write!(f, "'static'"),
+            TokenKind::KeywordExtern => write!(f, "'extern'"),
+            TokenKind::KeywordSizeof => write!(f, "'sizeof'"),
+            TokenKind::KeywordStruct => write!(f, "'struct'"),
+            TokenKind::KeywordUnion => write!(f, "'union'"),
+            TokenKind::KeywordEnum => write!(f, "'enum'"),
+            TokenKind::KeywordTypedef => write!(f, "'typedef'"),
+            TokenKind::Identifier(name) => write!(f, "identifier '{}'", name),
+            TokenKind::IntegerLiteral(literal) => write!(f, "integer literal '{}'", literal.value),
+            TokenKind::FloatingLiteral(value, _) => write!(f, "floating literal '{}'", value),
+            TokenKind::CharacterLiteral(_) => write!(f, "character literal"),
+            TokenKind::StringLiteral(_) => write!(f, "string literal"),
+            TokenKind::BitwiseComplement => write!(f, "'~'"),
+            TokenKind::LogicalNegation => write!(f, "'!'"),
+            TokenKind::MinusSign => write!(f, "'-'"),
+            TokenKind::PlusSign => write!(f, "'+'"),
+            TokenKind::MultiplicationSign => write!(f, "'*'"),
+            TokenKind::DivisionSign => write!(f, "'/'"),
+            TokenKind::ModuloSign => write!(f, "'%'"),
+            TokenKind::And => write!(f, "'&&'"),
+            TokenKind::Or => write!(f, "'||'"),
+            TokenKind::BitwiseAnd => write!(f, "'&'"),
+            TokenKind::BitwiseOr => write!(f, "'|'"),
+            TokenKind::BitwiseXor => write!(f, "'^'"),
+            TokenKind::ShiftLeft => write!(f, "'<<'"),
+            TokenKind::ShiftRight => write!(f, "'>>'"),
+            TokenKind::Equal => write!(f, "'=='"),
+            TokenKind::NotEqual => write!(f, "'!='"),
+            TokenKind::LessThan => write!(f, "'<'"),
+            TokenKind::LessThanOrEqual => write!(f, "'<='"),
+            TokenKind::GreaterThan => write!(f, "'>'"),
+            TokenKind::GreaterThanOrEqual => write!(f, "'>='"),
+            TokenKind::Assignment => write!(f, "'='"),
+            TokenKind::PlusAssignment => write!(f, "'+='"),
+            TokenKind::MinusAssignment => write!(f, "'-='"),
+            TokenKind::MultiplicationAssignment => write!(f, "'*='"),
+            TokenKind::DivisionAssignment => write!(f, "'/='"),
+            TokenKind::ModuloAssignment => write!(f, "'%='"),
+            TokenKind::ShiftLeftAssignment => write!(f, "'<<='"),
+            TokenKind::ShiftRightAssignment => write!(f, "'>>='"),
+            TokenKind::BitwiseAndAssignment => write!(f, "'&='"),
+            TokenKind::BitwiseOrAssignment => write!(f, "'|='"),
+            TokenKind::BitwiseXorAssignment => write!(f, "'^='"),
+            TokenKind::Increment => write!(f, "'++'"),
+            TokenKind::Decrement => write!(f, "'--'"),
+            TokenKind::QuestionMark => write!(f, "'?'"),
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Ellipsis => write!(f, "'...'"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::Arrow => write!(f, "'->'"),
+            TokenKind::Eof => write!(f, "end of file"),
         }
-        if Regex::new(r"^\)").unwrap().is_match(string) {
-            return Some((Token::CloseParen, &string[1..]));
-        }
-        if Regex::new(r"^;").unwrap().is_match(string) {
-            return Some((Token::Semicolon, &string[1..]));
-        }
-        if Regex::new(r"^&&").unwrap().is_match(string) {
-            return Some((Token::And, &string[2..]));
-        }
-        if Regex::new(r"^\|\|").unwrap().is_match(string) {
-            return Some((Token::Or, &string[2..]));
-        }
-        if Regex::new(r"^==").unwrap().is_match(string) {
-            return Some((Token::Equal, &string[2..]));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LexErrorKind {
+    UnexpectedCharacter(char),
+    InvalidIntegerLiteral(String),
+    InvalidIntegerSuffix(String),
+    InvalidOctalDigit(char),
+    MissingHexDigits,
+    InvalidFloatingSuffix(String),
+    MissingExponentDigits,
+    // The name of the type it's too large for
+    FloatingLiteralOutOfRange(&'static str),
+    UnterminatedComment,
+    // A '*/' after a comment that had a '/*' inside it (the span)
+    NestedComment(Span),
+    InvalidLineMarker,
+    UnterminatedCharacterLiteral,
+    UnterminatedStringLiteral,
+    EmptyCharacterLiteral,
+    MultiCharacterLiteral,
+    InvalidEscapeSequence,
+    EscapeSequenceOutOfRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Diagnostic {
+        match error.kind {
+            LexErrorKind::UnexpectedCharacter(character) => {
+                return Diagnostic::error(
+                    format!("Unexpected character '{}'", character),
+                    error.span,
+                );
+            },
+            LexErrorKind::InvalidIntegerLiteral(reason) => {
+                return Diagnostic::error(format!("Invalid integer literal: {}", reason), error.span)
+                    .with_note(format!("integer literals must fit in 64 bits, so be at most {}", u64::MAX));
+            },
+            LexErrorKind::InvalidOctalDigit(digit) => {
+                return Diagnostic::error(format!("Invalid digit '{}' in octal literal", digit), error.span)
+                    .with_note("a literal starting with '0' is octal, so only has the digits 0 to 7");
+            },
+            LexErrorKind::MissingHexDigits => {
+                return Diagnostic::error("Hex literal has no digits after '0x'", error.span);
+            },
+            LexErrorKind::InvalidIntegerSuffix(suffix) => {
+                return Diagnostic::error(format!("Invalid suffix '{}' on integer literal", suffix), error.span)
+                    .with_note("the suffixes are 'u', 'l', 'ul', 'll' and 'ull', in either case");
+            },
+            LexErrorKind::InvalidFloatingSuffix(suffix) => {
+                return Diagnostic::error(format!("Invalid suffix '{}' on floating literal", suffix), error.span)
+                    .with_note("the only suffix is 'f' for a 'float', 'long double' isn't supported");
+            },
+            LexErrorKind::MissingExponentDigits => {
+                return Diagnostic::error("Exponent has no digits", error.span);
+            },
+            LexErrorKind::FloatingLiteralOutOfRange(type_name) => {
+                return Diagnostic::error(format!("Floating literal is too large for '{}'", type_name), error.span);
+            },
+            LexErrorKind::UnterminatedComment => {
+                return Diagnostic::error("Unterminated block comment", error.span);
+            },
+            LexErrorKind::NestedComment(inner_start) => {
+                return Diagnostic::error("Unexpected '*/'", error.span)
+                    .with_label(inner_start, "this '/*' doesn't start a nested comment")
+                    .with_note("block comments don't nest, the comment ended at the first '*/'");
+            },
+            LexErrorKind::InvalidLineMarker => {
+                return Diagnostic::error("Expecting line marker '# <line> \"file\"'", error.span)
+                    .with_note("other preprocessor directives aren't supported, run the source through a C preprocessor first");
+            },
+            LexErrorKind::UnterminatedCharacterLiteral => {
+                return Diagnostic::error("Missing terminating ' character", error.span);
+            },
+            LexErrorKind::UnterminatedStringLiteral => {
+                return Diagnostic::error("Missing terminating \" character", error.span);
+            },
+            LexErrorKind::EmptyCharacterLiteral => {
+                return Diagnostic::error("Empty character literal", error.span);
+            },
+            LexErrorKind::MultiCharacterLiteral => {
+                return Diagnostic::error("Character literal has more than one character", error.span);
+            },
+            LexErrorKind::InvalidEscapeSequence => {
+                return Diagnostic::error("Unknown escape sequence", error.span);
+            },
+            LexErrorKind::EscapeSequenceOutOfRange => {
+                return Diagnostic::error("Escape sequence out of range", error.span)
+                    .with_note("the value has to fit in a byte, so be at most '\\377' or '\\xff'");
+            },
         }
-        if Regex::new(r"^!=").unwrap().is_match(string) {
-            return Some((Token::NotEqual, &string[2..]));
+    }
+}
+
+#[derive(Clone)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+// Single pass over the source, one character at a time, producing tokens
+// followed by a final `Eof`.
+pub struct Lexer<'a> {
+    source: &'a str,
+    file: Rc<str>,
+    position: Position,
+    finished: bool,
+    // Where a '/*' appeared inside the block comment just before the next
+    // token, if it did
+    nested_comment_start: Option<Span>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str, file_name: &str) -> Lexer<'a> {
+        return Lexer {
+            source: source,
+            file: Rc::from(file_name),
+            position: Position { offset: 0, line: 1, column: 1 },
+            finished: false,
+            nested_comment_start: None,
+        };
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.source[self.position.offset..].chars().next();
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        let mut chars = self.source[self.position.offset..].chars();
+        chars.next();
+        return chars.next();
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.position.offset += character.len_utf8();
+        if character == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
         }
-        if Regex::new(r"^<=").unwrap().is_match(string) {
-            return Some((Token::LessThanOrEqual, &string[2..]));
+        return Some(character);
+    }
+
+    fn bump_while<F: Fn(char) -> bool>(&mut self, predicate: F) {
+        while let Some(character) = self.peek() {
+            if !predicate(character) {
+                break;
+            }
+            self.bump();
         }
-        if Regex::new(r"^<").unwrap().is_match(string) {
-            return Some((Token::LessThan, &string[1..]));
+    }
+
+    // Span from `start` up to the current position
+    fn span_from(&self, start: &Position) -> Span {
+        return Span {
+            file: self.file.clone(),
+            offset: start.offset,
+            line: start.line,
+            column: start.column,
+            length: self.position.offset - start.offset,
+        };
+    }
+
+    // Skip whitespace, comments and line markers up to the next token
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            match (self.peek(), self.peek_next()) {
+                (Some(character), _) if character.is_whitespace() => {
+                    self.bump();
+                },
+                (Some('/'), Some('/')) => self.bump_while(|character| character != '\n'),
+                (Some('/'), Some('*')) => self.block_comment()?,
+                (Some('#'), _) if self.at_line_start() => self.line_marker()?,
+                _ => return Ok(()),
+            }
         }
-        if Regex::new(r"^>=").unwrap().is_match(string) {
-            return Some((Token::GreaterThanOrEqual, &string[2..]));
+    }
+
+    fn block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.position.clone();
+        self.bump();
+        self.bump();
+
+        let mut nested_comment_start: Option<Span> = None;
+        loop {
+            match (self.peek(), self.peek_next()) {
+                (Some('*'), Some('/')) => {
+                    self.bump();
+                    self.bump();
+                    self.nested_comment_start = nested_comment_start;
+                    return Ok(());
+                },
+                (Some('/'), Some('*')) => {
+                    let inner_start = self.position.clone();
+                    self.bump();
+                    self.bump();
+                    if nested_comment_start.is_none() {
+                        nested_comment_start = Some(self.span_from(&inner_start));
+                    }
+                },
+                (Some(_), _) => {
+                    self.bump();
+                },
+                (None, _) => {
+                    // Report it where the comment was opened, the end of the
+                    // file isn't much help
+                    let mut span = self.span_from(&start);
+                    span.length = 2;
+                    return Err(LexError { kind: LexErrorKind::UnterminatedComment, span: span });
+                },
+            }
         }
-        if Regex::new(r"^>").unwrap().is_match(string) {
-            return Some((Token::GreaterThan, &string[1..]));
+    }
+
+    fn at_line_start(&self) -> bool {
+        let line_so_far = self.source[..self.position.offset].rsplit('\n').next().unwrap_or("");
+        return line_so_far.trim().is_empty();
+    }
+
+    // `# 12 "file.c" flags` as output by the C preprocessor, or
+    // `#line 12 "file.c"`: the line after this one is line 12 of file.c
+    fn line_marker(&mut self) -> Result<(), LexError> {
+        let start = self.position.clone();
+        let is_blank = |character: char| character == ' ' || character == '\t';
+        self.bump();
+        self.bump_while(is_blank);
+        if self.source[self.position.offset..].starts_with("line") {
+            for _ in 0.."line".len() {
+                self.bump();
+            }
+            self.bump_while(is_blank);
         }
-        if Regex::new(r"^=").unwrap().is_match(string) {
-            return Some((Token::Assignment, &string[1..]));
+
+        let digits_start = self.position.offset;
+        self.bump_while(|character| character.is_ascii_digit());
+        let line = self.source[digits_start..self.position.offset].parse::<usize>();
+        self.bump_while(is_blank);
+
+        let mut file: Option<String> = None;
+        let mut is_valid = true;
+        if self.peek() == Some('"') {
+            self.bump();
+            let mut name = String::new();
+            loop {
+                match self.bump() {
+                    Some('"') => break,
+                    Some('\\') if self.peek().is_some() && self.peek() != Some('\n') => {
+                        name.push(self.bump().unwrap());
+                    },
+                    Some(character) if character != '\n' => name.push(character),
+                    _ => {
+                        is_valid = false;
+                        break;
+                    },
+                }
+            }
+            file = Some(name);
         }
-        if Regex::new(r"^~").unwrap().is_match(string) {
-            return Some((Token::BitwiseComplement, &string[1..]));
+
+        // Anything else on the line, like the preprocessor's flags, is ignored
+        self.bump_while(|character| character != '\n');
+        let line = match line {
+            Ok(line) if is_valid => line,
+            _ => return Err(LexError {
+                kind: LexErrorKind::InvalidLineMarker,
+                span: self.span_from(&start),
+            }),
+        };
+        self.bump();
+
+        self.position.line = line;
+        if let Some(file) = file {
+            self.file = Rc::from(file);
         }
-        if Regex::new(r"^!").unwrap().is_match(string) {
-            return Some((Token::LogicalNegation, &string[1..]));
+        return Ok(());
+    }
+
+    // Consume the second character of a two character operator if it's there
+    fn either(&mut self, second: char, matched: TokenKind, otherwise: TokenKind) -> TokenKind {
+        if self.peek() == Some(second) {
+            self.bump();
+            return matched;
         }
-        if Regex::new(r"^-").unwrap().is_match(string) {
-            return Some((Token::MinusSign, &string[1..]));
+        return otherwise;
+    }
+
+    // A decimal, octal (`017`) or hex (`0x1f`) literal, then any suffix.
+    // The first digit has already been consumed.
+    fn integer_literal(&mut self, start: &Position) -> Result<TokenKind, LexError> {
+        let is_zero = &self.source[start.offset..self.position.offset] == "0";
+        let is_hex = is_zero && matches!(self.peek(), Some('x') | Some('X'));
+        let is_octal = is_zero && !is_hex;
+        let radix = if is_hex { 16 } else if is_octal { 8 } else { 10 };
+        let digits_start = if is_hex {
+            self.bump();
+            self.position.offset
+        } else {
+            start.offset
+        };
+
+        // Any digits 8 and 9 in an octal literal are taken too, so they can
+        // be reported, or so that `017.5` is the decimal 17.5
+        self.bump_while(|character| character.is_digit(if is_hex { 16 } else { 10 }));
+        if !is_hex && matches!(self.peek(), Some('.') | Some('e') | Some('E')) {
+            return self.floating_literal(start);
         }
-        if Regex::new(r"^\+").unwrap().is_match(string) {
-            return Some((Token::PlusSign, &string[1..]));
+        let digits = &self.source[digits_start..self.position.offset];
+
+        let suffix_start = self.position.offset;
+        self.bump_while(|character| character.is_ascii_alphanumeric() || character == '_');
+        let (is_unsigned, long_count) = match &self.source[suffix_start..self.position.offset] {
+            "" => (false, 0),
+            "u" | "U" => (true, 0),
+            "l" | "L" => (false, 1),
+            "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" => (true, 1),
+            "ll" | "LL" => (false, 2),
+            "ull" | "uLL" | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => (true, 2),
+            suffix => return Err(LexError {
+                kind: LexErrorKind::InvalidIntegerSuffix(suffix.to_string()),
+                span: self.span_from(start),
+            }),
+        };
+
+        let error_kind = match digits.chars().find(|digit| !digit.is_digit(radix)) {
+            Some(digit) => Some(LexErrorKind::InvalidOctalDigit(digit)),
+            None if digits.is_empty() => Some(LexErrorKind::MissingHexDigits),
+            None => None,
+        };
+        if let Some(kind) = error_kind {
+            return Err(LexError { kind: kind, span: self.span_from(start) });
         }
-        if Regex::new(r"^\*").unwrap().is_match(string) {
-            return Some((Token::MultiplicationSign, &string[1..]));
+
+        match u64::from_str_radix(digits, radix) {
+            Ok(value) => return Ok(TokenKind::IntegerLiteral(IntegerLiteral {
+                value: value,
+                is_decimal: radix == 10,
+                is_unsigned: is_unsigned,
+                long_count: long_count,
+            })),
+            Err(err) => return Err(LexError {
+                kind: LexErrorKind::InvalidIntegerLiteral(err.to_string()),
+                span: self.span_from(start),
+            }),
         }
-        if Regex::new(r"^/").unwrap().is_match(string) {
-            return Some((Token::DivisionSign, &string[1..]));
+    }
+
+    // A decimal literal like `1.5`, `1.`, `.5` or `1e-3`, then an optional
+    // `f` suffix. Any digits before the point have already been consumed.
+    fn floating_literal(&mut self, start: &Position) -> Result<TokenKind, LexError> {
+        if self.peek() == Some('.') {
+            self.bump();
         }
-        if Regex::new(r"^int\s").unwrap().is_match(string) {
-            return Some((Token::KeywordInt, &string[4..]));
+        self.bump_while(|character| character.is_ascii_digit());
+        if matches!(self.peek(), Some('e') | Some('E')) {
+            self.bump();
+            if matches!(self.peek(), Some('+') | Some('-')) {
+                self.bump();
+            }
+            let digits_start = self.position.offset;
+            self.bump_while(|character| character.is_ascii_digit());
+            if self.position.offset == digits_start {
+                return Err(LexError { kind: LexErrorKind::MissingExponentDigits, span: self.span_from(start) });
+            }
         }
-        if Regex::new(r"^return\s").unwrap().is_match(string) {
-            return Some((Token::KeywordReturn, &string[7..]));
+        let text = &self.source[start.offset..self.position.offset];
+
+        let suffix_start = self.position.offset;
+        self.bump_while(|character| character.is_ascii_alphanumeric() || character == '_');
+        // A `float` is rounded straight from the text, rather than by way
+        // of a `double`
+        let (value, is_float) = match &self.source[suffix_start..self.position.offset] {
+            "" => (text.parse::<f64>(), false),
+            "f" | "F" => (text.parse::<f32>().map(f64::from), true),
+            suffix => return Err(LexError {
+                kind: LexErrorKind::InvalidFloatingSuffix(suffix.to_string()),
+                span: self.span_from(start),
+            }),
+        };
+
+        match value {
+            Ok(value) if value.is_finite() => return Ok(TokenKind::FloatingLiteral(value, is_float)),
+            _ => return Err(LexError {
+                kind: LexErrorKind::FloatingLiteralOutOfRange(if is_float { "float" } else { "double" }),
+                span: self.span_from(start),
+            }),
         }
-        if let Some(found) = Regex::new(r"^\d+").unwrap().find(&string.to_string()) {
-            let length = found.end() - found.start();
-            match found.as_str().parse::<i64>() {
-                Ok(i) => return Some((Token::IntegerLiteral(i), &string[length..])),
-                Err(err) => panic!("Error parsing integer: {}", err),
+    }
+
+    // `'c'`, which has the value of the character as a `char`
+    fn character_literal(&mut self, start: &Position) -> Result<TokenKind, LexError> {
+        let bytes = self.quoted_bytes(start, '\'')?;
+        let kind = match bytes.len() {
+            1 => return Ok(TokenKind::CharacterLiteral(i32::from(bytes[0] as i8))),
+            0 => LexErrorKind::EmptyCharacterLiteral,
+            _ => LexErrorKind::MultiCharacterLiteral,
+        };
+        return Err(LexError { kind: kind, span: self.span_from(start) });
+    }
+
+    fn string_literal(&mut self, start: &Position) -> Result<TokenKind, LexError> {
+        return Ok(TokenKind::StringLiteral(self.quoted_bytes(start, '"')?));
+    }
+
+    // The bytes up to the closing quote, with escape sequences replaced by
+    // what they stand for. Anything outside of ASCII is kept as UTF-8.
+    fn quoted_bytes(&mut self, start: &Position, quote: char) -> Result<Vec<u8>, LexError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        loop {
+            match self.peek() {
+                Some(character) if character == quote => {
+                    self.bump();
+                    return Ok(bytes);
+                },
+                Some('\\') => bytes.push(self.escape_sequence(start, quote)?),
+                Some(character) if character != '\n' => {
+                    self.bump();
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                },
+                _ => return Err(self.unterminated(start, quote)),
             }
         }
-        if let Some(found) = Regex::new(r"^\w+").unwrap().find(&string.to_string()) {
-            let length = found.end() - found.start();
-            let value = found.as_str().to_string();
-            return Some((Token::Identifier(value), &string[length..]));
+    }
+
+    // A literal that reached the end of the line without its closing quote
+    fn unterminated(&self, start: &Position, quote: char) -> LexError {
+        let kind = match quote {
+            '\'' => LexErrorKind::UnterminatedCharacterLiteral,
+            _ => LexErrorKind::UnterminatedStringLiteral,
+        };
+        return LexError { kind: kind, span: self.span_from(start) };
+    }
+
+    // `\n`, `\0`, `\x41` and the like. Octal escapes have up to three
+    // digits, and hexadecimal ones go on for as long as there are digits.
+    fn escape_sequence(&mut self, literal_start: &Position, quote: char) -> Result<u8, LexError> {
+        let start = self.position.clone();
+        self.bump();
+
+        let value = match self.peek() {
+            Some('n') => 10,
+            Some('t') => 9,
+            Some('r') => 13,
+            Some('a') => 7,
+            Some('b') => 8,
+            Some('f') => 12,
+            Some('v') => 11,
+            Some(character @ ('\\' | '\'' | '"' | '?')) => character as u32,
+            Some('0'..='7') => {
+                let digits_start = self.position.offset;
+                for _ in 0..3 {
+                    match self.peek() {
+                        Some('0'..='7') => self.bump(),
+                        _ => break,
+                    };
+                }
+                let digits = &self.source[digits_start..self.position.offset];
+                return self.escape_value(&start, u32::from_str_radix(digits, 8).ok());
+            },
+            Some('x') => {
+                self.bump();
+                let digits_start = self.position.offset;
+                self.bump_while(|character| character.is_ascii_hexdigit());
+                let digits = &self.source[digits_start..self.position.offset];
+                if digits.is_empty() {
+                    return Err(LexError { kind: LexErrorKind::InvalidEscapeSequence, span: self.span_from(&start) });
+                }
+                return self.escape_value(&start, u32::from_str_radix(digits, 16).ok());
+            },
+            Some(character) if character != '\n' => {
+                self.bump();
+                return Err(LexError { kind: LexErrorKind::InvalidEscapeSequence, span: self.span_from(&start) });
+            },
+            // The line ends with the backslash
+            _ => return Err(self.unterminated(literal_start, quote)),
+        };
+        self.bump();
+        return Ok(value as u8);
+    }
+
+    // Numeric escapes that don't fit in a byte are an error. `None` if
+    // there were too many digits to even parse.
+    fn escape_value(&self, start: &Position, value: Option<u32>) -> Result<u8, LexError> {
+        match value {
+            Some(value) if value <= 0xff => return Ok(value as u8),
+            _ => return Err(LexError { kind: LexErrorKind::EscapeSequenceOutOfRange, span: self.span_from(start) }),
         }
+    }
 
-        return None
+    fn identifier_or_keyword(&mut self, start: &Position) -> TokenKind {
+        self.bump_while(|character| character.is_ascii_alphanumeric() || character == '_');
+        match &self.source[start.offset..self.position.offset] {
+            "int" => return TokenKind::KeywordInt,
+            "char" => return TokenKind::KeywordChar,
+            "short" => return TokenKind::KeywordShort,
+            "long" => return TokenKind::KeywordLong,
+            "float" => return TokenKind::KeywordFloat,
+            "double" => return TokenKind::KeywordDouble,
+            "signed" => return TokenKind::KeywordSigned,
+            "unsigned" => return TokenKind::KeywordUnsigned,
+            "return" => return TokenKind::KeywordReturn,
+            "if" => return TokenKind::KeywordIf,
+            "else" => return TokenKind::KeywordElse,
+            "while" => return TokenKind::KeywordWhile,
+            "do" => return TokenKind::KeywordDo,
+            "for" => return TokenKind::KeywordFor,
+            "break" => return TokenKind::KeywordBreak,
+            "continue" => return TokenKind::KeywordContinue,
+            "static" => return TokenKind::KeywordStatic,
+            "extern" => return TokenKind::KeywordExtern,
+            "sizeof" => return TokenKind::KeywordSizeof,
+            "struct" => return TokenKind::KeywordStruct,
+            "union" => return TokenKind::KeywordUnion,
+            "enum" => return TokenKind::KeywordEnum,
+            "typedef" => return TokenKind::KeywordTypedef,
+            name => return TokenKind::Identifier(name.to_string()),
+        }
     }
-}
 
-pub fn parse(contents: String) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    let mut leftover_contents: &str = &contents.trim();
+    fn next_token(&mut self) -> Result<Token, LexError> {
+        self.skip_trivia()?;
+        // Only a '*/' straight after the comment is taken as closing a nested one
+        let nested_comment_start = self.nested_comment_start.take();
 
-    while let Some((token, leftover_string)) = Token::from_string(leftover_contents) {
-        leftover_contents = leftover_string.trim();
-        tokens.push(token);
+        let start = self.position.clone();
+        let character = match self.bump() {
+            Some(character) => character,
+            None => {
+                self.finished = true;
+                return Ok(Token { kind: TokenKind::Eof, span: self.span_from(&start) });
+            },
+        };
+
+        let kind = match character {
+            '{' => TokenKind::OpenBrace,
+            '}' => TokenKind::CloseBrace,
+            '(' => TokenKind::OpenParen,
+            ')' => TokenKind::CloseParen,
+            '[' => TokenKind::OpenBracket,
+            ']' => TokenKind::CloseBracket,
+            ';' => TokenKind::Semicolon,
+            '?' => TokenKind::QuestionMark,
+            ':' => TokenKind::Colon,
+            ',' => TokenKind::Comma,
+            '.' if self.source[self.position.offset..].starts_with("..") => {
+                self.bump();
+                self.bump();
+                TokenKind::Ellipsis
+            },
+            '.' if matches!(self.peek(), Some('0'..='9')) => self.floating_literal(&start)?,
+            '.' => TokenKind::Dot,
+            '~' => TokenKind::BitwiseComplement,
+            '-' if self.peek() == Some('-') => {
+                self.bump();
+                TokenKind::Decrement
+            },
+            '-' if self.peek() == Some('>') => {
+                self.bump();
+                TokenKind::Arrow
+            },
+            '-' => self.either('=', TokenKind::MinusAssignment, TokenKind::MinusSign),
+            '+' if self.peek() == Some('+') => {
+                self.bump();
+                TokenKind::Increment
+            },
+            '+' => self.either('=', TokenKind::PlusAssignment, TokenKind::PlusSign),
+            '*' if self.peek() == Some('/') && nested_comment_start.is_some() => {
+                self.bump();
+                return Err(LexError {
+                    kind: LexErrorKind::NestedComment(nested_comment_start.unwrap()),
+                    span: self.span_from(&start),
+                });
+            },
+            '*' => self.either('=', TokenKind::MultiplicationAssignment, TokenKind::MultiplicationSign),
+            '/' => self.either('=', TokenKind::DivisionAssignment, TokenKind::DivisionSign),
+            '%' => self.either('=', TokenKind::ModuloAssignment, TokenKind::ModuloSign),
+            '!' => self.either('=', TokenKind::NotEqual, TokenKind::LogicalNegation),
+            '=' => self.either('=', TokenKind::Equal, TokenKind::Assignment),
+            '<' if self.peek() == Some('<') => {
+                self.bump();
+                self.either('=', TokenKind::ShiftLeftAssignment, TokenKind::ShiftLeft)
+            },
+            '<' => self.either('=', TokenKind::LessThanOrEqual, TokenKind::LessThan),
+            '>' if self.peek() == Some('>') => {
+                self.bump();
+                self.either('=', TokenKind::ShiftRightAssignment, TokenKind::ShiftRight)
+            },
+            '>' => self.either('=', TokenKind::GreaterThanOrEqual, TokenKind::GreaterThan),
+            '&' if self.peek() == Some('&') => {
+                self.bump();
+                TokenKind::And
+            },
+            '&' => self.either('=', TokenKind::BitwiseAndAssignment, TokenKind::BitwiseAnd),
+            '|' if self.peek() == Some('|') => {
+                self.bump();
+                TokenKind::Or
+            },
+            '|' => self.either('=', TokenKind::BitwiseOrAssignment, TokenKind::BitwiseOr),
+            '^' => self.either('=', TokenKind::BitwiseXorAssignment, TokenKind::BitwiseXor),
+            '0'..='9' => self.integer_literal(&start)?,
+            '\'' => self.character_literal(&start)?,
+            '"' => self.string_literal(&start)?,
+            'a'..='z' | 'A'..='Z' | '_' => self.identifier_or_keyword(&start),
+            _ => return Err(LexError {
+                kind: LexErrorKind::UnexpectedCharacter(character),
+                span: self.span_from(&start),
+            }),
+        };
+
+        return Ok(Token { kind: kind, span: self.span_from(&start) });
     }
+}
 
-    if leftover_contents.chars().count() > 0 {
-        panic!("Error parsing program");
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Result<Token, LexError>> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.next_token();
+        if result.is_err() {
+            self.finished = true;
+        }
+        return Some(result);
     }
+}
 
-    return tokens;
+pub fn parse(contents: &str, file_name: &str) -> Result<Vec<Token>, Diagnostic> {
+    let tokens = Lexer::new(contents, file_name).collect::<Result<Vec<Token>, LexError>>()?;
+    return Ok(tokens);
 }
@@ -1,24 +1,61 @@
+#![allow(
+    clippy::needless_return,
+    clippy::needless_late_init,
+    clippy::redundant_field_names,
+    clippy::single_match,
+)]
+
+mod span;
+mod types;
+mod diagnostic;
 mod lexer;
 mod parser;
+mod sema;
 mod generator;
 mod asm;
+mod target;
 
 extern crate clap;
-extern crate regex;
 
 use std::io;
 use asm::Asm;
 use std::fs::File;
+use std::path::Path;
 use clap::{Arg, App};
 use std::io::prelude::*;
 use std::process::Command;
-use parser::program::Program;
+use std::thread;
+use sema::Program;
+use diagnostic::Diagnostic;
+use target::Target;
+
+// Parsing, checking and generating code all recurse through expressions,
+// which the parser lets be nested far deeper than the main thread's stack
+// would allow, so the compiler runs on a thread of its own
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
+    let compiler = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(compile)
+        .expect("Unable to start the compiler thread");
+    // It's already reported the panic
+    if compiler.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn compile() {
+    let triples: Vec<&str> = target::TARGETS.iter().map(Target::triple).collect();
     let matches = App::new("acc")
                       .arg(Arg::with_name("debug")
                            .short("d")
                            .help("Debug mode"))
+                      .arg(Arg::with_name("target")
+                           .long("target")
+                           .takes_value(true)
+                           .possible_values(&triples)
+                           .help("Platform to generate assembly for [default: the host]"))
                       .arg(Arg::with_name("INPUT")
                            .help("Sets the input file to use")
                            .required(true)
@@ -26,39 +63,62 @@ fn main() {
                       .get_matches();
     let file_name = matches.value_of("INPUT").unwrap().to_string();
     let debug = matches.is_present("debug");
+    let target = matches.value_of("target")
+        .and_then(Target::from_triple)
+        .unwrap_or_else(Target::host);
 
     let contents = read_file(&file_name);
-    let tokens = lexer::parse(contents);
+    let tokens = lexer::parse(&contents, &file_name)
+        .unwrap_or_else(|diagnostic| exit_with_diagnostic(&diagnostic, &contents));
 
     if debug {
         println!("-----Tokens-----");
         println!("{:#?}", tokens);
     }
 
+    let parsed_program: parser::program::Program;
+    match parser::program::parse(&tokens) {
+        Ok((match_program, warnings)) => {
+            print_diagnostics(&warnings, &contents);
+            parsed_program = match_program;
+        },
+        Err(diagnostics) => exit_with_diagnostics(&diagnostics, &contents),
+    }
+
     let program: Program;
-    match parser::program::parse(tokens) {
-        Ok(match_program) => program = match_program,
-        Err(err) => panic!("{}", err),
+    match sema::program::analyze(parsed_program) {
+        Ok((analyzed_program, warnings)) => {
+            print_diagnostics(&warnings, &contents);
+            program = analyzed_program;
+        },
+        Err(diagnostics) => exit_with_diagnostics(&diagnostics, &contents),
     }
 
     if debug {
-        println!("");
+        println!();
         println!("-----AST-----");
         println!("{:#?}", program);
     }
 
-    let assembly_file_name = file_name.replace(".c", ".s");
+    // Preprocessed `.i` files are accepted as well as `.c`
+    let assembly_file_name = Path::new(&file_name).with_extension("s").to_string_lossy().into_owned();
 
-    let mut asm: Asm = Default::default();
+    let mut asm = Asm { target: target, ..Default::default() };
     generator::program::asm(&mut asm, program);
     if debug {
-        println!("");
+        println!();
         println!("-----ASM-----");
         println!("{}", asm.source);
     }
     write_file(&assembly_file_name, &asm.source);
 
-    let executable_file_name = file_name.replace(".c", "");
+    // The host's gcc can't link for another platform, so leave the assembly
+    // for the target's toolchain
+    if target != Target::host() {
+        return;
+    }
+
+    let executable_file_name = Path::new(&file_name).with_extension("").to_string_lossy().into_owned();
     let output = Command::new("sh")
         .arg("-c")
         .arg(format!("gcc {} -o {}", assembly_file_name, executable_file_name))
@@ -66,26 +126,55 @@ fn main() {
         .expect("failed to execute process");
 
     if debug {
-        println!("");
+        println!();
         println!("-----GCC status-----");
         println!("status: {}", output.status);
     }
 
     io::stdout().write_all(&output.stdout).unwrap();
     io::stderr().write_all(&output.stderr).unwrap();
-    assert!(output.status.success());
+    if !output.status.success() {
+        exit_with_error(&format!("gcc failed to assemble '{}'", assembly_file_name));
+    }
 
     std::fs::remove_file(assembly_file_name).expect("Unable to delete assembly file");
 }
 
+fn exit_with_diagnostic(diagnostic: &Diagnostic, source: &str) -> ! {
+    exit_with_diagnostics(std::slice::from_ref(diagnostic), source);
+}
+
+fn exit_with_diagnostics(diagnostics: &[Diagnostic], source: &str) -> ! {
+    print_diagnostics(diagnostics, source);
+    let error_count = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+    if error_count > 1 {
+        eprintln!("error: aborting due to {} previous errors", error_count);
+    }
+    std::process::exit(1);
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic], source: &str) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.render(source));
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1);
+}
+
 fn read_file(file_name: &String) -> String {
-    let mut file = File::open(file_name).expect("Unable to open the file");
     let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("Unable to read the file");
-    return contents;
+    match File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => return contents,
+        Err(err) => exit_with_error(&format!("Unable to read '{}': {}", file_name, err)),
+    }
 }
 
 fn write_file(file_name: &String, contents: &String) {
-    let mut file = File::create(file_name).expect("Unable to create file");
-    file.write_all(contents.as_bytes()).expect("Unable to write to file");
+    match File::create(file_name).and_then(|mut file| file.write_all(contents.as_bytes())) {
+        Ok(_) => (),
+        Err(err) => exit_with_error(&format!("Unable to write '{}': {}", file_name, err)),
+    }
 }
@@ -0,0 +1,217 @@
+use parser::expression::{Expression, ExpressionKind, BinaryOperator};
+use parser::factor::{Factor, FactorKind, UnaryOperator};
+use types::Type;
+
+// A value worked out at compile time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    // Kept in an `i64` as its type would have it, see `Type::wrap`
+    Integer(i64),
+    // A `float` is kept rounded to one
+    Floating(f64),
+}
+
+impl Value {
+    fn is_zero(self) -> bool {
+        match self {
+            Value::Integer(value) => return value == 0,
+            Value::Floating(value) => return value == 0.0,
+        }
+    }
+
+    // The value of type `from` converted to `to`, as if it had been stored
+    // in a variable of that type. Floating values are truncated towards
+    // zero to convert them to integers.
+    pub fn convert(self, from: &Type, to: &Type) -> Value {
+        if *to == Type::Float {
+            match self {
+                Value::Integer(value) if from.is_signed() => return Value::Floating(f64::from(value as f32)),
+                Value::Integer(value) => return Value::Floating(f64::from(value as u64 as f32)),
+                Value::Floating(value) => return Value::Floating(f64::from(value as f32)),
+            }
+        }
+        if *to == Type::Double {
+            match self {
+                Value::Integer(value) if from.is_signed() => return Value::Floating(value as f64),
+                Value::Integer(value) => return Value::Floating(value as u64 as f64),
+                Value::Floating(value) => return Value::Floating(value),
+            }
+        }
+        match self {
+            Value::Integer(value) => return Value::Integer(to.wrap(value)),
+            Value::Floating(value) if to.is_signed() => return Value::Integer(to.wrap(value as i64)),
+            Value::Floating(value) => return Value::Integer(to.wrap(value as u64 as i64)),
+        }
+    }
+}
+
+// The value of an integer expression that can be worked out at compile
+// time, like an array's size. `None` if it refers to variables or calls,
+// or would divide by zero. Assignments and the comma operator aren't
+// allowed in constant expressions.
+pub fn evaluate(expression: &Expression) -> Option<i64> {
+    match typed(expression)? {
+        (Value::Integer(value), _) => return Some(value),
+        (Value::Floating(_), _) => return None,
+    }
+}
+
+// The value converted to `ty`, as needed for the initialiser of a global
+pub fn evaluate_as(expression: &Expression, ty: &Type) -> Option<Value> {
+    let (value, value_type) = typed(expression)?;
+    return Some(value.convert(&value_type, ty));
+}
+
+// The value with its type, which decides how it's worked out, e.g. whether
+// `-1 < 0u` compares as unsigned
+pub fn typed(expression: &Expression) -> Option<(Value, Type)> {
+    match expression.kind {
+        ExpressionKind::Assignment(_) => return None,
+        ExpressionKind::Conditional(ref conditional) => {
+            let (condition, _) = typed(&conditional.condition)?;
+            let (then_value, then_type) = typed(&conditional.expression)?;
+            let (else_value, else_type) = typed(&conditional.else_expression)?;
+            let ty = then_type.common_type(&else_type);
+            if !condition.is_zero() {
+                return Some((then_value.convert(&then_type, &ty), ty));
+            } else {
+                return Some((else_value.convert(&else_type, &ty), ty));
+            }
+        },
+        ExpressionKind::Binary(ref binary) => {
+            let left = typed(&binary.left)?;
+            let right = typed(&binary.right)?;
+            return binary_operation(&binary.operator, left, right);
+        },
+        ExpressionKind::Factor(ref factor) => return self::factor(factor),
+    }
+}
+
+// Both operands are converted to their common type first, except for
+// shifts, which have the type of their left operand. Shift counts are
+// masked to the size of that, as `sal` and `sar` do.
+fn binary_operation(operator: &BinaryOperator, left: (Value, Type), right: (Value, Type)) -> Option<(Value, Type)> {
+    let (left, left_type) = left;
+    let (right, right_type) = right;
+    let truth = |is_true: bool| Some((Value::Integer(i64::from(is_true)), Type::Int));
+
+    match operator {
+        BinaryOperator::Comma => return None,
+        BinaryOperator::LogicalOr => return truth(!left.is_zero() || !right.is_zero()),
+        BinaryOperator::LogicalAnd => return truth(!left.is_zero() && !right.is_zero()),
+        BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+            let (left, right) = match (left, right) {
+                (Value::Integer(left), Value::Integer(right)) => (left, right),
+                _ => return None,
+            };
+            let ty = left_type.promote();
+            let count = (right as u32) & (8 * ty.size() as u32 - 1);
+            let value: i64;
+            match operator {
+                BinaryOperator::LeftShift => value = left.wrapping_shl(count),
+                _ if ty.is_signed() => value = left.wrapping_shr(count),
+                _ => value = ((left as u64) >> count) as i64,
+            }
+            return Some((Value::Integer(ty.wrap(value)), ty));
+        },
+        _ => (),
+    }
+
+    let ty = left_type.common_type(&right_type);
+    match (left.convert(&left_type, &ty), right.convert(&right_type, &ty)) {
+        (Value::Integer(left), Value::Integer(right)) => return integer_operation(operator, left, right, ty),
+        (Value::Floating(left), Value::Floating(right)) => return floating_operation(operator, left, right, ty),
+        _ => panic!("Operands weren't converted to their common type"),
+    }
+}
+
+fn integer_operation(operator: &BinaryOperator, left: i64, right: i64, ty: Type) -> Option<(Value, Type)> {
+    let truth = |is_true: bool| Some((Value::Integer(i64::from(is_true)), Type::Int));
+    // 8 byte unsigned values are the only ones that don't compare right as
+    // `i64`s
+    let is_less = if ty.is_signed() { left < right } else { (left as u64) < (right as u64) };
+
+    let value: i64;
+    match operator {
+        BinaryOperator::BitwiseOr => value = left | right,
+        BinaryOperator::BitwiseXor => value = left ^ right,
+        BinaryOperator::BitwiseAnd => value = left & right,
+        BinaryOperator::Equal => return truth(left == right),
+        BinaryOperator::NotEqual => return truth(left != right),
+        BinaryOperator::LessThan => return truth(is_less),
+        BinaryOperator::LessThanOrEqual => return truth(is_less || left == right),
+        BinaryOperator::GreaterThan => return truth(!is_less && left != right),
+        BinaryOperator::GreaterThanOrEqual => return truth(!is_less),
+        BinaryOperator::Addition => value = left.wrapping_add(right),
+        BinaryOperator::Subtraction => value = left.wrapping_sub(right),
+        BinaryOperator::Multiplication => value = left.wrapping_mul(right),
+        BinaryOperator::Division if ty.is_signed() => value = left.checked_div(right)?,
+        BinaryOperator::Division => value = (left as u64).checked_div(right as u64)? as i64,
+        BinaryOperator::Modulo if ty.is_signed() => value = left.checked_rem(right)?,
+        BinaryOperator::Modulo => value = (left as u64).checked_rem(right as u64)? as i64,
+        _ => panic!("{:?} was handled before converting", operator),
+    }
+    return Some((Value::Integer(ty.wrap(value)), ty));
+}
+
+// A `float` result is worked out as a `double` and then rounded, which
+// comes out the same as working it out as a `float`
+fn floating_operation(operator: &BinaryOperator, left: f64, right: f64, ty: Type) -> Option<(Value, Type)> {
+    let truth = |is_true: bool| Some((Value::Integer(i64::from(is_true)), Type::Int));
+
+    let value: f64;
+    match operator {
+        BinaryOperator::Equal => return truth(left == right),
+        BinaryOperator::NotEqual => return truth(left != right),
+        BinaryOperator::LessThan => return truth(left < right),
+        BinaryOperator::LessThanOrEqual => return truth(left <= right),
+        BinaryOperator::GreaterThan => return truth(left > right),
+        BinaryOperator::GreaterThanOrEqual => return truth(left >= right),
+        BinaryOperator::Addition => value = left + right,
+        BinaryOperator::Subtraction => value = left - right,
+        BinaryOperator::Multiplication => value = left * right,
+        BinaryOperator::Division => value = left / right,
+        // Only allowed on integers
+        _ => return None,
+    }
+    return Some((Value::Floating(value).convert(&Type::Double, &ty), ty));
+}
+
+fn factor(factor: &Factor) -> Option<(Value, Type)> {
+    match factor.kind {
+        FactorKind::Expression(ref expression) => return typed(expression),
+        FactorKind::UnaryOperation(ref operation) => {
+            let (value, ty) = self::factor(&operation.factor)?;
+            let ty = ty.promote();
+            match (&operation.operator, value) {
+                (UnaryOperator::LogicalNegation, value) => return Some((Value::Integer(i64::from(value.is_zero())), Type::Int)),
+                (UnaryOperator::Negation, Value::Integer(value)) => return Some((Value::Integer(ty.wrap(value.wrapping_neg())), ty)),
+                (UnaryOperator::Negation, Value::Floating(value)) => return Some((Value::Floating(-value), ty)),
+                (UnaryOperator::BitwiseComplement, Value::Integer(value)) => return Some((Value::Integer(ty.wrap(!value)), ty)),
+                (UnaryOperator::BitwiseComplement, Value::Floating(_)) => return None,
+                (UnaryOperator::Dereference, _) | (UnaryOperator::AddressOf, _) => return None,
+            }
+        },
+        FactorKind::Constant(value, ref ty) => return Some((Value::Integer(value), ty.clone())),
+        FactorKind::FloatingConstant(value, ref ty) => return Some((Value::Floating(value), ty.clone())),
+        FactorKind::StringLiteral(_) => return None,
+        FactorKind::Identifier(_) => return None,
+        FactorKind::FunctionCall(_) => return None,
+        FactorKind::PrefixIncrement(_) => return None,
+        FactorKind::PostfixIncrement(_) => return None,
+        FactorKind::Subscript(_) => return None,
+        FactorKind::Member(_) => return None,
+        // The operand's type isn't known until semantic analysis has
+        // checked it
+        FactorKind::SizeOf(ref operand) => {
+            return operand.ty.as_ref().map(|ty| (Value::Integer(ty.size()), Type::UnsignedLong));
+        },
+        FactorKind::SizeOfType(ref ty) => return Some((Value::Integer(ty.size()), Type::UnsignedLong)),
+        // Casts to pointers aren't worked out, only ones to arithmetic types
+        FactorKind::Cast(ref cast) if cast.ty.is_arithmetic() => {
+            let (value, ty) = typed(&cast.expression)?;
+            return Some((value.convert(&ty, &cast.ty), cast.ty.clone()));
+        },
+        FactorKind::Cast(_) => return None,
+    }
+}
@@ -0,0 +1,133 @@
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, TagKind, NameKind};
+use parser::{error, declare_name};
+use parser::constant;
+use parser::expression;
+use parser::structure::wrong_kind_of_tag;
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
+
+// `enum tag`, `enum tag { constants }` or `enum { constants }`. Every enum
+// is an `int`, so it's only the constants that need keeping. A tag can only
+// refer to an enum that's already been defined.
+pub fn parse(tokens: &mut TokenStream) -> Result<Type, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordEnum => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'enum'")),
+    };
+
+    let tag: String;
+    let tag_span: Span;
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref name) => {
+            tag = name.clone();
+            tag_span = tokens.peek().span.clone();
+            tokens.advance();
+        },
+        TokenKind::OpenBrace => {
+            parse_constants(tokens)?;
+            return Ok(Type::Int);
+        },
+        _ => return Err(error(tokens.peek(), "Expecting identifier or '{'")),
+    }
+
+    let is_definition = tokens.peek().kind == TokenKind::OpenBrace;
+    let previous: Option<(Span, bool)>;
+    match tokens.lookup_tag(&tag) {
+        Some((tag, is_current_scope)) if is_current_scope || !is_definition => {
+            let is_enum = match tag.kind {
+                TagKind::Enum => true,
+                TagKind::Struct(_) => false,
+            };
+            previous = Some((tag.span.clone(), is_enum));
+        },
+        _ => previous = None,
+    }
+
+    match previous {
+        Some((previous_span, false)) => tokens.report(wrong_kind_of_tag(&tag, tag_span, previous_span)),
+        Some((previous_span, true)) if is_definition => tokens.report(Diagnostic::error(
+            format!("Redefinition of 'enum {}'", tag),
+            tag_span,
+        ).with_label(previous_span, "previously defined here")),
+        Some(_) => (),
+        None if is_definition => tokens.declare_tag(tag, tag_span, TagKind::Enum),
+        None => tokens.report(Diagnostic::error(
+            format!("Use of undefined 'enum {}'", tag),
+            tag_span,
+        ).with_note("an enum has to be defined before its tag can be used")),
+    }
+
+    if is_definition {
+        parse_constants(tokens)?;
+    }
+    return Ok(Type::Int);
+}
+
+// `{ A, B = 5, C }`, which can have a trailing comma. A constant without a
+// value is one more than the one before it, or 0 if it's the first. Each is
+// in scope straight after it's declared, so later values can use it. As in
+// ISO C, every value has to fit in an `int`.
+fn parse_constants(tokens: &mut TokenStream) -> Result<(), Diagnostic> {
+    let start = tokens.peek();
+    match start.kind {
+        TokenKind::OpenBrace => tokens.advance(),
+        _ => return Err(error(start, "Expecting '{'")),
+    };
+    if tokens.peek().kind == TokenKind::CloseBrace {
+        tokens.advance();
+        return Err(Diagnostic::error("Empty enum is invalid", start.span.to(&tokens.previous().span)));
+    }
+
+    let mut value: i64 = 0;
+    loop {
+        let name: String;
+        let name_span = tokens.peek().span.clone();
+        match tokens.peek().kind {
+            TokenKind::Identifier(ref matched_name) => {
+                name = matched_name.clone();
+                tokens.advance();
+            },
+            _ => return Err(error(tokens.peek(), "Expecting identifier")),
+        }
+
+        let mut value_span = name_span.clone();
+        if tokens.peek().kind == TokenKind::Assignment {
+            tokens.advance();
+            let expression = expression::parse_assignment_expression(tokens)?;
+            value_span = expression.span.clone();
+            match constant::evaluate(&expression) {
+                Some(constant) => value = constant,
+                None => tokens.report(Diagnostic::error(
+                    format!("Enumerator value for '{}' is not an integer constant", name),
+                    expression.span,
+                )),
+            }
+        }
+        if value != Type::Int.wrap(value) {
+            tokens.report(Diagnostic::error(
+                format!("Enumerator value for '{}' doesn't fit in an 'int'", name),
+                value_span,
+            ).with_note(format!("its value is {}", value)));
+        }
+        declare_name(tokens, &name, &name_span, NameKind::EnumConstant(Type::Int.wrap(value)));
+        value = Type::Int.wrap(value) + 1;
+
+        match tokens.peek().kind {
+            TokenKind::Comma => {
+                tokens.advance();
+                if tokens.peek().kind == TokenKind::CloseBrace {
+                    tokens.advance();
+                    break;
+                }
+            },
+            TokenKind::CloseBrace => {
+                tokens.advance();
+                break;
+            },
+            _ => return Err(error(tokens.peek(), "Expecting ',' or '}'")),
+        };
+    }
+    return Ok(());
+}
@@ -1,348 +1,334 @@
-use parser::term;
-use parser::StackFrame;
-use parser::term::Term;
-use lexer::Token;
+use parser::factor;
+use parser::error;
+use parser::factor::{Factor, FactorKind};
+use lexer::TokenKind;
+use parser::token_stream::TokenStream;
+use span::Span;
+use diagnostic::Diagnostic;
+use sema::SymbolId;
+use types::Type;
+use std::fmt;
 
 #[derive(Debug, Clone)]
-pub enum Expression {
-    Assignment(Box<Assignment>),
-    LogicalOrExpression(LogicalOrExpression),
-}
-
-#[derive(Debug, Clone)]
-pub struct LogicalOrExpression {
-    pub expression: LogicalAndExpression,
-    pub binary_expressions: Vec<BinaryLogicalAndExpression>,
-}
-
-#[derive(Debug, Clone)]
-pub struct BinaryLogicalAndExpression {
-    pub operator: LogicalOrOperator,
-    pub right_expression: LogicalAndExpression,
-}
-
-#[derive(Debug, Clone)]
-pub enum LogicalOrOperator {
-    Or,
-}
-
-#[derive(Debug, Clone)]
-pub struct LogicalAndExpression {
-    pub expression: EqualityExpression,
-    pub binary_expressions: Vec<BinaryEqualityExpression>,
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+    // Filled in by semantic analysis
+    pub ty: Option<Type>,
 }
 
-#[derive(Debug, Clone)]
-pub struct BinaryEqualityExpression {
-    pub operator: LogicalAndOperator,
-    pub right_expression: EqualityExpression,
+impl Expression {
+    pub fn ty(&self) -> &Type {
+        return self.ty.as_ref().expect("Expression wasn't type checked");
+    }
 }
 
 #[derive(Debug, Clone)]
-pub enum LogicalAndOperator {
-    And,
+pub enum ExpressionKind {
+    Assignment(Box<Assignment>),
+    Conditional(Box<ConditionalExpression>),
+    Binary(Box<BinaryExpression>),
+    Factor(Factor),
 }
 
+// `condition ? expression : else_expression`
 #[derive(Debug, Clone)]
-pub struct EqualityExpression {
-    pub expression: RelationalExpression,
-    pub binary_expressions: Vec<BinaryRelationalExpression>,
+pub struct ConditionalExpression {
+    pub condition: Expression,
+    pub expression: Expression,
+    pub else_expression: Expression,
 }
 
 #[derive(Debug, Clone)]
-pub struct BinaryRelationalExpression {
-    pub operator: EqualityOperator,
-    pub right_expression: RelationalExpression,
+pub struct BinaryExpression {
+    pub operator: BinaryOperator,
+    pub left: Expression,
+    pub right: Expression,
 }
 
-#[derive(Debug, Clone)]
-pub enum EqualityOperator {
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryOperator {
+    Comma,
+    LogicalOr,
+    LogicalAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
     Equal,
     NotEqual,
-}
-
-#[derive(Debug, Clone)]
-pub struct RelationalExpression {
-    pub expression: AdditiveExpression,
-    pub binary_expressions: Vec<BinaryAdditiveExpression>,
-}
-
-#[derive(Debug, Clone)]
-pub struct BinaryAdditiveExpression {
-    pub operator: RelationalOperator,
-    pub right_expression: AdditiveExpression,
-}
-
-#[derive(Debug, Clone)]
-pub enum RelationalOperator {
     LessThan,
     LessThanOrEqual,
     GreaterThan,
     GreaterThanOrEqual,
-}
-
-#[derive(Debug, Clone)]
-pub struct AdditiveExpression {
-    pub term: Term,
-    pub binary_terms: Vec<BinaryTerms>,
-}
-
-#[derive(Debug, Clone)]
-pub struct BinaryTerms {
-    pub operator: AdditiveOperator,
-    pub right_term: Term,
-}
-
-
-#[derive(Debug, Clone)]
-pub enum AdditiveOperator {
-    Subtraction,
+    LeftShift,
+    RightShift,
     Addition,
+    Subtraction,
+    Multiplication,
+    Division,
+    Modulo,
 }
 
 #[derive(Debug, Clone)]
 pub struct Var {
     pub name: String,
+    pub span: Span,
+    // The variable the name refers to, once semantic analysis has resolved
+    // it
+    pub symbol: Option<SymbolId>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Assignment {
-    pub var: Var,
+    // Any unary expression, which semantic analysis checks is assignable
+    pub target: Factor,
+    // The binary operator of a compound assignment like `+=`, or `None`
+    // for a plain `=`
+    pub operator: Option<BinaryOperator>,
     pub expression: Expression,
 }
 
-pub fn parse(
-    tokens: Vec<Token>,
-    stack_frame: &StackFrame,
-) -> Result<(Expression, Vec<Token>), String> {
-    match parse_assignment(tokens.clone(), stack_frame) {
-        Ok((assignment, leftover_tokens)) => {
-            match stack_frame.vars.get(&assignment.var.name) {
-                None => return Err(format!("Var '{}' hasn't been declared", assignment.var.name)),
-                _ => (),
-            }
-
-            return Ok((
-                Expression::Assignment(Box::new(assignment)),
-                leftover_tokens
-            ));
-        },
-        Err(_) => (),
-    }
-
-    let (expression, leftover_tokens) = parse_logical_or(tokens.clone(), stack_frame)?;
-    return Ok((
-        Expression::LogicalOrExpression(expression),
-        leftover_tokens,
-    ));
+// How tightly each level binds, loosest first
+mod precedence {
+    pub const COMMA: u8 = 1;
+    pub const ASSIGNMENT: u8 = 2;
+    pub const CONDITIONAL: u8 = 3;
+    pub const LOGICAL_OR: u8 = 4;
+    pub const LOGICAL_AND: u8 = 5;
+    pub const BITWISE_OR: u8 = 6;
+    pub const BITWISE_XOR: u8 = 7;
+    pub const BITWISE_AND: u8 = 8;
+    pub const EQUALITY: u8 = 9;
+    pub const RELATIONAL: u8 = 10;
+    pub const SHIFT: u8 = 11;
+    pub const ADDITIVE: u8 = 12;
+    pub const MULTIPLICATIVE: u8 = 13;
 }
 
-pub fn parse_with_parens(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(Expression, Vec<Token>), String> {
-    match tokens.get(0) {
-        Some(Token::OpenParen) => (),
-        _ => return Err("Expecting '('".to_string()),
+// Written as the token it's parsed from, e.g. `'+'`
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (token, _, _) = BINARY_OPERATORS.iter()
+            .find(|(_, operator, _)| operator == self)
+            .expect("Binary operator without a token");
+        write!(f, "{}", token)
     }
+}
 
-    let expression: Expression;
-    let leftover_tokens: Vec<Token>;
-    let (matched_expression, tokens) = parse(tokens[1..].to_vec(), stack_frame)?;
-    expression = matched_expression;
-    leftover_tokens = tokens;
-
-    match leftover_tokens[0] {
-        Token::CloseParen => (),
-        _ => return Err("Expecting ')'".to_string()),
-    }
+// The binary operators, which all group left to right
+const BINARY_OPERATORS: [(TokenKind, BinaryOperator, u8); 19] = [
+    (TokenKind::Comma, BinaryOperator::Comma, precedence::COMMA),
+    (TokenKind::Or, BinaryOperator::LogicalOr, precedence::LOGICAL_OR),
+    (TokenKind::And, BinaryOperator::LogicalAnd, precedence::LOGICAL_AND),
+    (TokenKind::BitwiseOr, BinaryOperator::BitwiseOr, precedence::BITWISE_OR),
+    (TokenKind::BitwiseXor, BinaryOperator::BitwiseXor, precedence::BITWISE_XOR),
+    (TokenKind::BitwiseAnd, BinaryOperator::BitwiseAnd, precedence::BITWISE_AND),
+    (TokenKind::Equal, BinaryOperator::Equal, precedence::EQUALITY),
+    (TokenKind::NotEqual, BinaryOperator::NotEqual, precedence::EQUALITY),
+    (TokenKind::LessThan, BinaryOperator::LessThan, precedence::RELATIONAL),
+    (TokenKind::LessThanOrEqual, BinaryOperator::LessThanOrEqual, precedence::RELATIONAL),
+    (TokenKind::GreaterThan, BinaryOperator::GreaterThan, precedence::RELATIONAL),
+    (TokenKind::GreaterThanOrEqual, BinaryOperator::GreaterThanOrEqual, precedence::RELATIONAL),
+    (TokenKind::ShiftLeft, BinaryOperator::LeftShift, precedence::SHIFT),
+    (TokenKind::ShiftRight, BinaryOperator::RightShift, precedence::SHIFT),
+    (TokenKind::PlusSign, BinaryOperator::Addition, precedence::ADDITIVE),
+    (TokenKind::MinusSign, BinaryOperator::Subtraction, precedence::ADDITIVE),
+    (TokenKind::MultiplicationSign, BinaryOperator::Multiplication, precedence::MULTIPLICATIVE),
+    (TokenKind::DivisionSign, BinaryOperator::Division, precedence::MULTIPLICATIVE),
+    (TokenKind::ModuloSign, BinaryOperator::Modulo, precedence::MULTIPLICATIVE),
+];
+
+// `=` and the compound assignments, which group right to left
+const ASSIGNMENT_OPERATORS: [(TokenKind, Option<BinaryOperator>); 11] = [
+    (TokenKind::Assignment, None),
+    (TokenKind::PlusAssignment, Some(BinaryOperator::Addition)),
+    (TokenKind::MinusAssignment, Some(BinaryOperator::Subtraction)),
+    (TokenKind::MultiplicationAssignment, Some(BinaryOperator::Multiplication)),
+    (TokenKind::DivisionAssignment, Some(BinaryOperator::Division)),
+    (TokenKind::ModuloAssignment, Some(BinaryOperator::Modulo)),
+    (TokenKind::ShiftLeftAssignment, Some(BinaryOperator::LeftShift)),
+    (TokenKind::ShiftRightAssignment, Some(BinaryOperator::RightShift)),
+    (TokenKind::BitwiseAndAssignment, Some(BinaryOperator::BitwiseAnd)),
+    (TokenKind::BitwiseOrAssignment, Some(BinaryOperator::BitwiseOr)),
+    (TokenKind::BitwiseXorAssignment, Some(BinaryOperator::BitwiseXor)),
+];
+
+// A full expression, including the comma operator
+pub fn parse(tokens: &mut TokenStream) -> Result<Expression, Diagnostic> {
+    return parse_with_precedence(tokens, precedence::COMMA);
+}
 
-    return Ok((expression, leftover_tokens[1..].to_vec()))
+// An expression without a top level comma, as in function arguments and
+// initialisers
+pub fn parse_assignment_expression(tokens: &mut TokenStream) -> Result<Expression, Diagnostic> {
+    return parse_with_precedence(tokens, precedence::ASSIGNMENT);
 }
 
-pub fn parse_logical_or(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(LogicalOrExpression, Vec<Token>), String> {
-    let expression: LogicalAndExpression;
-    let mut binary_expressions: Vec<BinaryLogicalAndExpression> = Vec::new();
-    let mut leftover_tokens: Vec<Token>;
+pub fn parse_with_parens(tokens: &mut TokenStream) -> Result<Expression, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::OpenParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '('")),
+    };
 
-    let (matched_expression, tokens) = parse_logical_and(tokens, stack_frame)?;
-    expression = matched_expression;
-    leftover_tokens = tokens;
+    let expression = parse(tokens)?;
 
-    while let Some(operator) = expression_operator_for_token(&leftover_tokens[0]) {
-        let (matched_expression, tokens) = parse_logical_and(leftover_tokens[1..].to_vec(), stack_frame)?;
-        binary_expressions.push(BinaryLogicalAndExpression {
-            operator: operator,
-            right_expression: matched_expression,
-        });
-        leftover_tokens = tokens;
-    }
+    match tokens.peek().kind {
+        TokenKind::CloseParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ')'")),
+    };
 
-    return Ok((
-        LogicalOrExpression {
-            expression: expression,
-            binary_expressions: binary_expressions
-        },
-        leftover_tokens,
-    ));
+    return Ok(expression);
 }
 
-fn parse_logical_and(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(LogicalAndExpression, Vec<Token>), String> {
-    let mut binary_expressions: Vec<BinaryEqualityExpression> = Vec::new();
-    let mut leftover_tokens: Vec<Token>;
-
-    let (expression, tokens) = parse_equality(tokens, stack_frame)?;
-    leftover_tokens = tokens;
-
-    while let Some(operator) = logical_and_operator_for_token(&leftover_tokens[0]) {
-        let (matched_expression, tokens) = parse_equality(leftover_tokens[1..].to_vec(), stack_frame)?;
-        binary_expressions.push(BinaryEqualityExpression {
-            operator: operator,
-            right_expression: matched_expression,
-        });
-        leftover_tokens = tokens;
-    }
-
-    return Ok((
-        LogicalAndExpression {
-            expression: expression,
-            binary_expressions: binary_expressions
-        },
-        leftover_tokens,
-    ));
+// Back out of however deep the expression went once it's parsed, whether or
+// not it parsed
+fn parse_with_precedence(tokens: &mut TokenStream, min_precedence: u8) -> Result<Expression, Diagnostic> {
+    let nesting = tokens.nesting();
+    let expression = parse_operators(tokens, min_precedence);
+    tokens.unnest(nesting);
+    return expression;
 }
 
-fn parse_equality(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(EqualityExpression, Vec<Token>), String> {
-    let mut binary_expressions: Vec<BinaryRelationalExpression> = Vec::new();
-    let mut leftover_tokens: Vec<Token>;
+// Precedence climbing: take an operand, then keep folding it into the left
+// side of any operator that binds at least as tightly as `min_precedence`.
+// Each one folded in puts the operand a level deeper.
+fn parse_operators(tokens: &mut TokenStream, min_precedence: u8) -> Result<Expression, Diagnostic> {
+    let factor = factor::parse(tokens)?;
+    let mut left = Expression { span: factor.span.clone(), kind: ExpressionKind::Factor(factor), ty: None };
 
-    let (expression, tokens) = parse_relational(tokens, stack_frame)?;
-    leftover_tokens = tokens;
+    loop {
+        let token = tokens.peek();
 
-    while let Some(operator) = equality_operator_for_token(&leftover_tokens[0]) {
-        let (matched_expression, tokens) = parse_relational(leftover_tokens[1..].to_vec(), stack_frame)?;
-        binary_expressions.push(BinaryRelationalExpression {
-            operator: operator,
-            right_expression: matched_expression,
-        });
-        leftover_tokens = tokens;
+        if let Some((operator, precedence)) = binary_operator_for_token(&token.kind) {
+            if precedence < min_precedence {
+                return Ok(left);
+            }
+            tokens.nest()?;
+            tokens.advance();
+
+            let right = parse_with_precedence(tokens, precedence + 1)?;
+
+            let span = left.span.to(&right.span);
+            left = Expression {
+                kind: ExpressionKind::Binary(Box::new(BinaryExpression {
+                    operator: operator,
+                    left: left,
+                    right: right,
+                })),
+                span: span,
+                ty: None,
+            };
+        } else if let Some(operator) = assignment_operator_for_token(&token.kind) {
+            if precedence::ASSIGNMENT < min_precedence {
+                return Ok(left);
+            }
+            tokens.nest()?;
+            left = parse_assignment(tokens, left, operator)?;
+        } else if token.kind == TokenKind::QuestionMark {
+            if precedence::CONDITIONAL < min_precedence {
+                return Ok(left);
+            }
+            tokens.nest()?;
+            left = parse_conditional(tokens, left)?;
+        } else {
+            return Ok(left);
+        }
     }
-
-    return Ok((
-        EqualityExpression {
-            expression: expression,
-            binary_expressions: binary_expressions
-        },
-        leftover_tokens,
-    ));
 }
 
-fn parse_relational(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(RelationalExpression, Vec<Token>), String> {
-    let mut binary_expressions: Vec<BinaryAdditiveExpression> = Vec::new();
-    let mut leftover_tokens: Vec<Token>;
+// `left ?`, with the '?' next
+fn parse_conditional(tokens: &mut TokenStream, condition: Expression) -> Result<Expression, Diagnostic> {
+    tokens.advance();
 
-    let (expression, tokens) = parse_additive(tokens, stack_frame)?;
-    leftover_tokens = tokens;
+    let expression = parse(tokens)?;
 
-    while let Some(operator) = relational_operator_for_token(&leftover_tokens[0]) {
-        let (matched_expression, tokens) = parse_additive(leftover_tokens[1..].to_vec(), stack_frame)?;
-        binary_expressions.push(BinaryAdditiveExpression {
-            operator: operator,
-            right_expression: matched_expression,
-        });
-        leftover_tokens = tokens;
-    }
+    match tokens.peek().kind {
+        TokenKind::Colon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ':'")),
+    };
 
-    return Ok((
-        RelationalExpression {
+    let else_expression = parse_with_precedence(tokens, precedence::CONDITIONAL)?;
+    let span = condition.span.to(&else_expression.span);
+    return Ok(Expression {
+        kind: ExpressionKind::Conditional(Box::new(ConditionalExpression {
+            condition: condition,
             expression: expression,
-            binary_expressions: binary_expressions
-        },
-        leftover_tokens,
-    ));
+            else_expression: else_expression,
+        })),
+        span: span,
+        ty: None,
+    });
 }
 
-fn parse_additive(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(AdditiveExpression, Vec<Token>), String> {
-    let mut binary_terms: Vec<BinaryTerms> = Vec::new();
-    let mut leftover_tokens: Vec<Token>;
+// `left =`, with the assignment operator next. Only a unary expression can
+// be assigned to, and semantic analysis checks it's one that's assignable.
+fn parse_assignment(
+    tokens: &mut TokenStream,
+    left: Expression,
+    operator: Option<BinaryOperator>,
+) -> Result<Expression, Diagnostic> {
+    let span = left.span.clone();
+    let target: Factor;
+    match left.kind {
+        ExpressionKind::Factor(factor) => target = factor,
+        _ => return Err(Diagnostic::error(
+            format!("Left operand of {} is not assignable", tokens.peek().kind),
+            tokens.peek().span.clone(),
+        ).with_label(span, "not a unary expression")),
+    }
+    tokens.advance();
 
-    let (term, tokens) = term::parse(tokens, stack_frame)?;
-    leftover_tokens = tokens;
+    let expression = parse_with_precedence(tokens, precedence::ASSIGNMENT)?;
 
-    while let Some(operator) = additive_operator_for_token(&leftover_tokens[0]) {
-        let (matched_term, tokens) = term::parse(leftover_tokens[1..].to_vec(), stack_frame)?;
-        binary_terms.push(BinaryTerms {
+    let span = span.to(&expression.span);
+    return Ok(Expression {
+        kind: ExpressionKind::Assignment(Box::new(Assignment {
+            target: target,
             operator: operator,
-            right_term: matched_term,
-        });
-        leftover_tokens = tokens;
-    }
-
-    return Ok((
-        AdditiveExpression { term: term, binary_terms: binary_terms },
-        leftover_tokens,
-    ));
+            expression: expression,
+        })),
+        span: span,
+        ty: None,
+    });
 }
 
-fn parse_assignment(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(Assignment, Vec<Token>), String> {
-    let var: Var;
-
-    let mut leftover_tokens: Vec<Token>;
-    match tokens.get(0) {
-        Some(Token::Identifier(ref name)) => {
-            var = Var { name: name.clone() };
-            leftover_tokens = tokens[1..].to_vec();
-        },
-        _ => return Err("Invalid assignment: Expecting identifier".to_string()),
-    }
-
-    match leftover_tokens.get(0) {
-        Some(Token::Assignment) => {
-            leftover_tokens = leftover_tokens[1..].to_vec();
+// The bytes of the expression if it's just a string literal, possibly in
+// parentheses
+pub fn string_literal(expression: &Expression) -> Option<&[u8]> {
+    match expression.kind {
+        ExpressionKind::Factor(ref factor) => match factor.kind {
+            FactorKind::StringLiteral(ref bytes) => return Some(bytes),
+            FactorKind::Expression(ref expression) => return string_literal(expression),
+            _ => return None,
         },
-        _ => return Err("Invalid assignment: Expecting '='".to_string()),
-    }
-
-    let (expression, leftover_tokens) = parse(leftover_tokens, stack_frame)?;
-    return Ok((
-        Assignment { var: var, expression: expression },
-        leftover_tokens,
-    ));
-}
-
-fn expression_operator_for_token(token: &Token) -> Option<LogicalOrOperator> {
-    match token {
-        Token::Or => return Some(LogicalOrOperator::Or),
         _ => return None,
     }
 }
 
-fn logical_and_operator_for_token(token: &Token) -> Option<LogicalAndOperator> {
-    match token {
-        Token::And => return Some(LogicalAndOperator::And),
-        _ => return None,
+// Whether the expression names an object that can be assigned to
+pub fn is_lvalue(expression: &Expression) -> bool {
+    match expression.kind {
+        ExpressionKind::Factor(ref factor) => return factor::is_lvalue(factor),
+        _ => return false,
     }
 }
 
-fn equality_operator_for_token(token: &Token) -> Option<EqualityOperator> {
-    match token {
-        Token::Equal => return Some(EqualityOperator::Equal),
-        Token::NotEqual => return Some(EqualityOperator::NotEqual),
-        _ => return None,
-    }
+// The token an assignment is written with, e.g. `'+='`
+pub fn assignment_token(operator: &Option<BinaryOperator>) -> TokenKind {
+    let (token, _) = ASSIGNMENT_OPERATORS.iter()
+        .find(|(_, assignment_operator)| assignment_operator == operator)
+        .expect("Assignment operator without a token");
+    return token.clone();
 }
 
-fn relational_operator_for_token(token: &Token) -> Option<RelationalOperator> {
-    match token {
-        Token::LessThan => return Some(RelationalOperator::LessThan),
-        Token::LessThanOrEqual => return Some(RelationalOperator::LessThanOrEqual),
-        Token::GreaterThan => return Some(RelationalOperator::GreaterThan),
-        Token::GreaterThanOrEqual => return Some(RelationalOperator::GreaterThanOrEqual),
-        _ => return None,
-    }
+fn binary_operator_for_token(token: &TokenKind) -> Option<(BinaryOperator, u8)> {
+    return BINARY_OPERATORS.iter()
+        .find(|(kind, _, _)| kind == token)
+        .map(|(_, operator, precedence)| (operator.clone(), *precedence));
 }
 
-fn additive_operator_for_token(token: &Token) -> Option<AdditiveOperator> {
-    match token {
-        Token::MinusSign => return Some(AdditiveOperator::Subtraction),
-        Token::PlusSign => return Some(AdditiveOperator::Addition),
-        _ => return None,
-    }
+fn assignment_operator_for_token(token: &TokenKind) -> Option<Option<BinaryOperator>> {
+    return ASSIGNMENT_OPERATORS.iter()
+        .find(|(kind, _)| kind == token)
+        .map(|(_, operator)| operator.clone());
 }
@@ -1,22 +1,111 @@
-use lexer::Token;
+use lexer::{TokenKind, IntegerLiteral};
+use parser::token_stream::{TokenStream, NameKind};
 use parser::expression;
-use parser::StackFrame;
-use parser::expression::Expression;
+use parser::{error, furthest_error, is_type_specifier, parse_type_specifiers, parse_pointers, parse_array_dimensions};
+use parser::expression::{Expression, ExpressionKind, Var};
 use parser::factor;
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
+use std::fmt;
 
 #[derive(Debug, Clone)]
-pub enum Factor {
+pub struct Factor {
+    pub kind: FactorKind,
+    pub span: Span,
+    // Filled in by semantic analysis
+    pub ty: Option<Type>,
+}
+
+impl Factor {
+    pub fn ty(&self) -> &Type {
+        return self.ty.as_ref().expect("Factor wasn't type checked");
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FactorKind {
     Expression(Box<Expression>),
     UnaryOperation(Box<UnaryOperation>),
-    Constant(i64),
-    Identifier(String),
+    // The value, as it is in the type of the literal it came from
+    Constant(i64, Type),
+    // A `float` or `double` constant. A `float` one has been rounded to a
+    // `float` already.
+    FloatingConstant(f64, Type),
+    // The bytes of a string literal, without the terminating zero. It's an
+    // array of `char`s in read-only memory.
+    StringLiteral(Vec<u8>),
+    Identifier(Var),
+    FunctionCall(FunctionCall),
+    // `++var`, which has the updated value
+    PrefixIncrement(Box<IncrementOperation>),
+    // `var++`, which has the value from before the update
+    PostfixIncrement(Box<IncrementOperation>),
+    Subscript(Box<Subscript>),
+    // `operand.member` or `pointer->member`
+    Member(Box<MemberAccess>),
+    // `sizeof operand`, where the operand is never evaluated
+    SizeOf(Box<Factor>),
+    // `sizeof(type)`
+    SizeOfType(Type),
+    // `(type) operand`. Semantic analysis adds these too, wherever a value
+    // is converted without one being written.
+    Cast(Box<Cast>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cast {
+    pub ty: Type,
+    pub expression: Expression,
 }
 
+// `array[index]`, which is the same as `*(array + index)`
 #[derive(Debug, Clone)]
+pub struct Subscript {
+    pub array: Factor,
+    pub index: Expression,
+}
+
+// `pointer->member` is the same as `(*pointer).member`
+#[derive(Debug, Clone)]
+pub struct MemberAccess {
+    pub operand: Factor,
+    pub name: String,
+    pub name_span: Span,
+    pub is_arrow: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    pub name_span: Span,
+    pub arguments: Vec<Expression>,
+    // Where a struct the function returns is kept in the frame, set aside
+    // by semantic analysis
+    pub return_slot: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOperator {
     Negation,
     BitwiseComplement,
     LogicalNegation,
+    // `*pointer`
+    Dereference,
+    // `&lvalue`
+    AddressOf,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOperator::Negation => write!(f, "'-'"),
+            UnaryOperator::BitwiseComplement => write!(f, "'~'"),
+            UnaryOperator::LogicalNegation => write!(f, "'!'"),
+            UnaryOperator::Dereference => write!(f, "'*'"),
+            UnaryOperator::AddressOf => write!(f, "'&'"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,99 +115,437 @@ pub struct UnaryOperation {
 }
 
 #[derive(Debug, Clone)]
-pub enum BinaryFactorOperator {
-    Multiplication,
-    Division,
+pub enum IncrementOperator {
+    Increment,
+    Decrement,
 }
 
 #[derive(Debug, Clone)]
-pub struct BinaryFactor {
-    pub operator: BinaryFactorOperator,
-    pub right_factor: Factor,
+pub struct IncrementOperation {
+    pub operator: IncrementOperator,
+    // Any operand, which semantic analysis checks is assignable
+    pub operand: Factor,
+}
+
+// Back out of however deep the factor went once it's parsed, whether or not
+// it parsed
+pub fn parse(tokens: &mut TokenStream) -> Result<Factor, Diagnostic> {
+    let nesting = tokens.nesting();
+    let factor = parse_postfix(tokens);
+    tokens.unnest(nesting);
+    return factor;
+}
+
+// An operand followed by any number of subscripts, member accesses and
+// `++`s or `--`s, each putting the operand a level deeper
+fn parse_postfix(tokens: &mut TokenStream) -> Result<Factor, Diagnostic> {
+    tokens.nest()?;
+    let mut factor = parse_operand(tokens)?;
+
+    loop {
+        match tokens.peek().kind {
+            TokenKind::OpenBracket => {
+                tokens.nest()?;
+                factor = parse_subscript(tokens, factor)?;
+                continue;
+            },
+            TokenKind::Dot | TokenKind::Arrow => {
+                tokens.nest()?;
+                factor = parse_member_access(tokens, factor)?;
+                continue;
+            },
+            _ => (),
+        }
+
+        let operator: IncrementOperator;
+        match increment_operator_for_token(&tokens.peek().kind) {
+            Some(matched_operator) => operator = matched_operator,
+            None => break,
+        }
+        tokens.nest()?;
+        tokens.advance();
+        let span = factor.span.to(&tokens.previous().span);
+        factor = Factor {
+            kind: FactorKind::PostfixIncrement(Box::new(IncrementOperation { operator: operator, operand: factor })),
+            span: span,
+            ty: None,
+        };
+    }
+
+    return Ok(factor);
 }
 
-pub fn parse(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(Factor, Vec<Token>), String> {
-    match expression::parse_with_parens(tokens.clone(), stack_frame) {
-        Ok((expression, leftover_tokens)) => {
-            return Ok((Factor::Expression(Box::new(expression)), leftover_tokens));
+fn parse_operand(tokens: &mut TokenStream) -> Result<Factor, Diagnostic> {
+    let mut errors: Vec<Diagnostic> = Vec::new();
+    let start = tokens.peek();
+    let checkpoint = tokens.checkpoint();
+
+    match parse_cast(tokens) {
+        Ok(cast) => {
+            let span = start.span.to(&cast.expression.span);
+            return Ok(Factor { kind: FactorKind::Cast(Box::new(cast)), span: span, ty: None });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    match parse_unary_operation(tokens.clone(), stack_frame) {
-        Ok((operation, leftover_tokens)) => {
-            return Ok((Factor::UnaryOperation(Box::new(operation)), leftover_tokens))
+    match expression::parse_with_parens(tokens) {
+        Ok(expression) => {
+            // Include both parens in the factor's span
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Factor { kind: FactorKind::Expression(Box::new(expression)), span: span, ty: None });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    match parse_integer_literal(tokens.clone()) {
-        Ok((integer, leftover_tokens)) => {
-            return Ok((Factor::Constant(integer), leftover_tokens))
+    match parse_prefix_increment(tokens) {
+        Ok(operation) => {
+            let span = start.span.to(&operation.operand.span);
+            return Ok(Factor { kind: FactorKind::PrefixIncrement(Box::new(operation)), span: span, ty: None });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    match parse_identifier(tokens.clone()) {
-        Ok((name, leftover_tokens)) => {
-            return Ok((Factor::Identifier(name), leftover_tokens))
+    match parse_sizeof(tokens) {
+        Ok(kind) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Factor { kind: kind, span: span, ty: None });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    return Err("Invalid factor".to_string())
-}
+    match parse_unary_operation(tokens) {
+        Ok(operation) => {
+            let span = start.span.to(&operation.factor.span);
+            return Ok(Factor { kind: FactorKind::UnaryOperation(Box::new(operation)), span: span, ty: None });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
 
-pub fn binary_factor_operator_for_token(token: &Token) -> Option<BinaryFactorOperator> {
-    match token {
-        Token::MultiplicationSign => return Some(BinaryFactorOperator::Multiplication),
-        Token::DivisionSign => return Some(BinaryFactorOperator::Division),
-        _ => return None,
+    match parse_integer_literal(tokens) {
+        Ok((value, ty)) => {
+            return Ok(Factor { kind: FactorKind::Constant(value, ty), span: start.span.clone(), ty: None });
+        },
+        Err(diagnostic) => errors.push(diagnostic),
     }
+
+    match parse_floating_literal(tokens) {
+        Ok((value, ty)) => {
+            return Ok(Factor { kind: FactorKind::FloatingConstant(value, ty), span: start.span.clone(), ty: None });
+        },
+        Err(diagnostic) => errors.push(diagnostic),
+    }
+
+    match parse_string_literal(tokens) {
+        Ok(bytes) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Factor { kind: FactorKind::StringLiteral(bytes), span: span, ty: None });
+        },
+        Err(diagnostic) => errors.push(diagnostic),
+    }
+
+    match parse_function_call(tokens) {
+        Ok(call) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Factor { kind: FactorKind::FunctionCall(call), span: span, ty: None });
+        },
+        // A name followed by a '(' can only be a call, so what went wrong in
+        // its arguments is the error
+        Err(diagnostic) if tokens.consumed_since(&checkpoint).len() > 1 => return Err(diagnostic),
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    // An enum constant is an `int` constant, and a typedef name can't be
+    // used as a value
+    match parse_identifier(tokens) {
+        Ok(name) => match tokens.lookup_name(&name).map(|(entry, _)| entry.kind.clone()) {
+            Some(NameKind::EnumConstant(value)) => {
+                return Ok(Factor { kind: FactorKind::Constant(value, Type::Int), span: start.span.clone(), ty: None });
+            },
+            Some(NameKind::Typedef(_)) => errors.push(error(start, "Expecting expression")),
+            _ => {
+                let var = Var { name: name, span: start.span.clone(), symbol: None };
+                return Ok(Factor { kind: FactorKind::Identifier(var), span: start.span.clone(), ty: None });
+            },
+        },
+        Err(diagnostic) => errors.push(diagnostic),
+    }
+
+    return Err(furthest_error(errors, start, "Expecting expression"));
 }
 
-fn parse_unary_operation(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(UnaryOperation, Vec<Token>), String> {
+fn parse_unary_operation(tokens: &mut TokenStream) -> Result<UnaryOperation, Diagnostic> {
     let operator: UnaryOperator;
-    let factor: Factor;
 
-    match unary_operator_for_token(&tokens[0]) {
+    match unary_operator_for_token(&tokens.peek().kind) {
         Some(matched_operator) => operator = matched_operator,
-        None => return Err("Expecting ~ or ! or -".to_string()),
+        None => return Err(error(tokens.peek(), "Expecting unary operator")),
     }
+    tokens.advance();
 
-    let leftover_tokens: Vec<Token>;
-    match factor::parse(tokens[1..].to_vec(), stack_frame) {
-        Ok((matched_factor, tokens)) => {
-            factor = matched_factor;
-            leftover_tokens = tokens; },
-        Err(err) => return Err(err),
+    let factor = factor::parse(tokens)?;
+    return Ok(UnaryOperation { operator: operator, factor: factor });
+}
+
+fn parse_prefix_increment(tokens: &mut TokenStream) -> Result<IncrementOperation, Diagnostic> {
+    let operator: IncrementOperator;
+
+    match increment_operator_for_token(&tokens.peek().kind) {
+        Some(matched_operator) => operator = matched_operator,
+        None => return Err(error(tokens.peek(), "Expecting ++ or --")),
     }
+    tokens.advance();
+
+    let factor = factor::parse(tokens)?;
+    return Ok(IncrementOperation { operator: operator, operand: factor });
+}
+
+// `[index]` after an operand
+fn parse_subscript(tokens: &mut TokenStream, array: Factor) -> Result<Factor, Diagnostic> {
+    tokens.advance();
+    let index = expression::parse(tokens)?;
+    match tokens.peek().kind {
+        TokenKind::CloseBracket => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ']'")),
+    };
 
-    return Ok((
-        UnaryOperation { operator: operator, factor: factor },
-        leftover_tokens
-    ));
+    let span = array.span.to(&tokens.previous().span);
+    return Ok(Factor {
+        kind: FactorKind::Subscript(Box::new(Subscript { array: array, index: index })),
+        span: span,
+        ty: None,
+    });
 }
 
-fn parse_integer_literal(tokens: Vec<Token>) -> Result<(i64, Vec<Token>), String> {
-    match tokens[0] {
-        Token::IntegerLiteral(value) => return Ok((value, tokens[1..].to_vec())),
-        _ => return Err("Expecting integer literal".to_string()),
+// `.member` or `->member` after an operand
+fn parse_member_access(tokens: &mut TokenStream, operand: Factor) -> Result<Factor, Diagnostic> {
+    let is_arrow = tokens.advance().kind == TokenKind::Arrow;
+    let name_span = tokens.peek().span.clone();
+    let name = parse_identifier(tokens)?;
+
+    let span = operand.span.to(&name_span);
+    return Ok(Factor {
+        kind: FactorKind::Member(Box::new(MemberAccess {
+            operand: operand,
+            name: name,
+            name_span: name_span,
+            is_arrow: is_arrow,
+        })),
+        span: span,
+        ty: None,
+    });
+}
+
+// `sizeof operand` or `sizeof(type)`, where the type is a base type
+// followed by any `*`s and `[size]`s
+fn parse_sizeof(tokens: &mut TokenStream) -> Result<FactorKind, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordSizeof => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'sizeof'")),
+    };
+
+    let is_type = tokens.peek().kind == TokenKind::OpenParen && is_type_specifier(tokens, &tokens.peek_nth(1).kind);
+    if !is_type {
+        return Ok(FactorKind::SizeOf(Box::new(factor::parse(tokens)?)));
     }
+
+    tokens.advance();
+    let ty = parse_type_specifiers(tokens, "Expecting type")?;
+    let ty = parse_pointers(tokens, ty);
+    let ty = parse_array_dimensions(tokens, ty)?;
+    match tokens.peek().kind {
+        TokenKind::CloseParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ')'")),
+    };
+    return Ok(FactorKind::SizeOfType(ty));
 }
 
-fn parse_identifier(tokens: Vec<Token>) -> Result<(String, Vec<Token>), String> {
-    match tokens[0] {
-        Token::Identifier(ref name) => return Ok((name.clone(), tokens[1..].to_vec())),
-        _ => return Err("Expecting identifier".to_string()),
+// `(type) operand`, where the type is a base type followed by any `*`s
+fn parse_cast(tokens: &mut TokenStream) -> Result<Cast, Diagnostic> {
+    let is_type = tokens.peek().kind == TokenKind::OpenParen && is_type_specifier(tokens, &tokens.peek_nth(1).kind);
+    if !is_type {
+        return Err(error(tokens.peek(), "Expecting cast"));
+    }
+
+    tokens.advance();
+    let ty = parse_type_specifiers(tokens, "Expecting type")?;
+    let ty = parse_pointers(tokens, ty);
+    match tokens.peek().kind {
+        TokenKind::CloseParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ')'")),
+    };
+
+    let operand = factor::parse(tokens)?;
+    let expression = Expression { span: operand.span.clone(), kind: ExpressionKind::Factor(operand), ty: None };
+    return Ok(Cast { ty: ty, expression: expression });
+}
+
+// Only variables, dereferenced pointers, array elements, members and
+// string literals, possibly in parentheses, can be assigned to,
+// incremented or have their address taken, which semantic analysis checks.
+// It rules out the arrays separately. A member is only an lvalue if the struct it's in is, unlike one
+// of a struct returned by a call.
+pub fn is_lvalue(factor: &Factor) -> bool {
+    match factor.kind {
+        FactorKind::Identifier(_) | FactorKind::Subscript(_) | FactorKind::StringLiteral(_) => return true,
+        FactorKind::Member(ref access) => return access.is_arrow || is_lvalue(&access.operand),
+        FactorKind::UnaryOperation(ref operation) => return operation.operator == UnaryOperator::Dereference,
+        FactorKind::Expression(ref expression) => return expression::is_lvalue(expression),
+        _ => return false,
+    }
+}
+
+// The value of an integer or character literal, and its type. A character
+// literal is an `int`. An integer literal is the first type it fits in
+// from a list depending on its suffix, and on whether it's decimal. One
+// that doesn't fit in any of them is reported, and parses as if it had
+// wrapped around.
+fn parse_integer_literal(tokens: &mut TokenStream) -> Result<(i64, Type), Diagnostic> {
+    let token = tokens.peek();
+    match token.kind {
+        TokenKind::CharacterLiteral(value) => {
+            tokens.advance();
+            return Ok((i64::from(value), Type::Int));
+        },
+        TokenKind::IntegerLiteral(ref literal) => {
+            tokens.advance();
+            let types = integer_literal_types(literal);
+            match types.iter().find(|ty| literal.value <= ty.max_value()) {
+                Some(ty) => return Ok((ty.wrap(literal.value as i64), ty.clone())),
+                None => (),
+            }
+
+            let ty = types.last().expect("Integer literal without types").clone();
+            let mut diagnostic = Diagnostic::error(
+                format!("Integer literal '{}' is out of range for '{}'", literal.value, ty),
+                token.span.clone(),
+            );
+            if !literal.is_unsigned {
+                diagnostic = diagnostic.with_note(format!("add a 'u' suffix to make it an '{}'", ty.to_unsigned()));
+            }
+            tokens.report(diagnostic);
+            return Ok((ty.wrap(literal.value as i64), ty));
+        },
+        _ => return Err(error(tokens.peek(), "Expecting integer literal")),
+    }
+}
+
+// The types an integer literal can have, smallest first. Octal and hex
+// literals can be unsigned to fit, but decimal ones need a `u`.
+fn integer_literal_types(literal: &IntegerLiteral) -> Vec<Type> {
+    let mut types: Vec<Type> = Vec::new();
+    let signed_types = [Type::Int, Type::Long, Type::LongLong];
+    for ty in signed_types.iter().skip(literal.long_count as usize) {
+        if !literal.is_unsigned {
+            types.push(ty.clone());
+        }
+        if literal.is_unsigned || !literal.is_decimal {
+            types.push(ty.to_unsigned());
+        }
+    }
+    return types;
+}
+
+// `1.5` is a `double` and `1.5f` a `float`
+fn parse_floating_literal(tokens: &mut TokenStream) -> Result<(f64, Type), Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::FloatingLiteral(value, is_float) => {
+            tokens.advance();
+            return Ok((value, if is_float { Type::Float } else { Type::Double }));
+        },
+        _ => return Err(error(tokens.peek(), "Expecting floating literal")),
+    }
+}
+
+// Adjacent string literals are joined into one, so `"a" "b"` is `"ab"`
+fn parse_string_literal(tokens: &mut TokenStream) -> Result<Vec<u8>, Diagnostic> {
+    let mut bytes: Vec<u8> = Vec::new();
+    match tokens.peek().kind {
+        TokenKind::StringLiteral(_) => (),
+        _ => return Err(error(tokens.peek(), "Expecting string literal")),
+    }
+
+    while let TokenKind::StringLiteral(ref literal) = tokens.peek().kind {
+        bytes.extend_from_slice(literal);
+        tokens.advance();
+    }
+    return Ok(bytes);
+}
+
+// `name(arguments)`
+fn parse_function_call(tokens: &mut TokenStream) -> Result<FunctionCall, Diagnostic> {
+    let start = tokens.peek();
+    let name = parse_identifier(tokens)?;
+    let mut arguments: Vec<Expression> = Vec::new();
+
+    match tokens.peek().kind {
+        TokenKind::OpenParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '('")),
+    };
+
+    match tokens.peek().kind {
+        TokenKind::CloseParen => {
+            tokens.advance();
+        },
+        _ => loop {
+            arguments.push(expression::parse_assignment_expression(tokens)?);
+
+            match tokens.peek().kind {
+                TokenKind::Comma => tokens.advance(),
+                TokenKind::CloseParen => {
+                    tokens.advance();
+                    break;
+                },
+                _ => return Err(error(tokens.peek(), "Expecting ',' or ')'")),
+            };
+        },
+    }
+
+    return Ok(FunctionCall { name: name, name_span: start.span.clone(), arguments: arguments, return_slot: None });
+}
+
+fn parse_identifier(tokens: &mut TokenStream) -> Result<String, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref name) => {
+            tokens.advance();
+            return Ok(name.clone());
+        },
+        _ => return Err(error(tokens.peek(), "Expecting identifier")),
+    }
+}
+
+fn increment_operator_for_token(token: &TokenKind) -> Option<IncrementOperator> {
+    match token {
+        TokenKind::Increment => return Some(IncrementOperator::Increment),
+        TokenKind::Decrement => return Some(IncrementOperator::Decrement),
+        _ => return None,
     }
 }
 
-fn unary_operator_for_token(token: &Token) -> Option<UnaryOperator> {
+fn unary_operator_for_token(token: &TokenKind) -> Option<UnaryOperator> {
     match token {
-        Token::MinusSign => return Some(UnaryOperator::Negation),
-        Token::BitwiseComplement => return Some(UnaryOperator::BitwiseComplement),
-        Token::LogicalNegation => return Some(UnaryOperator::LogicalNegation),
+        TokenKind::MinusSign => return Some(UnaryOperator::Negation),
+        TokenKind::BitwiseComplement => return Some(UnaryOperator::BitwiseComplement),
+        TokenKind::LogicalNegation => return Some(UnaryOperator::LogicalNegation),
+        TokenKind::MultiplicationSign => return Some(UnaryOperator::Dereference),
+        TokenKind::BitwiseAnd => return Some(UnaryOperator::AddressOf),
         _ => return None,
     }
 }
@@ -1,75 +1,161 @@
-use lexer::Token;
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, NameKind};
+use parser::{error, declare_name};
 use parser::statement;
-use parser::StackFrame;
-use parser::statement::Statement;
+use parser::{StorageClass, parse_storage_class, parse_type_specifiers, parse_pointers, parse_array_lengths, array_type};
+use parser::statement::BlockItem;
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
 
 #[derive(Debug)]
 pub struct Function {
     pub name: String,
-    pub statements: Vec<Statement>,
-    pub stack_frame: StackFrame,
+    pub name_span: Span,
+    pub return_type: Type,
+    pub params: Vec<Parameter>,
+    // Whether the parameters end with `...`, so it takes more arguments
+    // than it names, like `printf`
+    pub is_variadic: bool,
+    pub storage_class: Option<StorageClass>,
+    // `None` for a declaration without a body, e.g. a prototype of a libc
+    // function
+    pub body: Option<Vec<BlockItem>>,
+    #[allow(dead_code)]
+    pub span: Span,
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Function, String> {
+// The name is optional in a declaration like `int putchar(int);`. An array
+// parameter is really a pointer to the array's first element, so `int a[]`
+// and `int a[10]` both declare an `int *`.
+#[derive(Debug)]
+pub struct Parameter {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub span: Span,
+}
+
+pub fn parse(tokens: &mut TokenStream) -> Result<Function, Diagnostic> {
     let name: String;
-    let mut statements: Vec<Statement> = Vec::new();
-    let mut leftover_tokens: Vec<Token> = tokens.clone();
-    let mut stack_frame: StackFrame = Default::default();
+    let name_span: Span;
+    let start = tokens.peek();
 
-    match leftover_tokens.get(0) {
-        Some(Token::KeywordInt) => (),
-        _ => return Err("Expecting function return type".to_string()),
-    }
+    let storage_class = parse_storage_class(tokens);
 
-    match leftover_tokens.get(1) {
-        Some(Token::Identifier(ref matched_name)) => name = matched_name.clone(),
-        _ => return Err("Expecting identifier".to_string()),
-    }
+    let return_type = parse_type_specifiers(tokens, "Expecting function return type")?;
+    let return_type = parse_pointers(tokens, return_type);
 
-    match leftover_tokens.get(2) {
-        Some(Token::OpenParen) => (),
-        _ => return Err("Expecting '('".to_string()),
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref matched_name) => {
+            name = matched_name.clone();
+            name_span = tokens.peek().span.clone();
+            tokens.advance();
+        },
+        _ => return Err(error(tokens.peek(), "Expecting identifier")),
     }
+    declare_name(tokens, &name, &name_span, NameKind::Variable);
+
+    // The parameters are in a scope of their own, which the body is inside
+    tokens.enter_scope();
+    let parameters_and_body = parse_parameters(tokens).and_then(|parameters| Ok((parameters, parse_body(tokens)?)));
+    tokens.exit_scope();
+    let ((params, is_variadic), body) = parameters_and_body?;
 
-    match leftover_tokens.get(3) {
-        Some(Token::CloseParen) => (),
-        _ => return Err("Expecting ')'".to_string()),
+    return Ok(Function {
+        name: name,
+        name_span: name_span,
+        return_type: return_type,
+        params: params,
+        is_variadic: is_variadic,
+        storage_class: storage_class,
+        body: body,
+        span: start.span.to(&tokens.previous().span),
+    });
+}
+
+// The body in braces, or `None` for a declaration ending in `;`
+fn parse_body(tokens: &mut TokenStream) -> Result<Option<Vec<BlockItem>>, Diagnostic> {
+    let body: Option<Vec<BlockItem>>;
+    match tokens.peek().kind {
+        TokenKind::OpenBrace => body = Some(statement::parse_block(tokens)?),
+        TokenKind::Semicolon => {
+            tokens.advance();
+            body = None;
+        },
+        _ => return Err(error(tokens.peek(), "Expecting '{' or ';'")),
     }
+    return Ok(body);
+}
+
+// The parameters, and whether they end with `...`
+fn parse_parameters(tokens: &mut TokenStream) -> Result<(Vec<Parameter>, bool), Diagnostic> {
+    let mut params: Vec<Parameter> = Vec::new();
+    let mut is_variadic = false;
 
-    match leftover_tokens.get(4) {
-        Some(Token::OpenBrace) => {},
-        _ => return Err("Expecting '{'".to_string()),
+    match tokens.peek().kind {
+        TokenKind::OpenParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '('")),
+    };
+
+    match tokens.peek().kind {
+        TokenKind::CloseParen => {
+            tokens.advance();
+            return Ok((params, is_variadic));
+        },
+        _ => (),
     }
 
-    leftover_tokens = leftover_tokens[5..].to_vec();
     loop {
-        match leftover_tokens.get(0) {
-            Some(Token::CloseBrace) => break,
-            _ => (),
+        let start = tokens.peek();
+        if start.kind == TokenKind::Ellipsis {
+            if params.is_empty() {
+                return Err(Diagnostic::error("ISO C requires a named parameter before '...'", start.span.clone()));
+            }
+            tokens.advance();
+            is_variadic = true;
+            match tokens.peek().kind {
+                TokenKind::CloseParen => tokens.advance(),
+                _ => return Err(error(tokens.peek(), "Expecting ')'")),
+            };
+            break;
         }
 
-        let (statement, tokens) = statement::parse(
-            leftover_tokens.clone(),
-            &mut stack_frame,
-        )?;
+        let mut name: Option<String> = None;
 
-        let mut is_return: bool = false;
-        match statement {
-            Statement::Return(_) => is_return = true,
+        let ty = parse_type_specifiers(tokens, "Expecting parameter type")?;
+        let ty = parse_pointers(tokens, ty);
+
+        match tokens.peek().kind {
+            TokenKind::Identifier(ref matched_name) => {
+                name = Some(matched_name.clone());
+                declare_name(tokens, matched_name, &tokens.peek().span, NameKind::Variable);
+                tokens.advance();
+            },
             _ => (),
         }
+        let ty = parse_parameter_dimensions(tokens, ty)?;
 
-        statements.push(statement);
-        leftover_tokens = tokens;
+        params.push(Parameter { name: name, ty: ty, span: start.span.to(&tokens.previous().span) });
 
-        if is_return {
-            break;
-        }
+        match tokens.peek().kind {
+            TokenKind::Comma => tokens.advance(),
+            TokenKind::CloseParen => {
+                tokens.advance();
+                break;
+            },
+            _ => return Err(error(tokens.peek(), "Expecting ',' or ')'")),
+        };
     }
 
-    return Ok(Function {
-        name: name,
-        statements: statements,
-        stack_frame: stack_frame,
-    });
+    return Ok((params, is_variadic));
+}
+
+// Like any other array's, except that the first size can be left out
+fn parse_parameter_dimensions(tokens: &mut TokenStream, base: Type) -> Result<Type, Diagnostic> {
+    let (is_unsized, lengths) = parse_array_lengths(tokens)?;
+    let ty = array_type(base, &lengths)?;
+    if is_unsized {
+        return Ok(Type::Pointer(Box::new(ty)));
+    }
+    return Ok(ty.decay());
 }
@@ -0,0 +1,77 @@
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, NameKind};
+use parser::{error, declare_name, is_tagged_type, parse_storage_class, parse_type_specifiers, parse_pointers, parse_array_lengths};
+use parser::StorageClass;
+use parser::initializer;
+use parser::initializer::Initializer;
+use parser::expression::{Var, Expression};
+use diagnostic::Diagnostic;
+use types::Type;
+
+// A declaration of a variable outside of any function. A variable can be
+// declared any number of times in a file.
+#[derive(Debug)]
+pub struct GlobalVariable {
+    pub var: Var,
+    // Made into an array by the `[size]`s after the name, if there are
+    // any, once semantic analysis has worked them out
+    pub ty: Type,
+    pub lengths: Vec<Expression>,
+    // Whether the lengths come after a `[]`, whose size is counted from the
+    // initialiser
+    pub is_unsized: bool,
+    pub storage_class: Option<StorageClass>,
+    // Made up of constant expressions
+    pub initializer: Option<Initializer>,
+}
+
+// `[static | extern] type [*...] name [[size]...] [= initializer];`, or
+// `None` for a declaration of a struct, union or enum on its own, like
+// `struct point { int x, y; };`, which only declares its tag and any enum
+// constants
+pub fn parse(tokens: &mut TokenStream) -> Result<Option<GlobalVariable>, Diagnostic> {
+    let var: Var;
+    let mut initializer: Option<Initializer> = None;
+
+    let storage_class = parse_storage_class(tokens);
+
+    let is_tagged = is_tagged_type(&tokens.peek().kind);
+    let ty = parse_type_specifiers(tokens, "Expecting type")?;
+    if is_tagged && storage_class.is_none() && tokens.peek().kind == TokenKind::Semicolon {
+        tokens.advance();
+        return Ok(None);
+    }
+    let ty = parse_pointers(tokens, ty);
+
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref name) => {
+            var = Var { name: name.clone(), span: tokens.peek().span.clone(), symbol: None };
+            tokens.advance();
+        },
+        _ => return Err(error(tokens.peek(), "Expecting identifier")),
+    }
+    declare_name(tokens, &var.name, &var.span, NameKind::Variable);
+    let (is_unsized, lengths) = parse_array_lengths(tokens)?;
+
+    match tokens.peek().kind {
+        TokenKind::Assignment => {
+            tokens.advance();
+            initializer = Some(initializer::parse(tokens)?);
+        },
+        _ => (),
+    }
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(Some(GlobalVariable {
+        var: var,
+        ty: ty,
+        lengths: lengths,
+        is_unsized: is_unsized,
+        storage_class: storage_class,
+        initializer: initializer,
+    }));
+}
@@ -0,0 +1,59 @@
+use lexer::TokenKind;
+use parser::token_stream::TokenStream;
+use parser::error;
+use parser::expression;
+use parser::expression::Expression;
+use span::Span;
+use diagnostic::Diagnostic;
+
+// What follows the `=` in a declaration
+#[derive(Debug)]
+pub enum Initializer {
+    Expression(Expression),
+    // `{a, b, c}`, with a span covering the braces. Elements that aren't
+    // given start out as zero.
+    List(Vec<Initializer>, Span),
+}
+
+impl Initializer {
+    pub fn span(&self) -> &Span {
+        match self {
+            Initializer::Expression(expression) => return &expression.span,
+            Initializer::List(_, span) => return span,
+        }
+    }
+
+    // The bytes of a string literal on its own, which can initialise an
+    // array of characters
+    pub fn string_literal(&self) -> Option<&[u8]> {
+        match self {
+            Initializer::Expression(expression) => return expression::string_literal(expression),
+            Initializer::List(_, _) => return None,
+        }
+    }
+}
+
+// An expression, or a list in braces that can have a trailing comma
+pub fn parse(tokens: &mut TokenStream) -> Result<Initializer, Diagnostic> {
+    let start = tokens.peek();
+    match start.kind {
+        TokenKind::OpenBrace => tokens.advance(),
+        _ => return Ok(Initializer::Expression(expression::parse_assignment_expression(tokens)?)),
+    };
+
+    let mut elements: Vec<Initializer> = Vec::new();
+    while tokens.peek().kind != TokenKind::CloseBrace {
+        elements.push(parse(tokens)?);
+
+        match tokens.peek().kind {
+            TokenKind::Comma => {
+                tokens.advance();
+            },
+            TokenKind::CloseBrace => (),
+            _ => return Err(error(tokens.peek(), "Expecting ',' or '}'")),
+        };
+    }
+    tokens.advance();
+
+    return Ok(Initializer::List(elements, start.span.to(&tokens.previous().span)));
+}
@@ -1,21 +1,350 @@
-use std::collections::HashMap;
+use lexer::{Token, TokenKind};
+use parser::token_stream::{TokenStream, Checkpoint, NameKind};
+use parser::expression::Expression;
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
 
+pub mod token_stream;
 pub mod program;
 pub mod function;
+pub mod global;
+pub mod constant;
+pub mod initializer;
+pub mod structure;
+pub mod enumeration;
+pub mod typedef;
 pub mod statement;
 pub mod expression;
-pub mod term;
 pub mod factor;
 
-#[derive(Debug, Default)]
-pub struct StackFrame {
-    pub current_offset: i64,
-    pub vars: HashMap<String, i64>,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageClass {
+    Static,
+    Extern,
 }
 
-impl StackFrame {
-    pub fn add_var(&mut self, name: String) {
-        self.current_offset -= 8;
-        self.vars.insert(name, self.current_offset);
+// An optional `static` or `extern` at the start of a file scope declaration
+pub fn parse_storage_class(tokens: &mut TokenStream) -> Option<StorageClass> {
+    let storage_class: StorageClass;
+    match tokens.peek().kind {
+        TokenKind::KeywordStatic => storage_class = StorageClass::Static,
+        TokenKind::KeywordExtern => storage_class = StorageClass::Extern,
+        _ => return None,
+    }
+    tokens.advance();
+    return Some(storage_class);
+}
+
+// Whether the token is one of the keywords naming a base type, or starting
+// a struct, union or enum one, or a typedef name in scope, which is what
+// tells a declaration or a cast apart from an expression
+pub fn is_type_specifier(tokens: &TokenStream, kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::Identifier(ref name) => return tokens.typedef(name).is_some(),
+        _ => return is_type_keyword(kind),
+    }
+}
+
+// Whether the token starts a struct, union or enum type, which can be
+// declared on its own
+pub fn is_tagged_type(kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::KeywordStruct | TokenKind::KeywordUnion | TokenKind::KeywordEnum => return true,
+        _ => return false,
+    }
+}
+
+fn is_type_keyword(kind: &TokenKind) -> bool {
+    match kind {
+        _ if is_tagged_type(kind) => return true,
+        TokenKind::KeywordInt
+        | TokenKind::KeywordChar
+        | TokenKind::KeywordShort
+        | TokenKind::KeywordLong
+        | TokenKind::KeywordFloat
+        | TokenKind::KeywordDouble
+        | TokenKind::KeywordSigned
+        | TokenKind::KeywordUnsigned => return true,
+        _ => return false,
+    }
+}
+
+// A base type like `int` or `unsigned long long`. The keywords can come in
+// any order, as in C, and `int` can be left off anything but a plain
+// `int`, so `signed` on its own is an `int` and `unsigned short` is an
+// `unsigned short int`. A struct, union or enum type, or a typedef name,
+// can't be combined with any of them, so an identifier after them is
+// always the declarator, even if it's also a typedef name. `expected` is
+// what to report if there aren't any.
+pub fn parse_type_specifiers(tokens: &mut TokenStream, expected: &str) -> Result<Type, Diagnostic> {
+    let start = tokens.peek();
+    match start.kind {
+        TokenKind::KeywordStruct | TokenKind::KeywordUnion => return structure::parse(tokens),
+        TokenKind::KeywordEnum => return enumeration::parse(tokens),
+        TokenKind::Identifier(ref name) => match tokens.typedef(name) {
+            Some(ty) => {
+                let ty = ty.clone();
+                tokens.advance();
+                return Ok(ty);
+            },
+            None => (),
+        },
+        _ => (),
+    }
+
+    let mut specifiers: Vec<TokenKind> = Vec::new();
+    while is_type_keyword(&tokens.peek().kind) {
+        specifiers.push(tokens.advance().kind.clone());
+    }
+    if specifiers.is_empty() {
+        return Err(error(start, expected));
+    }
+
+    let count = |kind: TokenKind| specifiers.iter().filter(|specifier| **specifier == kind).count();
+    if count(TokenKind::KeywordStruct) + count(TokenKind::KeywordUnion) + count(TokenKind::KeywordEnum) > 0 {
+        return Err(invalid_type_specifiers(start, tokens));
+    }
+    // Floating types can't be combined with anything else
+    match (count(TokenKind::KeywordFloat), count(TokenKind::KeywordDouble), specifiers.len()) {
+        (0, 0, _) => (),
+        (1, 0, 1) => return Ok(Type::Float),
+        (0, 1, 1) => return Ok(Type::Double),
+        (0, 1, 2) if count(TokenKind::KeywordLong) == 1 => return Err(Diagnostic::error(
+            "'long double' isn't supported",
+            start.span.to(&tokens.previous().span),
+        )),
+        _ => return Err(invalid_type_specifiers(start, tokens)),
+    }
+
+    let signed = count(TokenKind::KeywordSigned);
+    let unsigned = count(TokenKind::KeywordUnsigned);
+    let integer = |ty: Type| if unsigned == 1 { ty.to_unsigned() } else { ty };
+
+    let ty: Type;
+    match (count(TokenKind::KeywordChar), count(TokenKind::KeywordShort), count(TokenKind::KeywordInt), count(TokenKind::KeywordLong)) {
+        _ if signed + unsigned > 1 => return Err(invalid_type_specifiers(start, tokens)),
+        (1, 0, 0, 0) if signed == 1 => ty = Type::SignedChar,
+        (1, 0, 0, 0) => ty = integer(Type::Char),
+        (0, 1, 0 | 1, 0) => ty = integer(Type::Short),
+        (0, 0, 0 | 1, 0) => ty = integer(Type::Int),
+        (0, 0, 0 | 1, 1) => ty = integer(Type::Long),
+        (0, 0, 0 | 1, 2) => ty = integer(Type::LongLong),
+        _ => return Err(invalid_type_specifiers(start, tokens)),
+    }
+    return Ok(ty);
+}
+
+fn invalid_type_specifiers(start: &Token, tokens: &TokenStream) -> Diagnostic {
+    return Diagnostic::error(
+        "Invalid combination of type specifiers",
+        start.span.to(&tokens.previous().span),
+    ).with_note("a type is an optional 'signed' or 'unsigned', then 'char', 'short', 'int', 'long' or 'long long', or else 'float' or 'double' on its own");
+}
+
+// The `*`s after a base type like `int`, each one making a pointer to the
+// type before it
+pub fn parse_pointers(tokens: &mut TokenStream, base: Type) -> Type {
+    let mut ty = base;
+    while tokens.peek().kind == TokenKind::MultiplicationSign {
+        tokens.advance();
+        ty = Type::Pointer(Box::new(ty));
+    }
+    return ty;
+}
+
+// The `[size]`s after a declarator's name, each one making an array of the
+// type after it, so `int a[2][3]` is an array of two `int[3]`s. The sizes
+// are worked out straight away, for types that are needed while parsing,
+// like a typedef name's or a struct member's. A `sizeof` an expression in
+// them can't be, as expressions only get types in semantic analysis.
+pub fn parse_array_dimensions(tokens: &mut TokenStream, base: Type) -> Result<Type, Diagnostic> {
+    let lengths = parse_sized_array_lengths(tokens)?;
+    return array_type(base, &lengths);
+}
+
+// The `[size]`s after a variable's name, left for semantic analysis to
+// work out with `array_type`, and whether they come after a `[]`. The first
+// size can be left out like that when there's an initialiser to count, as
+// in `int a[] = {1, 2}`, or for a parameter, which is a pointer anyway.
+pub fn parse_array_lengths(tokens: &mut TokenStream) -> Result<(bool, Vec<Expression>), Diagnostic> {
+    let is_unsized = tokens.peek().kind == TokenKind::OpenBracket
+        && tokens.peek_nth(1).kind == TokenKind::CloseBracket;
+    if is_unsized {
+        tokens.advance();
+        tokens.advance();
+    }
+    return Ok((is_unsized, parse_sized_array_lengths(tokens)?));
+}
+
+fn parse_sized_array_lengths(tokens: &mut TokenStream) -> Result<Vec<Expression>, Diagnostic> {
+    let mut lengths: Vec<Expression> = Vec::new();
+    while tokens.peek().kind == TokenKind::OpenBracket {
+        tokens.advance();
+        lengths.push(expression::parse(tokens)?);
+        match tokens.peek().kind {
+            TokenKind::CloseBracket => tokens.advance(),
+            _ => return Err(error(tokens.peek(), "Expecting ']'")),
+        };
+    }
+    return Ok(lengths);
+}
+
+// The array of `element`s with the given sizes, outermost first. Each size
+// has to be a positive constant, and the size in bytes has to fit in an
+// `i64`, like any other size.
+pub fn array_type(element: Type, lengths: &[Expression]) -> Result<Type, Diagnostic> {
+    let mut ty = element;
+    for expression in lengths.iter().rev() {
+        let length: i64;
+        match constant::evaluate(expression) {
+            Some(value) if value > 0 => length = value,
+            Some(_) => return Err(Diagnostic::error("Size of array has to be greater than zero", expression.span.clone())),
+            None => return Err(Diagnostic::error("Size of array is not a constant expression", expression.span.clone())),
+        }
+        if ty.size().checked_mul(length).is_none() {
+            return Err(Diagnostic::error("Size of array is too large", expression.span.clone())
+                .with_note(format!("it would be {} elements of {} bytes", length, ty.size())));
+        }
+        ty = Type::Array(Box::new(ty), length);
+    }
+    return Ok(ty);
+}
+
+// Declare an ordinary identifier in the current block. Only variables and
+// functions can be declared again in the same block, which semantic
+// analysis checks, and typedef names as long as it's for the same type.
+pub fn declare_name(tokens: &mut TokenStream, name: &str, span: &Span, kind: NameKind) {
+    let mut diagnostic: Option<Diagnostic> = None;
+    match tokens.lookup_name(name) {
+        Some((previous, true)) => match (&previous.kind, &kind) {
+            (NameKind::Variable, NameKind::Variable) => (),
+            (NameKind::Typedef(previous_type), NameKind::Typedef(ty)) if previous_type == ty => (),
+            (NameKind::Typedef(previous_type), NameKind::Typedef(_)) => diagnostic = Some(Diagnostic::error(
+                format!("Conflicting types for '{}'", name),
+                span.clone(),
+            ).with_label(previous.span.clone(), format!("previously declared here as '{}'", previous_type))),
+            _ => diagnostic = Some(Diagnostic::error(
+                format!("'{}' redeclared as a different kind of symbol", name),
+                span.clone(),
+            ).with_label(previous.span.clone(), "previously declared here")),
+        },
+        _ => (),
+    }
+    match diagnostic {
+        Some(diagnostic) => tokens.report(diagnostic),
+        None => (),
+    }
+    tokens.declare_name(name.to_string(), span.clone(), kind);
+}
+
+// Error pointing at the offending token, naming what was found there
+pub fn error(token: &Token, message: &str) -> Diagnostic {
+    return Diagnostic::error(
+        format!("{}, found {}", message, token.kind),
+        token.span.clone(),
+    );
+}
+
+// Of the errors from alternatives that all failed, report the one that got
+// furthest into the input. If none got past the first token, none of them is
+// more specific than `fallback`.
+pub fn furthest_error(errors: Vec<Diagnostic>, token: &Token, fallback: &str) -> Diagnostic {
+    let mut furthest: Option<Diagnostic> = None;
+    for diagnostic in errors {
+        let is_further = match furthest {
+            Some(ref current) => diagnostic.span().offset > current.span().offset,
+            None => diagnostic.span().offset > token.span.offset,
+        };
+        if is_further {
+            furthest = Some(diagnostic);
+        }
+    }
+
+    match furthest {
+        Some(diagnostic) => return diagnostic,
+        None => return error(token, fallback),
+    }
+}
+
+// Panic-mode recovery from an error in the block item, struct member or
+// file scope declaration that started at `start`. Skips past the point the
+// error was reported at, then up to and including the next ';' or the '}'
+// closing a group of braces, along with a ';' straight after it, like the
+// one ending a struct definition. Braces are counted from `start`, so
+// recovery never stops inside a group opened before or after the error, and
+// a '}' closing the enclosing block or struct is left for it to consume.
+//
+// A missing ';' is reported at whatever comes next, so when the error is at
+// something that starts a statement or declaration after the one that
+// failed, parsing resumes there instead, and errors in it still get
+// reported.
+pub fn synchronize(tokens: &mut TokenStream, start: &Checkpoint, diagnostic: &Diagnostic) {
+    let consumed = tokens.consumed_since(start);
+    let mut depth: usize = 0;
+    for token in consumed {
+        match token.kind {
+            TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseBrace => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    let mut has_skipped = !consumed.is_empty();
+    loop {
+        let token = tokens.peek();
+        let is_past_error = token.span.offset >= diagnostic.span().offset;
+        let is_at_error = token.span.offset == diagnostic.span().offset;
+        if is_at_error && has_skipped && depth == 0 && starts_item(tokens, &token.kind) {
+            return;
+        }
+        match token.kind {
+            TokenKind::Eof => return,
+            TokenKind::OpenBrace => depth += 1,
+            // At file scope there's nothing for a stray '}' to close, so
+            // it has to be skipped to make progress
+            TokenKind::CloseBrace if depth == 0 => {
+                if !has_skipped {
+                    tokens.advance();
+                }
+                return;
+            },
+            TokenKind::CloseBrace => {
+                depth -= 1;
+                if depth == 0 && is_past_error {
+                    tokens.advance();
+                    if tokens.peek().kind == TokenKind::Semicolon {
+                        tokens.advance();
+                    }
+                    return;
+                }
+            },
+            TokenKind::Semicolon if depth == 0 && is_past_error => {
+                tokens.advance();
+                return;
+            },
+            _ => (),
+        }
+        tokens.advance();
+        has_skipped = true;
+    }
+}
+
+// Whether the token can start a block item, struct member or file scope
+// declaration
+fn starts_item(tokens: &TokenStream, kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::KeywordStatic
+        | TokenKind::KeywordExtern
+        | TokenKind::KeywordTypedef
+        | TokenKind::KeywordReturn
+        | TokenKind::KeywordIf
+        | TokenKind::KeywordWhile
+        | TokenKind::KeywordDo
+        | TokenKind::KeywordFor
+        | TokenKind::KeywordBreak
+        | TokenKind::KeywordContinue
+        | TokenKind::OpenBrace => return true,
+        _ => return is_type_specifier(tokens, kind),
     }
 }
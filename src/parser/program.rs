@@ -1,13 +1,111 @@
 use parser::function;
+use parser::global;
 use parser::function::Function;
-use lexer::Token;
+use parser::global::GlobalVariable;
+use lexer::{Token, TokenKind};
+use parser::token_stream::TokenStream;
+use parser::typedef;
+use parser::{synchronize, is_type_specifier, is_tagged_type};
+use diagnostic::Diagnostic;
 
+// The file scope declarations, in the order they appear
 #[derive(Debug)]
 pub struct Program {
-    pub function: Function,
+    pub declarations: Vec<Declaration>,
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Program, String> {
-    let function = function::parse(tokens)?;
-    return Ok(Program { function: function });
+#[derive(Debug)]
+pub enum Declaration {
+    Function(Function),
+    Variable(GlobalVariable),
+}
+
+// Any warnings come back with the program. If there were errors, everything
+// that was reported comes back instead.
+pub fn parse(tokens: &[Token]) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let mut tokens = TokenStream::new(tokens);
+    let mut declarations: Vec<Declaration> = Vec::new();
+
+    loop {
+        match tokens.peek().kind {
+            TokenKind::Eof => break,
+            _ => (),
+        }
+
+        let start = tokens.checkpoint();
+        let declaration: Result<Option<Declaration>, Diagnostic>;
+        if tokens.peek().kind == TokenKind::KeywordTypedef {
+            declaration = typedef::parse(&mut tokens).map(|_| None);
+        } else if is_function(&tokens) {
+            declaration = function::parse(&mut tokens).map(|function| Some(Declaration::Function(function)));
+        } else {
+            declaration = global::parse(&mut tokens).map(|global| global.map(Declaration::Variable));
+        }
+
+        match declaration {
+            Ok(Some(declaration)) => declarations.push(declaration),
+            Ok(None) => (),
+            Err(diagnostic) => {
+                synchronize(&mut tokens, &start, &diagnostic);
+                tokens.report(diagnostic);
+            },
+        }
+    }
+
+    let diagnostics = tokens.take_diagnostics();
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(diagnostics);
+    }
+
+    return Ok((Program { declarations: declarations }, diagnostics));
+}
+
+// Whether the declaration coming up is a function, from a '(' after its
+// name, e.g. `static unsigned char *name(`, `struct point name(` or
+// `size_t name(` for a typedef name
+fn is_function(tokens: &TokenStream) -> bool {
+    let mut name_index = 0;
+    match tokens.peek().kind {
+        TokenKind::KeywordStatic | TokenKind::KeywordExtern => name_index += 1,
+        _ => (),
+    }
+    match tokens.peek_nth(name_index).kind {
+        ref kind if is_tagged_type(kind) => name_index = skip_tagged_type(tokens, name_index),
+        TokenKind::Identifier(_) => name_index += 1,
+        _ => while is_type_specifier(tokens, &tokens.peek_nth(name_index).kind) {
+            name_index += 1;
+        },
+    }
+    while tokens.peek_nth(name_index).kind == TokenKind::MultiplicationSign {
+        name_index += 1;
+    }
+
+    match tokens.peek_nth(name_index + 1).kind {
+        TokenKind::OpenParen => return true,
+        _ => return false,
+    }
+}
+
+// The index of the token after a struct, union or enum type starting at
+// `index`, skipping its tag and anything in braces
+fn skip_tagged_type(tokens: &TokenStream, index: usize) -> usize {
+    let mut index = index + 1;
+    if let TokenKind::Identifier(_) = tokens.peek_nth(index).kind {
+        index += 1;
+    }
+    if tokens.peek_nth(index).kind != TokenKind::OpenBrace {
+        return index;
+    }
+
+    let mut depth = 0;
+    loop {
+        match tokens.peek_nth(index).kind {
+            TokenKind::Eof => return index,
+            TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseBrace if depth == 1 => return index + 1,
+            TokenKind::CloseBrace => depth -= 1,
+            _ => (),
+        }
+        index += 1;
+    }
 }
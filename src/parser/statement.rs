@@ -1,128 +1,472 @@
-use lexer::Token;
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, NameKind};
+use parser::{error, furthest_error, synchronize, declare_name, is_type_specifier, is_tagged_type};
+use parser::{parse_type_specifiers, parse_pointers, parse_array_lengths};
+use parser::typedef;
 use parser::expression;
-use parser::StackFrame;
 use parser::expression::Var;
 use parser::expression::Expression;
+use parser::initializer;
+use parser::initializer::Initializer;
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
 
 #[derive(Debug)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum StatementKind {
     Return(Expression),
+    If(IfStatement),
+    Compound(Vec<BlockItem>),
+    Expression(Expression),
+    While(WhileStatement),
+    DoWhile(WhileStatement),
+    For(Box<ForStatement>),
+    Break,
+    Continue,
+}
+
+#[derive(Debug)]
+pub enum BlockItem {
+    Statement(Statement),
+    Declaration(VariableDeclaration),
+}
+
+#[derive(Debug)]
+pub struct IfStatement {
+    pub condition: Expression,
+    pub statement: Box<Statement>,
+    pub else_statement: Option<Box<Statement>>,
+}
+
+// `while (condition) statement` or `do statement while (condition);`
+#[derive(Debug)]
+pub struct WhileStatement {
+    pub condition: Expression,
+    pub statement: Box<Statement>,
+}
+
+#[derive(Debug)]
+pub struct ForStatement {
+    pub init: Option<ForInit>,
+    pub condition: Option<Expression>,
+    pub post: Option<Expression>,
+    pub statement: Box<Statement>,
+}
+
+#[derive(Debug)]
+pub enum ForInit {
+    Declaration(VariableDeclaration),
     Expression(Expression),
-    VariableDeclaration(VariableDeclaration),
 }
 
 #[derive(Debug)]
 pub struct VariableDeclaration {
     pub var: Var,
-    pub expression: Option<Expression>,
+    // Made into an array by the `[size]`s after the name, if there are
+    // any, once semantic analysis has worked them out
+    pub ty: Type,
+    pub lengths: Vec<Expression>,
+    // Whether the lengths come after a `[]`, whose size is counted from the
+    // initialiser
+    pub is_unsized: bool,
+    pub initializer: Option<Initializer>,
 }
 
-pub fn parse(
-    tokens: Vec<Token>,
-    stack_frame: &mut StackFrame,
-) -> Result<(Statement, Vec<Token>), String> {
+pub fn parse(tokens: &mut TokenStream) -> Result<Statement, Diagnostic> {
+    let mut errors: Vec<Diagnostic> = Vec::new();
+    let start = tokens.peek();
+    let checkpoint = tokens.checkpoint();
 
-    match parse_return(tokens.clone(), stack_frame) {
-        Ok((expression, leftover_tokens)) => {
-            return Ok((Statement::Return(expression), leftover_tokens))
+    match parse_return(tokens) {
+        Ok(expression) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::Return(expression), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    match parse_expression(tokens.clone(), stack_frame) {
-        Ok((expression, leftover_tokens)) => {
-            return Ok((Statement::Expression(expression), leftover_tokens))
+    match parse_if(tokens) {
+        Ok(if_statement) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::If(if_statement), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    match parse_while(tokens) {
+        Ok(while_statement) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::While(while_statement), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    match parse_variable_declaration(tokens.clone(), stack_frame) {
-        Ok((declaration, leftover_tokens)) => {
-            match stack_frame.vars.get(&declaration.var.name) {
-                Some(_) => {
-                    return Err(format!(
-                        "Variable '{}' has already been declared",
-                        &declaration.var.name,
-                    ));
-                },
-                None => {
-                    stack_frame.add_var(declaration.var.name.clone());
-                },
-            }
-            return Ok((Statement::VariableDeclaration(declaration), leftover_tokens))
+    match parse_do_while(tokens) {
+        Ok(while_statement) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::DoWhile(while_statement), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
         },
-        Err(_) => (),
     }
 
-    return Err("Invalid statement".to_string());
+    match parse_for(tokens) {
+        Ok(for_statement) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::For(Box::new(for_statement)), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    match parse_jump(tokens, TokenKind::KeywordBreak) {
+        Ok(()) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::Break, span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    match parse_jump(tokens, TokenKind::KeywordContinue) {
+        Ok(()) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::Continue, span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    match parse_block(tokens) {
+        Ok(block_items) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::Compound(block_items), span: span });
+        },
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
+
+    match parse_expression(tokens) {
+        Ok(expression) => {
+            let span = start.span.to(&tokens.previous().span);
+            return Ok(Statement { kind: StatementKind::Expression(expression), span: span });
+        },
+        Err(diagnostic) => errors.push(diagnostic),
+    }
+
+    return Err(furthest_error(errors, start, "Expecting statement"));
 }
 
-fn parse_return(
-    tokens: Vec<Token>,
-    stack_frame: &StackFrame,
-) -> Result<(Expression, Vec<Token>), String> {
-    match tokens.get(0) {
-        Some(Token::KeywordReturn) => (),
-        _ => return Err("Expecting 'return'".to_string()),
+// `None` for a declaration that only declares a tag or typedef names
+pub fn parse_block_item(tokens: &mut TokenStream) -> Result<Option<BlockItem>, Diagnostic> {
+    let mut errors: Vec<Diagnostic> = Vec::new();
+    let start = tokens.peek();
+    let checkpoint = tokens.checkpoint();
+
+    if start.kind == TokenKind::KeywordTypedef {
+        return typedef::parse(tokens).map(|_| None);
     }
 
-    let (expression, leftover_tokens) = expression::parse(
-        tokens[1..].to_vec(),
-        stack_frame,
-    )?;
+    match parse_variable_declaration(tokens) {
+        Ok(declaration) => return Ok(declaration.map(BlockItem::Declaration)),
+        Err(diagnostic) => {
+            errors.push(diagnostic);
+            tokens.restore(checkpoint);
+        },
+    }
 
-    match leftover_tokens.get(0) {
-        Some(Token::Semicolon) => {},
-        _ => return Err("Expecting ';'".to_string()),
+    match parse(tokens) {
+        Ok(statement) => return Ok(Some(BlockItem::Statement(statement))),
+        Err(diagnostic) => errors.push(diagnostic),
     }
 
-    return Ok((expression, leftover_tokens[1..].to_vec()));
+    return Err(furthest_error(errors, start, "Expecting statement or declaration"));
 }
 
-fn parse_expression(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(Expression, Vec<Token>), String> {
-    let (expression, leftover_tokens) = expression::parse(tokens, stack_frame)?;
+// `{ block items }`. Errors in the items are reported and recovered from so
+// the rest of the block still gets checked. Tags and typedef names declared
+// in the block go out of scope at its end.
+pub fn parse_block(tokens: &mut TokenStream) -> Result<Vec<BlockItem>, Diagnostic> {
+    let mut block_items: Vec<BlockItem> = Vec::new();
 
-    match leftover_tokens.get(0) {
-        Some(Token::Semicolon) => {},
-        _ => return Err("Expecting ';'".to_string()),
+    match tokens.peek().kind {
+        TokenKind::OpenBrace => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '{'")),
+    };
+    tokens.enter_scope();
+
+    loop {
+        match tokens.peek().kind {
+            TokenKind::CloseBrace => {
+                tokens.advance();
+                break;
+            },
+            TokenKind::Eof => {
+                let diagnostic = error(tokens.peek(), "Expecting '}'");
+                tokens.report(diagnostic);
+                break;
+            },
+            _ => (),
+        }
+
+        let start = tokens.checkpoint();
+        match parse_block_item(tokens) {
+            Ok(Some(block_item)) => block_items.push(block_item),
+            Ok(None) => (),
+            Err(diagnostic) => {
+                synchronize(tokens, &start, &diagnostic);
+                tokens.report(diagnostic);
+            },
+        }
     }
 
-    return Ok((expression, leftover_tokens[1..].to_vec()));
+    tokens.exit_scope();
+    return Ok(block_items);
 }
 
-fn parse_variable_declaration(tokens: Vec<Token>, stack_frame: &StackFrame) -> Result<(VariableDeclaration, Vec<Token>), String> {
-    let var: Var;
-    let mut expression: Option<Expression> = None;
+fn parse_return(tokens: &mut TokenStream) -> Result<Expression, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordReturn => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'return'")),
+    };
 
-    match tokens.get(0) {
-        Some(Token::KeywordInt) => {},
-        _ => return Err("Expecting 'int'".to_string()),
+    let expression = expression::parse(tokens)?;
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(expression);
+}
+
+fn parse_if(tokens: &mut TokenStream) -> Result<IfStatement, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordIf => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'if'")),
+    };
+
+    let condition = expression::parse_with_parens(tokens)?;
+    let statement = parse(tokens)?;
+
+    // An 'else' always belongs to the nearest 'if', which is the one that
+    // gets to look for it first here
+    let mut else_statement: Option<Box<Statement>> = None;
+    match tokens.peek().kind {
+        TokenKind::KeywordElse => {
+            tokens.advance();
+            else_statement = Some(Box::new(parse(tokens)?));
+        },
+        _ => (),
     }
 
-    match tokens.get(1) {
-        Some(Token::Identifier(ref name)) => {
-            var = Var { name: name.clone() }
+    return Ok(IfStatement {
+        condition: condition,
+        statement: Box::new(statement),
+        else_statement: else_statement,
+    });
+}
+
+fn parse_while(tokens: &mut TokenStream) -> Result<WhileStatement, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordWhile => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'while'")),
+    };
+
+    let condition = expression::parse_with_parens(tokens)?;
+    let statement = parse(tokens)?;
+
+    return Ok(WhileStatement { condition: condition, statement: Box::new(statement) });
+}
+
+fn parse_do_while(tokens: &mut TokenStream) -> Result<WhileStatement, Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordDo => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'do'")),
+    };
+
+    let statement = parse(tokens)?;
+
+    match tokens.peek().kind {
+        TokenKind::KeywordWhile => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'while'")),
+    };
+
+    let condition = expression::parse_with_parens(tokens)?;
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(WhileStatement { condition: condition, statement: Box::new(statement) });
+}
+
+// The loop is a block of its own, so the scope of anything declared in its
+// init clause ends with it
+fn parse_for(tokens: &mut TokenStream) -> Result<ForStatement, Diagnostic> {
+    tokens.enter_scope();
+    let for_statement = parse_for_clauses(tokens);
+    tokens.exit_scope();
+    return for_statement;
+}
+
+fn parse_for_clauses(tokens: &mut TokenStream) -> Result<ForStatement, Diagnostic> {
+    let init: Option<ForInit>;
+    let mut condition: Option<Expression> = None;
+    let mut post: Option<Expression> = None;
+
+    match tokens.peek().kind {
+        TokenKind::KeywordFor => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'for'")),
+    };
+
+    match tokens.peek().kind {
+        TokenKind::OpenParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '('")),
+    };
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => {
+            tokens.advance();
+            init = None;
         },
-        _ => return Err("Expecting identifier".to_string()),
+        ref kind if is_type_specifier(tokens, kind) => match parse_variable_declaration(tokens)? {
+            Some(declaration) => init = Some(ForInit::Declaration(declaration)),
+            None => return Err(Diagnostic::error(
+                "Declaration in 'for' loop doesn't declare a variable",
+                tokens.previous().span.clone(),
+            )),
+        },
+        _ => init = Some(ForInit::Expression(parse_expression(tokens)?)),
+    }
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => (),
+        _ => condition = Some(expression::parse(tokens)?),
     }
 
-    let leftover_tokens: Vec<Token>;
-    match tokens.get(2) {
-        Some(Token::Assignment) => {
-            let (parsed_expression, tokens) = expression::parse(tokens[3..].to_vec(), stack_frame)?;
-            expression = Some(parsed_expression);
-            leftover_tokens = tokens;
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    match tokens.peek().kind {
+        TokenKind::CloseParen => (),
+        _ => post = Some(expression::parse(tokens)?),
+    }
+
+    match tokens.peek().kind {
+        TokenKind::CloseParen => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ')'")),
+    };
+
+    let statement = parse(tokens)?;
+
+    return Ok(ForStatement {
+        init: init,
+        condition: condition,
+        post: post,
+        statement: Box::new(statement),
+    });
+}
+
+// `break;` or `continue;`
+fn parse_jump(tokens: &mut TokenStream, keyword: TokenKind) -> Result<(), Diagnostic> {
+    let token = tokens.peek();
+    if token.kind != keyword {
+        return Err(error(token, &format!("Expecting {}", keyword)));
+    }
+    tokens.advance();
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(());
+}
+
+fn parse_expression(tokens: &mut TokenStream) -> Result<Expression, Diagnostic> {
+    let expression = expression::parse(tokens)?;
+
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(expression);
+}
+
+// `None` for a declaration of a struct, union or enum on its own, which
+// only declares its tag and any enum constants. The variable's name is in
+// scope from the end of its declarator, so it can hide a typedef name in
+// its own initialiser.
+fn parse_variable_declaration(tokens: &mut TokenStream) -> Result<Option<VariableDeclaration>, Diagnostic> {
+    let var: Var;
+    let mut initializer: Option<Initializer> = None;
+
+    let is_tagged = is_tagged_type(&tokens.peek().kind);
+    let ty = parse_type_specifiers(tokens, "Expecting type")?;
+    if is_tagged && tokens.peek().kind == TokenKind::Semicolon {
+        tokens.advance();
+        return Ok(None);
+    }
+    let ty = parse_pointers(tokens, ty);
+
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref name) => {
+            var = Var { name: name.clone(), span: tokens.peek().span.clone(), symbol: None };
+            tokens.advance();
         },
-        _ => leftover_tokens = tokens[2..].to_vec(),
+        _ => return Err(error(tokens.peek(), "Expecting identifier")),
     }
+    let (is_unsized, lengths) = parse_array_lengths(tokens)?;
+    declare_name(tokens, &var.name, &var.span, NameKind::Variable);
 
-    match leftover_tokens.get(0) {
-        Some(Token::Semicolon) => {},
-        _ => return Err("Expecting ';'".to_string()),
+    match tokens.peek().kind {
+        TokenKind::Assignment => {
+            tokens.advance();
+            initializer = Some(initializer::parse(tokens)?);
+        },
+        _ => (),
     }
 
-    return Ok((
-        VariableDeclaration { var: var, expression: expression },
-        leftover_tokens[1..].to_vec(),
-    ));
+    match tokens.peek().kind {
+        TokenKind::Semicolon => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting ';'")),
+    };
+
+    return Ok(Some(VariableDeclaration {
+        var: var,
+        ty: ty,
+        lengths: lengths,
+        is_unsized: is_unsized,
+        initializer: initializer,
+    }));
 }
@@ -0,0 +1,186 @@
+use std::rc::Rc;
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, TagKind};
+use parser::{error, synchronize, parse_type_specifiers, parse_pointers, parse_array_dimensions};
+use span::Span;
+use diagnostic::Diagnostic;
+use types::{Type, StructType};
+
+// `struct tag`, `struct tag { members }` or `struct { members }`, or the
+// same with `union`. A tag refers to the innermost struct declared with it,
+// and declares a new, incomplete one in the current block if there isn't
+// one. `struct tag;` on its own always declares a new one, hiding any from
+// outside the block.
+pub fn parse(tokens: &mut TokenStream) -> Result<Type, Diagnostic> {
+    let is_union: bool;
+    match tokens.peek().kind {
+        TokenKind::KeywordStruct => is_union = false,
+        TokenKind::KeywordUnion => is_union = true,
+        _ => return Err(error(tokens.peek(), "Expecting 'struct' or 'union'")),
+    }
+    tokens.advance();
+
+    let tag: String;
+    let tag_span: Span;
+    match tokens.peek().kind {
+        TokenKind::Identifier(ref name) => {
+            tag = name.clone();
+            tag_span = tokens.peek().span.clone();
+            tokens.advance();
+        },
+        TokenKind::OpenBrace => {
+            let struct_type = StructType::new(None, is_union);
+            parse_members(tokens, &struct_type)?;
+            return Ok(Type::Struct(struct_type));
+        },
+        _ => return Err(error(tokens.peek(), "Expecting identifier or '{'")),
+    }
+
+    // Whether a definition or a declaration on its own can use the tag
+    // that's in scope, or needs a new one. Its members are still parsed
+    // if it's the wrong kind or already defined, into a struct that isn't
+    // declared.
+    let is_definition = tokens.peek().kind == TokenKind::OpenBrace;
+    let is_declaration = is_definition || tokens.peek().kind == TokenKind::Semicolon;
+    let struct_type: Rc<StructType>;
+    match tokens.lookup_tag(&tag) {
+        Some((previous, is_current_scope)) if is_current_scope || !is_declaration => {
+            let previous_span = previous.span.clone();
+            match previous.kind {
+                TagKind::Struct(ref previous_type) if previous_type.is_union == is_union => {
+                    if is_definition && previous_type.is_complete() {
+                        tokens.report(Diagnostic::error(
+                            format!("Redefinition of '{}'", Type::Struct(previous_type.clone())),
+                            tag_span,
+                        ).with_label(previous_span, "previously defined here"));
+                        struct_type = StructType::new(Some(tag), is_union);
+                    } else {
+                        struct_type = previous_type.clone();
+                    }
+                },
+                _ => {
+                    tokens.report(wrong_kind_of_tag(&tag, tag_span, previous_span));
+                    struct_type = StructType::new(Some(tag), is_union);
+                },
+            }
+        },
+        _ => {
+            struct_type = StructType::new(Some(tag.clone()), is_union);
+            tokens.declare_tag(tag, tag_span, TagKind::Struct(struct_type.clone()));
+        },
+    }
+
+    // The tag is declared before the members are parsed, so they can
+    // point to the struct they're in
+    if is_definition {
+        parse_members(tokens, &struct_type)?;
+    }
+    return Ok(Type::Struct(struct_type));
+}
+
+// `{ type name; ... }`, where each member declaration can name several
+// members of the same base type, like `int x, *y;`. Every member has to
+// have a complete type, so a struct can't contain itself, only a pointer to
+// itself.
+fn parse_members(tokens: &mut TokenStream, struct_type: &Rc<StructType>) -> Result<(), Diagnostic> {
+    let start = tokens.peek();
+    let mut members: Vec<(String, Type)> = Vec::new();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut has_errors = false;
+
+    match tokens.peek().kind {
+        TokenKind::OpenBrace => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting '{'")),
+    };
+
+    loop {
+        match tokens.peek().kind {
+            TokenKind::CloseBrace => break,
+            TokenKind::Eof => return Err(error(tokens.peek(), "Expecting '}'")),
+            _ => (),
+        }
+
+        // A member declaration that fails to parse is skipped, so the rest
+        // of the members are still checked
+        let member_start = tokens.checkpoint();
+        match parse_member_declaration(tokens, &mut members, &mut spans) {
+            Ok(()) => (),
+            Err(diagnostic) => {
+                synchronize(tokens, &member_start, &diagnostic);
+                tokens.report(diagnostic);
+                has_errors = true;
+            },
+        }
+    }
+    tokens.advance();
+
+    if members.is_empty() && !has_errors {
+        tokens.report(Diagnostic::error(
+            format!("'{}' has no members", Type::Struct(struct_type.clone())),
+            start.span.to(&tokens.previous().span),
+        ));
+    }
+    if !tokens.define_struct(struct_type, members) {
+        tokens.report(Diagnostic::error(
+            format!("Size of '{}' is too large", Type::Struct(struct_type.clone())),
+            start.span.to(&tokens.previous().span),
+        ));
+    }
+    return Ok(());
+}
+
+// `type name, *name, name[size];`, adding each member to the ones declared
+// so far
+fn parse_member_declaration(
+    tokens: &mut TokenStream,
+    members: &mut Vec<(String, Type)>,
+    spans: &mut Vec<Span>,
+) -> Result<(), Diagnostic> {
+    let base = parse_type_specifiers(tokens, "Expecting member type or '}'")?;
+    loop {
+        let ty = parse_pointers(tokens, base.clone());
+        let name: String;
+        let name_span = tokens.peek().span.clone();
+        match tokens.peek().kind {
+            TokenKind::Identifier(ref matched_name) => {
+                name = matched_name.clone();
+                tokens.advance();
+            },
+            _ => return Err(error(tokens.peek(), "Expecting identifier")),
+        }
+        let ty = parse_array_dimensions(tokens, ty)?;
+
+        if !ty.is_complete() {
+            tokens.report(Diagnostic::error(
+                format!("Member '{}' has incomplete type '{}'", name, ty),
+                name_span.clone(),
+            ));
+        }
+        match members.iter().position(|(member, _)| *member == name) {
+            Some(index) => tokens.report(Diagnostic::error(
+                format!("Duplicate member '{}'", name),
+                name_span.clone(),
+            ).with_label(spans[index].clone(), "previously declared here")),
+            None => (),
+        }
+        members.push((name, ty));
+        spans.push(name_span);
+
+        match tokens.peek().kind {
+            TokenKind::Comma => tokens.advance(),
+            TokenKind::Semicolon => {
+                tokens.advance();
+                break;
+            },
+            _ => return Err(error(tokens.peek(), "Expecting ',' or ';'")),
+        };
+    }
+    return Ok(());
+}
+
+pub fn wrong_kind_of_tag(tag: &str, span: Span, previous_span: Span) -> Diagnostic {
+    return Diagnostic::error(
+        format!("'{}' defined as wrong kind of tag", tag),
+        span,
+    ).with_label(previous_span, "previously declared here");
+}
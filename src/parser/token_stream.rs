@@ -0,0 +1,220 @@
+use std::rc::Rc;
+use lexer::{Token, TokenKind};
+use diagnostic::Diagnostic;
+use types::{Type, StructType};
+use span::Span;
+
+// Cursor over the lexed tokens. The lexer always ends the tokens with an
+// `Eof`, which the stream never moves past, so there's always a token to
+// look at.
+//
+// Errors the parser has recovered from are reported to the stream too, so
+// that backtracking to a checkpoint also drops errors from the abandoned
+// attempt. The same goes for tags, typedef names and enum constants, which
+// are declared in the stream as they're parsed, as the types and values
+// they name are needed straight away, and whether an identifier is a
+// typedef name decides how a declaration or cast parses.
+
+// How many operators deep an expression can be. Everything after the parser
+// walks expressions recursively, so anything deeper is reported here rather
+// than overflowing the stack later.
+const MAX_NESTING: usize = 5000;
+pub struct TokenStream<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    diagnostics: Vec<Diagnostic>,
+    // Every tag in scope, innermost last
+    tags: Vec<Tag>,
+    // Every ordinary identifier in scope, innermost last
+    names: Vec<Name>,
+    // How many blocks deep the parser is, 0 being file scope
+    depth: usize,
+    // Structs defined so far, in order, so a definition can be undone
+    definitions: Vec<Rc<StructType>>,
+    // How many operators deep the expression being parsed is
+    nesting: usize,
+}
+
+// A struct, union or enum tag, and the block it was declared in
+pub struct Tag {
+    pub name: String,
+    pub span: Span,
+    pub kind: TagKind,
+    depth: usize,
+}
+
+pub enum TagKind {
+    Struct(Rc<StructType>),
+    // Enums are all `int`s, and are declared by defining them
+    Enum,
+}
+
+// A typedef name, an enum constant, or a variable or function that's
+// declared with the same name as one of them, hiding it
+pub struct Name {
+    pub name: String,
+    pub span: Span,
+    pub kind: NameKind,
+    depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameKind {
+    Typedef(Type),
+    EnumConstant(i64),
+    Variable,
+}
+
+#[derive(Clone, Copy)]
+pub struct Checkpoint {
+    position: usize,
+    diagnostic_count: usize,
+    tag_count: usize,
+    name_count: usize,
+    depth: usize,
+    definition_count: usize,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(tokens: &'a [Token]) -> TokenStream<'a> {
+        return TokenStream {
+            tokens: tokens,
+            position: 0,
+            diagnostics: Vec::new(),
+            tags: Vec::new(),
+            names: Vec::new(),
+            depth: 0,
+            definitions: Vec::new(),
+            nesting: 0,
+        };
+    }
+
+    pub fn peek(&self) -> &'a Token {
+        return self.peek_nth(0);
+    }
+
+    pub fn peek_nth(&self, n: usize) -> &'a Token {
+        let index = (self.position + n).min(self.tokens.len() - 1);
+        return &self.tokens[index];
+    }
+
+    pub fn advance(&mut self) -> &'a Token {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Eof => (),
+            _ => self.position += 1,
+        }
+        return token;
+    }
+
+    // The last token consumed, used to find where a node's span ends
+    pub fn previous(&self) -> &'a Token {
+        return &self.tokens[self.position.saturating_sub(1)];
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        return Checkpoint {
+            position: self.position,
+            diagnostic_count: self.diagnostics.len(),
+            tag_count: self.tags.len(),
+            name_count: self.names.len(),
+            depth: self.depth,
+            definition_count: self.definitions.len(),
+        };
+    }
+
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+        self.diagnostics.truncate(checkpoint.diagnostic_count);
+        self.tags.truncate(checkpoint.tag_count);
+        self.names.truncate(checkpoint.name_count);
+        self.depth = checkpoint.depth;
+        for struct_type in self.definitions.drain(checkpoint.definition_count..) {
+            struct_type.undefine();
+        }
+    }
+
+    // The tokens consumed since the checkpoint was taken
+    pub fn consumed_since(&self, checkpoint: &Checkpoint) -> &'a [Token] {
+        return &self.tokens[checkpoint.position..self.position.max(checkpoint.position)];
+    }
+
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return std::mem::take(&mut self.diagnostics);
+    }
+
+    // Go one operator deeper into the expression being parsed. Whatever
+    // parses the operand goes back out with `unnest`, whether or not it
+    // parsed.
+    pub fn nest(&mut self) -> Result<(), Diagnostic> {
+        if self.nesting == MAX_NESTING {
+            return Err(Diagnostic::error("Expression is nested too deeply", self.peek().span.clone())
+                .with_note(format!("expressions can be at most {} operators deep", MAX_NESTING)));
+        }
+        self.nesting += 1;
+        return Ok(());
+    }
+
+    pub fn nesting(&self) -> usize {
+        return self.nesting;
+    }
+
+    pub fn unnest(&mut self, nesting: usize) {
+        self.nesting = nesting;
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.depth += 1;
+    }
+
+    // Forget the tags and names declared in the block that's ending
+    pub fn exit_scope(&mut self) {
+        let depth = self.depth;
+        self.tags.retain(|tag| tag.depth < depth);
+        self.names.retain(|name| name.depth < depth);
+        self.depth -= 1;
+    }
+
+    // The innermost tag with the name, and whether it was declared in the
+    // current block
+    pub fn lookup_tag(&self, name: &str) -> Option<(&Tag, bool)> {
+        return self.tags.iter().rev()
+            .find(|tag| tag.name == name)
+            .map(|tag| (tag, tag.depth == self.depth));
+    }
+
+    pub fn declare_tag(&mut self, name: String, span: Span, kind: TagKind) {
+        self.tags.push(Tag { name: name, span: span, kind: kind, depth: self.depth });
+    }
+
+    // The innermost declaration of an ordinary identifier, and whether it
+    // was in the current block
+    pub fn lookup_name(&self, name: &str) -> Option<(&Name, bool)> {
+        return self.names.iter().rev()
+            .find(|entry| entry.name == name)
+            .map(|entry| (entry, entry.depth == self.depth));
+    }
+
+    // The type a typedef name stands for, if the identifier is one
+    pub fn typedef(&self, name: &str) -> Option<&Type> {
+        match self.lookup_name(name) {
+            Some((Name { kind: NameKind::Typedef(ty), .. }, _)) => return Some(ty),
+            _ => return None,
+        }
+    }
+
+    pub fn declare_name(&mut self, name: String, span: Span, kind: NameKind) {
+        self.names.push(Name { name: name, span: span, kind: kind, depth: self.depth });
+    }
+
+    // Whether its size fits in an `i64`, see `StructType::define`
+    pub fn define_struct(&mut self, struct_type: &Rc<StructType>, members: Vec<(String, Type)>) -> bool {
+        let fits = struct_type.define(members);
+        self.definitions.push(struct_type.clone());
+        return fits;
+    }
+}
@@ -0,0 +1,41 @@
+use lexer::TokenKind;
+use parser::token_stream::{TokenStream, NameKind};
+use parser::{error, declare_name, parse_type_specifiers, parse_pointers, parse_array_dimensions};
+use diagnostic::Diagnostic;
+
+// `typedef type name, *pointer_name, array_name[size];`, where each
+// declarator is like a variable's. The names stand for their types from
+// then on until the end of the block, unless something else declared with
+// the same name hides them.
+pub fn parse(tokens: &mut TokenStream) -> Result<(), Diagnostic> {
+    match tokens.peek().kind {
+        TokenKind::KeywordTypedef => tokens.advance(),
+        _ => return Err(error(tokens.peek(), "Expecting 'typedef'")),
+    };
+
+    let base = parse_type_specifiers(tokens, "Expecting type")?;
+    loop {
+        let ty = parse_pointers(tokens, base.clone());
+        let name: String;
+        let name_span = tokens.peek().span.clone();
+        match tokens.peek().kind {
+            TokenKind::Identifier(ref matched_name) => {
+                name = matched_name.clone();
+                tokens.advance();
+            },
+            _ => return Err(error(tokens.peek(), "Expecting identifier")),
+        }
+        let ty = parse_array_dimensions(tokens, ty)?;
+        declare_name(tokens, &name, &name_span, NameKind::Typedef(ty));
+
+        match tokens.peek().kind {
+            TokenKind::Comma => tokens.advance(),
+            TokenKind::Semicolon => {
+                tokens.advance();
+                break;
+            },
+            _ => return Err(error(tokens.peek(), "Expecting ',' or ';'")),
+        };
+    }
+    return Ok(());
+}
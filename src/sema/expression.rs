@@ -0,0 +1,357 @@
+use std::mem;
+use sema::Analyzer;
+use sema::factor;
+use parser::constant;
+use parser::constant::Value;
+use parser::factor::{Factor, FactorKind, Cast, is_lvalue};
+use parser::expression::{
+    Expression,
+    ExpressionKind,
+    Assignment,
+    ConditionalExpression,
+    BinaryExpression,
+    BinaryOperator,
+    assignment_token,
+};
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
+
+// Returns the expression's type, which is also recorded on it for the
+// generator. Anything that's reported still gets a type, so checking can
+// carry on.
+pub fn analyze(analyzer: &mut Analyzer, expression: &mut Expression) -> Type {
+    let ty: Type;
+    match expression.kind {
+        ExpressionKind::Assignment(ref mut assignment) => {
+            ty = analyze_assignment(analyzer, assignment, &expression.span);
+        },
+        ExpressionKind::Conditional(ref mut conditional) => {
+            ty = analyze_conditional(analyzer, conditional, &expression.span);
+        },
+        ExpressionKind::Binary(ref mut binary) => {
+            ty = analyze_binary(analyzer, binary, &expression.span);
+        },
+        ExpressionKind::Factor(ref mut factor) => {
+            ty = factor::analyze_value(analyzer, factor);
+        },
+    }
+
+    expression.ty = Some(ty.clone());
+    return ty;
+}
+
+// An expression that's compared with zero, like an `if`'s condition, which
+// has to be a scalar
+pub fn analyze_condition(analyzer: &mut Analyzer, expression: &mut Expression) {
+    let ty = analyze(analyzer, expression);
+    check_scalar(analyzer, &ty, &expression.span);
+}
+
+pub fn check_scalar(analyzer: &mut Analyzer, ty: &Type, span: &Span) {
+    if !ty.is_scalar() && !ty.is_error() {
+        analyzer.report(Diagnostic::error(
+            format!("Used '{}' value where a scalar is required", ty),
+            span.clone(),
+        ));
+    }
+}
+
+// Whether the expression's value can be stored in a `ty` without a cast.
+// Integers and floating point values convert to each other, being cut down
+// to size or truncated towards zero if they need to be, but integers only
+// convert to pointers when they're a null pointer constant.
+pub fn is_convertible(expression: &Expression, ty: &Type) -> bool {
+    let value_type = expression.ty();
+    if value_type == ty || value_type.is_error() || ty.is_error() {
+        return true;
+    }
+    if value_type.is_arithmetic() && ty.is_arithmetic() {
+        return true;
+    }
+    return ty.is_pointer() && is_null_pointer_constant(expression);
+}
+
+// Have the expression converted to `ty` when it's evaluated, unless it's
+// already of that type or is an error. The conversion is a cast, as if it
+// had been written.
+pub fn convert(analyzer: &mut Analyzer, expression: &mut Expression, ty: &Type) {
+    if expression.ty() == ty || expression.ty().is_error() {
+        return;
+    }
+    check_overflow(analyzer, expression, ty);
+
+    let span = expression.span.clone();
+    let placeholder = Factor { kind: FactorKind::Constant(0, Type::Int), span: span.clone(), ty: None };
+    let operand = mem::replace(expression, Expression {
+        kind: ExpressionKind::Factor(placeholder),
+        span: span.clone(),
+        ty: None,
+    });
+    let cast = Factor {
+        kind: FactorKind::Cast(Box::new(Cast { ty: ty.clone(), expression: operand })),
+        span: span,
+        ty: Some(ty.clone()),
+    };
+    expression.kind = ExpressionKind::Factor(cast);
+    expression.ty = Some(ty.clone());
+}
+
+// Warn about a constant whose value would be changed by converting it to an
+// integer type, like gcc's -Woverflow. A negative value that fits in the
+// signed version of an unsigned type is left alone, as `unsigned u = -1;`
+// is deliberate, and so is an unsigned value converted to the signed type
+// of the same size, like `0xffffffff` to `int`. Floating point values are
+// only warned about if they're out of range, not for being truncated.
+pub fn check_overflow(analyzer: &mut Analyzer, expression: &Expression, ty: &Type) {
+    let (value, value_type) = match constant::typed(expression) {
+        Some(typed) if ty.is_integer() => typed,
+        _ => return,
+    };
+
+    let bits = 8 * ty.size() as u32;
+    let signed_range = -(1i128 << (bits - 1))..(1i128 << (bits - 1));
+    let unsigned_range = 0..(1i128 << bits);
+    let conversion = if ty.is_signed() { "Overflow in conversion" } else { "Unsigned conversion" };
+    let message: String;
+    match (value, value.convert(&value_type, ty)) {
+        (Value::Integer(before), Value::Integer(after)) => {
+            let before = if value_type.is_signed() { i128::from(before) } else { i128::from(before as u64) };
+            let after = if ty.is_signed() { i128::from(after) } else { i128::from(after as u64) };
+            let is_reinterpreted = !value_type.is_signed() && value_type.size() == ty.size();
+            if before == after || signed_range.contains(&before) || is_reinterpreted {
+                return;
+            }
+            message = format!(
+                "{} from '{}' to '{}' changes value from '{}' to '{}'",
+                conversion, value_type, ty, before, after,
+            );
+        },
+        // What an out of range value converts to is undefined
+        (Value::Floating(before), _) => {
+            let range = if ty.is_signed() { signed_range } else { unsigned_range };
+            if before.trunc() >= range.start as f64 && before.trunc() < range.end as f64 {
+                return;
+            }
+            message = format!("{} from '{}' to '{}' is out of range for '{:e}'", conversion, value_type, ty, before);
+        },
+        (Value::Integer(_), Value::Floating(_)) => panic!("Integer converted to an integer type came out floating point"),
+    }
+    analyzer.report(Diagnostic::warning(message, expression.span.clone()));
+}
+
+// A constant integer expression that's zero, like `0`, `1 - 1` or `'\0'`
+pub fn is_null_pointer_constant(expression: &Expression) -> bool {
+    return expression.ty().is_integer() && constant::evaluate(expression) == Some(0);
+}
+
+fn analyze_assignment(analyzer: &mut Analyzer, assignment: &mut Assignment, span: &Span) -> Type {
+    let target_type = factor::analyze(analyzer, &mut assignment.target);
+    let value_type = analyze(analyzer, &mut assignment.expression);
+    if target_type.is_error() {
+        return Type::Error;
+    }
+
+    if !is_lvalue(&assignment.target) {
+        analyzer.report(Diagnostic::error(
+            format!("Left operand of {} is not assignable", assignment_token(&assignment.operator)),
+            assignment.target.span.clone(),
+        ));
+        return Type::Error;
+    }
+
+    if target_type.is_array() {
+        analyzer.report(Diagnostic::error(
+            format!("Assignment to expression with array type '{}'", target_type),
+            span.clone(),
+        ));
+        return target_type.decay();
+    }
+
+    match assignment.operator {
+        None => {
+            if !is_convertible(&assignment.expression, &target_type) {
+                analyzer.report(Diagnostic::error(
+                    format!("Incompatible types when assigning to type '{}' from type '{}'", target_type, value_type),
+                    span.clone(),
+                ));
+            }
+            convert(analyzer, &mut assignment.expression, &target_type);
+        },
+        // The result has to fit back in the target, so `pointer -= pointer`
+        // isn't allowed even though `pointer - pointer` is. The generator
+        // converts the target to the type the operation is done in, and the
+        // result back again.
+        Some(ref operator) => {
+            let fits = match binary_type(operator, &target_type, &value_type, false) {
+                Some(ty) => ty == target_type || ty.is_error() || (ty.is_arithmetic() && target_type.is_arithmetic()),
+                None => false,
+            };
+            if !fits {
+                analyzer.report(invalid_operands(operator, &target_type, &value_type, span));
+            }
+            match operand_type(operator, &target_type, &value_type) {
+                Some(ref ty) if value_type.is_arithmetic() => {
+                    check_division(analyzer, operator, ty, &assignment.expression);
+                    convert(analyzer, &mut assignment.expression, ty);
+                },
+                _ => (),
+            }
+        },
+    }
+
+    return target_type;
+}
+
+// Arithmetic branches are converted to their common type. Otherwise one has
+// to be convertible to the other's type, like a null pointer constant to a
+// pointer, or a struct to the same struct.
+fn analyze_conditional(analyzer: &mut Analyzer, conditional: &mut ConditionalExpression, span: &Span) -> Type {
+    analyze_condition(analyzer, &mut conditional.condition);
+    let then_type = analyze(analyzer, &mut conditional.expression);
+    let else_type = analyze(analyzer, &mut conditional.else_expression);
+
+    if then_type.is_arithmetic() && else_type.is_arithmetic() {
+        let ty = then_type.common_type(&else_type);
+        convert(analyzer, &mut conditional.expression, &ty);
+        convert(analyzer, &mut conditional.else_expression, &ty);
+        return ty;
+    }
+    if is_convertible(&conditional.else_expression, &then_type) {
+        convert(analyzer, &mut conditional.else_expression, &then_type);
+        return then_type;
+    }
+    if is_convertible(&conditional.expression, &else_type) {
+        convert(analyzer, &mut conditional.expression, &else_type);
+        return else_type;
+    }
+
+    analyzer.report(Diagnostic::error(
+        format!("Type mismatch in conditional expression (have '{}' and '{}')", then_type, else_type),
+        span.clone(),
+    ));
+    return Type::Error;
+}
+
+fn analyze_binary(analyzer: &mut Analyzer, binary: &mut BinaryExpression, span: &Span) -> Type {
+    let left = analyze(analyzer, &mut binary.left);
+    let right = analyze(analyzer, &mut binary.right);
+    // Only worth evaluating when it's a pointer and an integer, as it means
+    // walking the whole operand
+    let has_null_pointer_constant = left.is_pointer() != right.is_pointer()
+        && (is_null_pointer_constant(&binary.left) || is_null_pointer_constant(&binary.right));
+
+    match binary_type(&binary.operator, &left, &right, has_null_pointer_constant) {
+        Some(ty) => {
+            if let Some(operand_type) = operand_type(&binary.operator, &left, &right) {
+                check_division(analyzer, &binary.operator, &operand_type, &binary.right);
+                if left.is_arithmetic() {
+                    convert(analyzer, &mut binary.left, &operand_type);
+                }
+                if right.is_arithmetic() {
+                    convert(analyzer, &mut binary.right, &operand_type);
+                }
+            }
+            return ty;
+        },
+        None => {
+            analyzer.report(invalid_operands(&binary.operator, &left, &right, span));
+            return Type::Error;
+        },
+    }
+}
+
+// The type of `left operator right`, or `None` if the operator can't take
+// those operands. Arithmetic operands are converted to a common type first,
+// see `Type::common_type`, except that a shift has the type of its left
+// operand. `%`, shifts and bitwise operators only take integers. Pointers
+// can have integers added or subtracted, which moves them by whole
+// elements as long as the elements are complete, and can be compared with
+// and subtracted from pointers of the same type. Structs can't be operands
+// of any of them but the comma. An operand that's already been reported
+// makes the result an error too.
+fn binary_type(operator: &BinaryOperator, left: &Type, right: &Type, has_null_pointer_constant: bool) -> Option<Type> {
+    let is_int = left.is_integer() && right.is_integer();
+    let is_arithmetic = left.is_arithmetic() && right.is_arithmetic();
+    let is_same_pointer = left.is_pointer() && left == right;
+    let is_steppable = |pointer: &Type| pointer.pointee().is_some_and(Type::is_complete);
+
+    match operator {
+        BinaryOperator::Comma => return Some(right.clone()),
+        _ if left.is_error() || right.is_error() => return Some(Type::Error),
+        BinaryOperator::LogicalOr | BinaryOperator::LogicalAnd if left.is_scalar() && right.is_scalar() => {
+            return Some(Type::Int);
+        },
+        BinaryOperator::Equal | BinaryOperator::NotEqual => {
+            let is_null_comparison = left.is_pointer() != right.is_pointer() && has_null_pointer_constant;
+            if is_arithmetic || is_same_pointer || is_null_comparison {
+                return Some(Type::Int);
+            }
+            return None;
+        },
+        BinaryOperator::LessThan
+        | BinaryOperator::LessThanOrEqual
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::GreaterThanOrEqual => {
+            if is_arithmetic || is_same_pointer {
+                return Some(Type::Int);
+            }
+            return None;
+        },
+        BinaryOperator::Addition => match (left.is_pointer(), right.is_pointer()) {
+            (false, false) if is_arithmetic => return Some(left.common_type(right)),
+            (true, false) if right.is_integer() && is_steppable(left) => return Some(left.clone()),
+            (false, true) if left.is_integer() && is_steppable(right) => return Some(right.clone()),
+            _ => return None,
+        },
+        BinaryOperator::Subtraction => match (left.is_pointer(), right.is_pointer()) {
+            (false, false) if is_arithmetic => return Some(left.common_type(right)),
+            (true, false) if right.is_integer() && is_steppable(left) => return Some(left.clone()),
+            // The number of elements between them
+            (true, true) if is_same_pointer && is_steppable(left) => return Some(Type::Long),
+            _ => return None,
+        },
+        BinaryOperator::LeftShift | BinaryOperator::RightShift if is_int => return Some(left.promote()),
+        BinaryOperator::Multiplication | BinaryOperator::Division if is_arithmetic => {
+            return Some(left.common_type(right));
+        },
+        _ if is_int => return Some(left.common_type(right)),
+        _ => return None,
+    }
+}
+
+// The type arithmetic operands are converted to before the operation, so the
+// generator only has to handle operands of one type. A shift count is
+// converted to the type being shifted, as only its low bits are used. An
+// integer added to or subtracted from a pointer becomes a `long`, ready to
+// be scaled, and a null pointer constant compared with a pointer becomes a
+// pointer. `None` if the operands aren't combined at all, or one of them is
+// an error.
+fn operand_type(operator: &BinaryOperator, left: &Type, right: &Type) -> Option<Type> {
+    match operator {
+        BinaryOperator::Comma | BinaryOperator::LogicalOr | BinaryOperator::LogicalAnd => return None,
+        _ if left.is_error() || right.is_error() => return None,
+        BinaryOperator::LeftShift | BinaryOperator::RightShift => return Some(left.promote()),
+        _ if left.is_arithmetic() && right.is_arithmetic() => return Some(left.common_type(right)),
+        BinaryOperator::Addition | BinaryOperator::Subtraction => return Some(Type::Long),
+        _ if left.is_pointer() => return Some(left.clone()),
+        _ => return Some(right.clone()),
+    }
+}
+
+// Integer division by a constant zero is undefined. Floating point division
+// by zero isn't, it gives an infinity or NaN.
+fn check_division(analyzer: &mut Analyzer, operator: &BinaryOperator, ty: &Type, divisor: &Expression) {
+    let is_division = *operator == BinaryOperator::Division || *operator == BinaryOperator::Modulo;
+    if is_division && ty.is_integer() && constant::evaluate(divisor) == Some(0) {
+        analyzer.report(Diagnostic::warning("Division by zero", divisor.span.clone())
+            .with_note("dividing by zero is undefined, and will usually crash the program"));
+    }
+}
+
+fn invalid_operands(operator: &BinaryOperator, left: &Type, right: &Type, span: &Span) -> Diagnostic {
+    return Diagnostic::error(
+        format!("Invalid operands to binary {} (have '{}' and '{}')", operator, left, right),
+        span.clone(),
+    );
+}
@@ -0,0 +1,377 @@
+use std::mem;
+use sema::{Analyzer, FunctionDeclaration, Storage};
+use sema::expression;
+use parser::expression::{Expression, ExpressionKind};
+use parser::factor::{
+    Factor,
+    FactorKind,
+    FunctionCall,
+    UnaryOperation,
+    UnaryOperator,
+    IncrementOperation,
+    IncrementOperator,
+    Subscript,
+    MemberAccess,
+    is_lvalue,
+};
+use span::Span;
+use diagnostic::Diagnostic;
+use types::Type;
+
+// Returns the factor's type, which is also recorded on it for the
+// generator. An array keeps its array type, for the few places that use it
+// as a whole, like `sizeof` and `&`.
+pub fn analyze(analyzer: &mut Analyzer, factor: &mut Factor) -> Type {
+    let ty: Type;
+    match factor.kind {
+        FactorKind::Expression(ref mut expression) => {
+            expression::analyze(analyzer, expression);
+            // `(array)` is still an array
+            match expression.kind {
+                ExpressionKind::Factor(ref inner) => ty = inner.ty().clone(),
+                _ => ty = expression.ty().clone(),
+            }
+        },
+        FactorKind::UnaryOperation(ref mut operation) => {
+            ty = analyze_unary_operation(analyzer, operation, &factor.span);
+        },
+        FactorKind::Constant(_, ref constant_type) | FactorKind::FloatingConstant(_, ref constant_type) => {
+            ty = constant_type.clone();
+        },
+        // With room for the terminating zero
+        FactorKind::StringLiteral(ref bytes) => {
+            ty = Type::Array(Box::new(Type::Char), bytes.len() as i64 + 1);
+        },
+        FactorKind::Identifier(ref mut var) => {
+            ty = analyzer.resolve(var);
+        },
+        FactorKind::FunctionCall(ref mut call) => {
+            ty = analyze_function_call(analyzer, call, &factor.span);
+        },
+        FactorKind::PrefixIncrement(ref mut operation) | FactorKind::PostfixIncrement(ref mut operation) => {
+            ty = analyze_increment(analyzer, operation, &factor.span);
+        },
+        FactorKind::Subscript(ref mut subscript) => {
+            ty = analyze_subscript(analyzer, subscript, &factor.span);
+        },
+        FactorKind::Member(ref mut access) => {
+            ty = analyze_member_access(analyzer, access);
+        },
+        // The result is a `size_t`
+        FactorKind::SizeOf(ref mut operand) => {
+            let operand_type = analyze(analyzer, operand);
+            check_sizeof(analyzer, &operand_type, &factor.span);
+            ty = if operand_type.is_error() { Type::Error } else { Type::UnsignedLong };
+        },
+        FactorKind::SizeOfType(ref operand_type) => {
+            check_sizeof(analyzer, operand_type, &factor.span);
+            ty = Type::UnsignedLong;
+        },
+        // Any arithmetic value or pointer can be cast to any other, but
+        // floating point values and pointers don't mix, and structs can't
+        // be cast at all
+        FactorKind::Cast(ref mut cast) => {
+            let from = expression::analyze(analyzer, &mut cast.expression);
+            let is_mixed = (from.is_floating() && cast.ty.is_pointer()) || (from.is_pointer() && cast.ty.is_floating());
+            let is_scalar = (from.is_scalar() || from.is_error()) && cast.ty.is_scalar();
+            if is_mixed || !is_scalar {
+                analyzer.report(Diagnostic::error(
+                    format!("Invalid cast from type '{}' to type '{}'", from, cast.ty),
+                    factor.span.clone(),
+                ));
+            }
+            ty = cast.ty.clone();
+        },
+    }
+
+    factor.ty = Some(ty.clone());
+    return ty;
+}
+
+// The type of the factor's value, with an array standing for a pointer to
+// its first element
+pub fn analyze_value(analyzer: &mut Analyzer, factor: &mut Factor) -> Type {
+    return analyze(analyzer, factor).decay();
+}
+
+fn analyze_unary_operation(analyzer: &mut Analyzer, operation: &mut UnaryOperation, span: &Span) -> Type {
+    if operation.operator == UnaryOperator::AddressOf {
+        let operand = analyze(analyzer, &mut operation.factor);
+        if operand.is_error() {
+            return Type::Error;
+        }
+        if !is_lvalue(&operation.factor) {
+            analyzer.report(Diagnostic::error("Operand of '&' has no address", operation.factor.span.clone()));
+            return Type::Error;
+        }
+        return Type::Pointer(Box::new(operand));
+    }
+
+    let operand = analyze_value(analyzer, &mut operation.factor);
+    if operand.is_error() {
+        return Type::Error;
+    }
+
+    match operation.operator {
+        UnaryOperator::Negation | UnaryOperator::BitwiseComplement => {
+            let is_valid = match operation.operator {
+                UnaryOperator::Negation => operand.is_arithmetic(),
+                _ => operand.is_integer(),
+            };
+            if !is_valid {
+                analyzer.report(Diagnostic::error(
+                    format!("Invalid operand to unary {} (have '{}')", operation.operator, operand),
+                    span.clone(),
+                ));
+                return Type::Error;
+            }
+            return operand.promote();
+        },
+        UnaryOperator::LogicalNegation => {
+            expression::check_scalar(analyzer, &operand, span);
+            return Type::Int;
+        },
+        UnaryOperator::Dereference => match operand.pointee() {
+            Some(pointee) => return pointee.clone(),
+            None => {
+                analyzer.report(Diagnostic::error(
+                    format!("Invalid operand to unary {} (have '{}')", operation.operator, operand),
+                    span.clone(),
+                ).with_note("only pointers can be dereferenced"));
+                return Type::Error;
+            },
+        },
+        UnaryOperator::AddressOf => panic!("Address taken of the operand's value"),
+    }
+}
+
+// Pointers are moved on by one element. An array or a struct can't be
+// moved at all.
+fn analyze_increment(analyzer: &mut Analyzer, operation: &mut IncrementOperation, span: &Span) -> Type {
+    let operand = analyze(analyzer, &mut operation.operand);
+    let operator: &str;
+    match operation.operator {
+        IncrementOperator::Increment => operator = "'++'",
+        IncrementOperator::Decrement => operator = "'--'",
+    }
+    if operand.is_error() {
+        return Type::Error;
+    }
+    if !is_lvalue(&operation.operand) {
+        analyzer.report(Diagnostic::error(
+            format!("Operand of {} is not assignable", operator),
+            operation.operand.span.clone(),
+        ));
+        return Type::Error;
+    }
+    if !operand.is_scalar() {
+        analyzer.report(Diagnostic::error(
+            format!("Invalid operand to {} (have '{}')", operator, operand),
+            span.clone(),
+        ));
+        return operand.decay();
+    }
+    return operand;
+}
+
+// `E1[E2]` is the same as `*((E1) + (E2))`, so the array or pointer can be
+// on either side, as in `3[array]`
+fn analyze_subscript(analyzer: &mut Analyzer, subscript: &mut Subscript, span: &Span) -> Type {
+    let mut array = analyze_value(analyzer, &mut subscript.array);
+    let mut index = expression::analyze(analyzer, &mut subscript.index);
+    if index.is_pointer() && !array.is_pointer() {
+        swap_operands(subscript);
+        mem::swap(&mut array, &mut index);
+    }
+
+    // Made a `long`, ready to be scaled by the element size
+    if index.is_integer() {
+        expression::convert(analyzer, &mut subscript.index, &Type::Long);
+    } else if !index.is_error() {
+        analyzer.report(Diagnostic::error(
+            format!("Array subscript is not an integer (have '{}')", index),
+            subscript.index.span.clone(),
+        ));
+    }
+    match array.pointee() {
+        Some(element) => return element.clone(),
+        None if array.is_error() => return Type::Error,
+        None => {
+            analyzer.report(Diagnostic::error(
+                format!("Subscripted value is neither an array nor a pointer (have '{}')", array),
+                span.clone(),
+            ));
+            return Type::Error;
+        },
+    }
+}
+
+// Puts the pointer in front, where the generator expects it, keeping the
+// types the operands have been given
+fn swap_operands(subscript: &mut Subscript) {
+    let placeholder = Factor { kind: FactorKind::Constant(0, Type::Int), span: subscript.array.span.clone(), ty: None };
+    let array = mem::replace(&mut subscript.array, placeholder);
+    let array_type = array.ty().decay();
+    let index_span = subscript.index.span.clone();
+    let index_type = subscript.index.ty().clone();
+    let index = mem::replace(&mut subscript.index, Expression {
+        span: array.span.clone(),
+        kind: ExpressionKind::Factor(array),
+        ty: Some(array_type),
+    });
+    subscript.array = Factor {
+        kind: FactorKind::Expression(Box::new(index)),
+        span: index_span,
+        ty: Some(index_type),
+    };
+}
+
+// The member's type. `->` takes a pointer to a struct, and `.` the struct
+// itself, which has to be complete.
+fn analyze_member_access(analyzer: &mut Analyzer, access: &mut MemberAccess) -> Type {
+    let operand = analyze_value(analyzer, &mut access.operand);
+    if operand.is_error() {
+        return Type::Error;
+    }
+    let struct_type: Type;
+    match (access.is_arrow, operand.pointee()) {
+        (true, Some(pointee)) if pointee.is_struct() => struct_type = pointee.clone(),
+        (false, _) if operand.is_struct() => struct_type = operand.clone(),
+        (true, _) => {
+            analyzer.report(Diagnostic::error(
+                format!("Invalid type argument of '->' (have '{}')", operand),
+                access.operand.span.clone(),
+            ).with_note("'->' takes a pointer to a struct or union"));
+            return Type::Error;
+        },
+        (false, pointee) => {
+            let diagnostic = Diagnostic::error(
+                format!("Request for member '{}' in something not a struct or union (have '{}')", access.name, operand),
+                access.operand.span.clone(),
+            );
+            match pointee {
+                Some(pointee) if pointee.is_struct() => analyzer.report(diagnostic.with_note("did you mean to use '->'?")),
+                _ => analyzer.report(diagnostic),
+            }
+            return Type::Error;
+        },
+    }
+
+    if !struct_type.is_complete() {
+        analyzer.report(Diagnostic::error(
+            format!("Invalid use of incomplete type '{}'", struct_type),
+            access.operand.span.clone(),
+        ));
+        return Type::Error;
+    }
+    match struct_type.struct_type().and_then(|struct_type| struct_type.member(&access.name)) {
+        Some(member) => return member.ty,
+        None => {
+            analyzer.report(Diagnostic::error(
+                format!("'{}' has no member named '{}'", struct_type, access.name),
+                access.name_span.clone(),
+            ));
+            return Type::Error;
+        },
+    }
+}
+
+fn check_sizeof(analyzer: &mut Analyzer, ty: &Type, span: &Span) {
+    if !ty.is_complete() {
+        analyzer.report(Diagnostic::error(
+            format!("Invalid application of 'sizeof' to incomplete type '{}'", ty),
+            span.clone(),
+        ));
+    }
+}
+
+// A local variable with the function's name hides it, so it can't be called.
+// A struct it returns is put in a slot in the frame, where it can be used
+// from like any other struct.
+fn analyze_function_call(analyzer: &mut Analyzer, call: &mut FunctionCall, span: &Span) -> Type {
+    let declaration = function_declaration(analyzer, call, span);
+
+    for argument in call.arguments.iter_mut() {
+        expression::analyze(analyzer, argument);
+    }
+
+    // Arguments are converted to the parameters' types, as if they were
+    // being assigned. Any more than that to a variadic function have the
+    // default argument promotions instead, the integer promotions and
+    // `float` to `double`.
+    match declaration {
+        Some(declaration) => {
+            for argument in call.arguments.iter_mut().skip(declaration.param_types.len()) {
+                let ty = if *argument.ty() == Type::Float { Type::Double } else { argument.ty().promote() };
+                expression::convert(analyzer, argument, &ty);
+            }
+            for (index, (argument, param_type)) in call.arguments.iter_mut().zip(declaration.param_types.iter()).enumerate() {
+                if !expression::is_convertible(argument, param_type) {
+                    analyzer.report(Diagnostic::error(
+                        format!("Incompatible type for argument {} of '{}'", index + 1, call.name),
+                        argument.span.clone(),
+                    ).with_note(format!("expected '{}' but argument is of type '{}'", param_type, argument.ty())));
+                }
+                expression::convert(analyzer, argument, param_type);
+            }
+
+            let return_type = declaration.return_type;
+            if !return_type.is_complete() {
+                analyzer.report(Diagnostic::error(
+                    format!("Invalid use of incomplete type '{}'", return_type),
+                    span.clone(),
+                ).with_note(format!("'{}' returns it", call.name)));
+            } else if return_type.is_struct() {
+                match analyzer.allocate(&return_type, span) {
+                    Storage::Stack(offset) => call.return_slot = Some(offset),
+                    Storage::Static(_) => panic!("Frame slot isn't on the stack"),
+                }
+            }
+            return return_type;
+        },
+        None => return Type::Error,
+    }
+}
+
+// The declaration of the function being called, if there is one
+fn function_declaration(analyzer: &mut Analyzer, call: &FunctionCall, span: &Span) -> Option<FunctionDeclaration> {
+    match analyzer.lookup(&call.name) {
+        Some(symbol) => {
+            let declaration_span = analyzer.symbols.get(symbol).span.clone();
+            analyzer.report(Diagnostic::error(
+                format!("Called object '{}' is not a function", call.name),
+                call.name_span.clone(),
+            ).with_label(declaration_span, "declared here as a variable"));
+            return None;
+        },
+        None => (),
+    }
+
+    let declaration: FunctionDeclaration;
+    match analyzer.functions.get(&call.name) {
+        Some(matched_declaration) => declaration = matched_declaration.clone(),
+        None => {
+            analyzer.report(Diagnostic::error(
+                format!("Function '{}' has not been declared", call.name),
+                call.name_span.clone(),
+            ));
+            return None;
+        },
+    }
+
+    let is_missing = call.arguments.len() < declaration.param_types.len();
+    if is_missing || (call.arguments.len() > declaration.param_types.len() && !declaration.is_variadic) {
+        analyzer.report(Diagnostic::error(
+            format!(
+                "Function '{}' takes {}{} arguments but {} were supplied",
+                call.name,
+                if declaration.is_variadic { "at least " } else { "" },
+                declaration.param_types.len(),
+                call.arguments.len(),
+            ),
+            span.clone(),
+        ).with_label(declaration.span.clone(), "declared here"));
+    }
+
+    return Some(declaration);
+}
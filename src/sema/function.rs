@@ -0,0 +1,147 @@
+use asm::{argument_registers, classify};
+use sema::statement;
+use sema::{Analyzer, FunctionDeclaration, FunctionDefinition, Storage, SymbolId};
+use parser::StorageClass;
+use parser::expression::Var;
+use parser::function::{Function, Parameter};
+use diagnostic::Diagnostic;
+use types::Type;
+
+// Returns the function's definition, or `None` if this is only a
+// declaration
+pub fn analyze(analyzer: &mut Analyzer, function: Function) -> Option<FunctionDefinition> {
+    // Declared before the body is checked so the function can call itself
+    let declaration = FunctionDeclaration {
+        return_type: function.return_type.clone(),
+        param_types: function.params.iter().map(|param| param.ty.clone()).collect(),
+        is_variadic: function.is_variadic,
+        span: function.name_span.clone(),
+        is_defined: function.body.is_some(),
+        is_static: function.storage_class == Some(StorageClass::Static),
+    };
+    let is_static = declare(analyzer, &function.name, declaration);
+
+    let mut body = function.body?;
+
+    // Only a definition needs to know how big its parameters and what it
+    // returns are
+    if !function.return_type.is_complete() {
+        analyzer.report(Diagnostic::error(
+            format!("Return type '{}' is an incomplete type", function.return_type),
+            function.name_span.clone(),
+        ));
+    }
+    for param in function.params.iter().filter(|param| !param.ty.is_complete()) {
+        analyzer.report(Diagnostic::error(
+            format!("Parameter has incomplete type '{}'", param.ty),
+            param.span.clone(),
+        ));
+    }
+
+    // The body shares a scope with the parameters, so it can't redeclare
+    // them
+    analyzer.enter_function(&function.return_type);
+    let mut return_address: Option<i64> = None;
+    if classify(&function.return_type).is_none() {
+        match analyzer.allocate(&Type::Pointer(Box::new(function.return_type.clone())), &function.name_span) {
+            Storage::Stack(offset) => return_address = Some(offset),
+            Storage::Static(_) => panic!("Frame slot isn't on the stack"),
+        }
+    }
+    let mut params: Vec<SymbolId> = Vec::new();
+    let types: Vec<Type> = function.params.iter().map(|param| param.ty.clone()).collect();
+    let mut stack_offset = 16;
+    for (param, registers) in function.params.into_iter().zip(argument_registers(&function.return_type, &types)) {
+        let storage: Storage;
+        match registers {
+            Some(_) => storage = analyzer.allocate(&param.ty, &param.span),
+            // Each argument on the stack takes up whole 8 byte slots
+            None => {
+                storage = Storage::Stack(stack_offset);
+                stack_offset += (param.ty.size() + 7) / 8 * 8;
+            },
+        }
+        match declare_parameter(analyzer, param, storage) {
+            Some(symbol) => params.push(symbol),
+            None => (),
+        }
+    }
+    statement::analyze_block_items(analyzer, &mut body);
+    let frame_size = analyzer.exit_function();
+
+    return Some(FunctionDefinition {
+        name: function.name,
+        is_static: is_static,
+        return_type: function.return_type,
+        params: params,
+        return_address: return_address,
+        body: body,
+        frame_size: frame_size,
+    });
+}
+
+// Add the function to the file scope, reporting a declaration that doesn't
+// agree with an earlier one. Returns whether the function is static.
+fn declare(analyzer: &mut Analyzer, name: &str, mut declaration: FunctionDeclaration) -> bool {
+    match analyzer.globals.get(name) {
+        Some(global) => analyzer.diagnostics.push(Diagnostic::error(
+            format!("'{}' redeclared as a different kind of symbol", name),
+            declaration.span.clone(),
+        ).with_label(global.span.clone(), "previously declared as a variable here")),
+        None => (),
+    }
+
+    match analyzer.functions.get(name) {
+        Some(previous) => {
+            if previous.param_types.len() != declaration.param_types.len() {
+                analyzer.diagnostics.push(Diagnostic::error(
+                    format!("Conflicting declaration of function '{}'", name),
+                    declaration.span.clone(),
+                ).with_label(previous.span.clone(), format!(
+                    "previously declared here with {} parameters",
+                    previous.param_types.len(),
+                )));
+            } else if previous.param_types != declaration.param_types
+                || previous.return_type != declaration.return_type
+                || previous.is_variadic != declaration.is_variadic {
+                analyzer.diagnostics.push(Diagnostic::error(
+                    format!("Conflicting types for function '{}'", name),
+                    declaration.span.clone(),
+                ).with_label(previous.span.clone(), "previously declared here"));
+            } else if previous.is_defined && declaration.is_defined {
+                analyzer.diagnostics.push(Diagnostic::error(
+                    format!("Function '{}' has already been defined", name),
+                    declaration.span.clone(),
+                ).with_label(previous.span.clone(), "previously defined here"));
+            } else if declaration.is_static && !previous.is_static {
+                analyzer.diagnostics.push(Diagnostic::error(
+                    format!("Static declaration of '{}' follows non-static declaration", name),
+                    declaration.span.clone(),
+                ).with_label(previous.span.clone(), "previously declared here"));
+            }
+            declaration.is_defined = declaration.is_defined || previous.is_defined;
+            declaration.is_static = declaration.is_static || previous.is_static;
+        },
+        None => (),
+    }
+
+    let is_static = declaration.is_static;
+    analyzer.functions.insert(name.to_string(), declaration);
+    return is_static;
+}
+
+// Parameters passed in registers are copied into the frame like local
+// variables, into slots from `Analyzer::allocate`. The rest were pushed by
+// the caller, and sit above the saved frame pointer and return address.
+fn declare_parameter(analyzer: &mut Analyzer, param: Parameter, storage: Storage) -> Option<SymbolId> {
+    let mut var: Var;
+    match param.name {
+        Some(name) => var = Var { name: name, span: param.span, symbol: None },
+        None => {
+            analyzer.report(Diagnostic::error("Parameter name omitted", param.span));
+            return None;
+        },
+    }
+
+    return Some(analyzer.declare_local(&mut var, &param.ty, storage, "Parameter"));
+}
@@ -0,0 +1,116 @@
+use sema::{Analyzer, GlobalDeclaration, StaticVariable, Initializer, Storage, Symbol};
+use parser::StorageClass;
+use sema::initializer;
+use parser::global::GlobalVariable;
+use diagnostic::Diagnostic;
+
+// Add a declaration to the file scope, merging it into an earlier
+// declaration of the same variable. Declarations that don't agree are
+// reported.
+pub fn declare(analyzer: &mut Analyzer, mut global: GlobalVariable) {
+    let name = global.var.name.clone();
+    let span = global.var.span.clone();
+    global.ty = analyzer.array_type(&global.ty, &mut global.lengths);
+    if global.is_unsized {
+        global.ty = initializer::unsized_array_type(analyzer, &global.ty, global.initializer.as_ref(), &global.var);
+    }
+
+    let mut initializer: Option<Initializer> = None;
+    match global.initializer {
+        Some(ref mut global_initializer) => {
+            initializer::analyze_expressions(analyzer, global_initializer);
+            let mut data = Vec::new();
+            initializer::static_data(analyzer, global_initializer, &global.ty, &mut data);
+            initializer = Some(Initializer { data: data, span: global_initializer.span().clone() });
+        },
+        None => (),
+    }
+
+    // An `extern` declaration with an initialiser defines the variable
+    let mut storage_class = global.storage_class;
+    if storage_class == Some(StorageClass::Extern) && initializer.is_some() {
+        storage_class = None;
+    }
+
+    match analyzer.functions.get(&name) {
+        Some(function) => analyzer.diagnostics.push(Diagnostic::error(
+            format!("'{}' redeclared as a different kind of symbol", name),
+            span.clone(),
+        ).with_label(function.span.clone(), "previously declared as a function here")),
+        None => (),
+    }
+
+    let previous_declaration: GlobalDeclaration;
+    match analyzer.globals.get(&name) {
+        Some(declaration) => previous_declaration = declaration.clone(),
+        None => {
+            let symbol = analyzer.symbols.add(Symbol {
+                name: name.clone(),
+                span: span.clone(),
+                ty: global.ty.clone(),
+                storage: Storage::Static(name.clone()),
+            });
+            analyzer.globals.insert(name.clone(), GlobalDeclaration {
+                span: span,
+                symbol: symbol,
+                index: analyzer.variables.len(),
+            });
+            analyzer.variables.push(StaticVariable {
+                name: name,
+                ty: global.ty,
+                storage_class: storage_class,
+                initializer: initializer,
+            });
+            return;
+        },
+    }
+
+    let previous = &mut analyzer.variables[previous_declaration.index];
+
+    if previous.ty != global.ty && !previous.ty.is_error() && !global.ty.is_error() {
+        analyzer.diagnostics.push(Diagnostic::error(
+            format!("Conflicting types for '{}'", name),
+            span.clone(),
+        ).with_label(previous_declaration.span.clone(), format!("previously declared here as '{}'", previous.ty)));
+    }
+
+    match (previous.storage_class, storage_class) {
+        (Some(StorageClass::Static), Some(StorageClass::Static)) => (),
+        (_, Some(StorageClass::Static)) => analyzer.diagnostics.push(Diagnostic::error(
+            format!("Static declaration of '{}' follows non-static declaration", name),
+            span.clone(),
+        ).with_label(previous_declaration.span.clone(), "previously declared here")),
+        (Some(StorageClass::Static), None) => analyzer.diagnostics.push(Diagnostic::error(
+            format!("Non-static declaration of '{}' follows static declaration", name),
+            span.clone(),
+        ).with_label(previous_declaration.span.clone(), "previously declared here")),
+        // A definition of something only declared `extern` until now
+        (Some(StorageClass::Extern), None) => previous.storage_class = None,
+        _ => (),
+    }
+
+    match (&previous.initializer, initializer) {
+        (Some(previous_initializer), Some(_)) => analyzer.diagnostics.push(Diagnostic::error(
+            format!("Redefinition of '{}'", name),
+            span,
+        ).with_label(previous_initializer.span.clone(), "previously initialized here")),
+        (None, Some(initializer)) => previous.initializer = Some(initializer),
+        _ => (),
+    }
+}
+
+// A variable defined at file scope can have a type that's completed later
+// in the file, like a struct defined after it, but not one that never is
+pub fn check_sizes(analyzer: &mut Analyzer) {
+    let mut diagnostics = Vec::new();
+    for variable in &analyzer.variables {
+        if variable.storage_class == Some(StorageClass::Extern) || variable.ty.is_complete() {
+            continue;
+        }
+        diagnostics.push(Diagnostic::error(
+            format!("Storage size of '{}' isn't known", variable.name),
+            analyzer.globals[&variable.name].span.clone(),
+        ).with_note(format!("it has incomplete type '{}'", variable.ty)));
+    }
+    analyzer.diagnostics.extend(diagnostics);
+}
@@ -0,0 +1,298 @@
+use sema::Analyzer;
+use sema::expression;
+use parser::constant;
+use parser::constant::Value;
+use parser::initializer::Initializer;
+use parser::expression::{Expression, Var};
+use span::Span;
+use diagnostic::Diagnostic;
+use types::{Type, Member};
+use asm::Data;
+
+const ARRAY_LIST_NOTE: &str = "an array has to be initialized with a list in braces, like `{1, 2, 3}`";
+
+// Check a local variable's initialiser against its type. Arrays take a list
+// in braces, one element per item, and a scalar can be in braces on its own.
+// An array of characters can be initialised with a string literal instead.
+// Structs take either a list with an item per member, in order, or another
+// struct of the same type.
+pub fn analyze(analyzer: &mut Analyzer, initializer: &mut Initializer, ty: &Type) {
+    // The variable's type has already been reported
+    if ty.is_error() {
+        analyze_expressions(analyzer, initializer);
+        return;
+    }
+    if check_string(analyzer, initializer, ty) {
+        return;
+    }
+
+    match (ty, initializer) {
+        (Type::Array(element, length), Initializer::List(elements, _)) => {
+            check_length(analyzer, elements, *length);
+            for initializer in elements.iter_mut() {
+                analyze(analyzer, initializer, element);
+            }
+        },
+        (Type::Array(_, _), Initializer::Expression(expression)) => {
+            analyzer.report(array_without_list(ty, &expression.span));
+        },
+        (Type::Struct(_), Initializer::List(elements, _)) => {
+            let members = initialized_members(ty);
+            check_members(analyzer, elements, ty, members.len());
+            for (initializer, member) in elements.iter_mut().zip(members.iter()) {
+                analyze(analyzer, initializer, &member.ty);
+            }
+        },
+        (_, Initializer::List(elements, span)) => {
+            if check_scalar_list(analyzer, elements, span) {
+                analyze(analyzer, &mut elements[0], ty);
+            }
+        },
+        (_, Initializer::Expression(expression)) => {
+            let value_type = expression::analyze(analyzer, expression);
+            if !expression::is_convertible(expression, ty) {
+                analyzer.report(Diagnostic::error(
+                    format!("Incompatible types when initializing type '{}' using type '{}'", ty, value_type),
+                    expression.span.clone(),
+                ));
+            }
+            expression::convert(analyzer, expression, ty);
+        },
+    }
+}
+
+// The type of a variable declared with `[]`, an array with as many
+// `element`s as its initialiser gives. A string literal gives one for each
+// character, along with the terminating zero.
+pub fn unsized_array_type(
+    analyzer: &mut Analyzer,
+    element: &Type,
+    initializer: Option<&Initializer>,
+    var: &Var,
+) -> Type {
+    if element.is_error() {
+        return Type::Error;
+    }
+
+    let length: i64;
+    match initializer {
+        Some(initializer) => match (initializer.string_literal(), initializer) {
+            (Some(bytes), _) if element.is_integer() && element.size() == 1 => length = bytes.len() as i64 + 1,
+            (_, Initializer::List(elements, _)) => length = elements.len() as i64,
+            (_, Initializer::Expression(expression)) => {
+                analyzer.report(Diagnostic::error(
+                    format!("Invalid initializer for array '{}'", var.name),
+                    expression.span.clone(),
+                ).with_note(ARRAY_LIST_NOTE));
+                return Type::Error;
+            },
+        },
+        None => {
+            analyzer.report(Diagnostic::error(format!("Size of array '{}' is missing", var.name), var.span.clone())
+                .with_note("it can only be left out when there's an initialiser to count"));
+            return Type::Error;
+        },
+    }
+
+    if length == 0 {
+        analyzer.report(Diagnostic::error("Size of array has to be greater than zero", var.span.clone()));
+        return Type::Error;
+    }
+    if element.size().checked_mul(length).is_none() {
+        analyzer.report(Diagnostic::error("Size of array is too large", var.span.clone())
+            .with_note(format!("it would be {} elements of {} bytes", length, element.size())));
+        return Type::Error;
+    }
+    return Type::Array(Box::new(element.clone()), length);
+}
+
+// Type check every expression in the initialiser, without checking them
+// against the type being initialised. A global's have to be checked before
+// their values are worked out, as `sizeof` an expression needs its type.
+pub fn analyze_expressions(analyzer: &mut Analyzer, initializer: &mut Initializer) {
+    match initializer {
+        Initializer::Expression(expression) => {
+            expression::analyze(analyzer, expression);
+        },
+        Initializer::List(elements, _) => for element in elements.iter_mut() {
+            analyze_expressions(analyzer, element);
+        },
+    }
+}
+
+// The data a global starts out with. Every expression in its initialiser
+// has to be constant. Those are all arithmetic, so a pointer can only be
+// initialised with a null pointer, or to point at a string literal.
+pub fn static_data(analyzer: &mut Analyzer, initializer: &Initializer, ty: &Type, data: &mut Vec<Data>) {
+    match initializer.string_literal() {
+        Some(bytes) if check_string(analyzer, initializer, ty) => {
+            data.push(Data::String(bytes.to_vec()));
+            push_zero(data, ty.size() - bytes.len() as i64);
+            return;
+        },
+        Some(bytes) if *ty == Type::Pointer(Box::new(Type::Char)) => {
+            data.push(Data::StringAddress(bytes.to_vec()));
+            return;
+        },
+        _ => (),
+    }
+
+    match (ty, initializer) {
+        // The variable's type has already been reported
+        (Type::Error, _) => (),
+        (Type::Array(element, length), Initializer::List(elements, _)) => {
+            check_length(analyzer, elements, *length);
+            for initializer in elements.iter().take(*length as usize) {
+                static_data(analyzer, initializer, element, data);
+            }
+            let missing = (*length - elements.len() as i64).max(0);
+            push_zero(data, missing * element.size());
+        },
+        (Type::Array(_, _), Initializer::Expression(expression)) => {
+            analyzer.report(array_without_list(ty, &expression.span));
+        },
+        // Padding between the members, and any left uninitialised at the
+        // end, are zero
+        (Type::Struct(_), Initializer::List(elements, _)) => {
+            let members = initialized_members(ty);
+            check_members(analyzer, elements, ty, members.len());
+            let mut offset = 0;
+            for (initializer, member) in elements.iter().zip(members.iter()) {
+                push_zero(data, member.offset - offset);
+                static_data(analyzer, initializer, &member.ty, data);
+                offset = member.offset + member.ty.size();
+            }
+            push_zero(data, ty.size() - offset);
+        },
+        // Another struct's value can't be worked out at compile time
+        (Type::Struct(_), Initializer::Expression(expression)) => not_constant(analyzer, initializer, expression, ty),
+        (_, Initializer::List(elements, span)) => {
+            if check_scalar_list(analyzer, elements, span) {
+                static_data(analyzer, &elements[0], ty, data);
+            }
+        },
+        (_, Initializer::Expression(expression)) if ty.is_pointer() => match constant::evaluate(expression) {
+            Some(0) => data.push(Data::Quad(0)),
+            Some(_) => analyzer.report(Diagnostic::error(
+                format!("Incompatible types when initializing type '{}' using type 'int'", ty),
+                expression.span.clone(),
+            )),
+            None => not_constant(analyzer, initializer, expression, ty),
+        },
+        // Converted to the type, as if it had been stored in it. Floating
+        // point values are stored as their bits.
+        (_, Initializer::Expression(expression)) => {
+            expression::check_overflow(analyzer, expression, ty);
+            match constant::evaluate_as(expression, ty) {
+                Some(Value::Floating(value)) if *ty == Type::Float => {
+                    data.push(Data::Long(i64::from((value as f32).to_bits())));
+                },
+                Some(Value::Floating(value)) => data.push(Data::Quad(value.to_bits() as i64)),
+                Some(Value::Integer(value)) => match ty.size() {
+                    1 => data.push(Data::Byte(value)),
+                    2 => data.push(Data::Short(value)),
+                    4 => data.push(Data::Long(value)),
+                    _ => data.push(Data::Quad(value)),
+                },
+                None => not_constant(analyzer, initializer, expression, ty),
+            }
+        },
+    }
+}
+
+// Report an initialiser that can't be worked out at compile time, which
+// could be a string literal for something that isn't a `char *`, unless
+// it's already been reported as invalid
+fn not_constant(analyzer: &mut Analyzer, initializer: &Initializer, expression: &Expression, ty: &Type) {
+    if expression.ty().is_error() {
+        return;
+    }
+    match initializer.string_literal() {
+        Some(_) => analyzer.report(Diagnostic::error(
+            format!("Incompatible types when initializing type '{}' using type 'char *'", ty),
+            expression.span.clone(),
+        )),
+        None => analyzer.report(Diagnostic::error(
+            "Initializer element is not a constant expression",
+            expression.span.clone(),
+        )),
+    }
+}
+
+// Whether it's a string literal initialising an array of characters. The
+// string has to fit, though it can leave off its terminating zero.
+fn check_string(analyzer: &mut Analyzer, initializer: &Initializer, ty: &Type) -> bool {
+    let bytes: &[u8];
+    match (initializer.string_literal(), ty) {
+        (Some(string_bytes), Type::Array(element, _)) if element.is_integer() && element.size() == 1 => {
+            bytes = string_bytes;
+        },
+        _ => return false,
+    }
+
+    if bytes.len() as i64 > ty.size() {
+        analyzer.report(Diagnostic::error(
+            format!("Initializer string for array of type '{}' is too long", ty),
+            initializer.span().clone(),
+        ).with_note(format!("the string has {} characters", bytes.len())));
+    }
+    return true;
+}
+
+// Runs of zeros are merged, so an array that's mostly zero fill is a single
+// `.zero`
+fn push_zero(data: &mut Vec<Data>, size: i64) {
+    if size <= 0 {
+        return;
+    }
+    match data.last_mut() {
+        Some(Data::Zero(previous_size)) => *previous_size += size,
+        _ => data.push(Data::Zero(size)),
+    }
+}
+
+fn check_length(analyzer: &mut Analyzer, elements: &[Initializer], length: i64) {
+    if elements.len() as i64 > length {
+        analyzer.report(Diagnostic::error(
+            "Excess elements in array initializer",
+            elements[length as usize].span().clone(),
+        ).with_note(format!("the array only has {} elements", length)));
+    }
+}
+
+// The members a list initialises, in order. Only a union's first member is
+// initialised.
+pub fn initialized_members(ty: &Type) -> Vec<Member> {
+    let struct_type = ty.struct_type().expect("Only structs have members");
+    let mut members = struct_type.members();
+    if struct_type.is_union {
+        members.truncate(1);
+    }
+    return members;
+}
+
+fn check_members(analyzer: &mut Analyzer, elements: &[Initializer], ty: &Type, count: usize) {
+    if elements.len() > count {
+        analyzer.report(Diagnostic::error(
+            format!("Excess elements in '{}' initializer", ty),
+            elements[count].span().clone(),
+        ).with_note(format!("there are {} members to initialize", count)));
+    }
+}
+
+// Whether there's exactly one element in braces around a scalar
+fn check_scalar_list(analyzer: &mut Analyzer, elements: &[Initializer], span: &Span) -> bool {
+    match elements.len() {
+        0 => analyzer.report(Diagnostic::error("Empty scalar initializer", span.clone())),
+        1 => return true,
+        _ => analyzer.report(Diagnostic::error("Excess elements in scalar initializer", elements[1].span().clone())),
+    }
+    return false;
+}
+
+fn array_without_list(ty: &Type, span: &Span) -> Diagnostic {
+    return Diagnostic::error(
+        format!("Invalid initializer for type '{}'", ty),
+        span.clone(),
+    ).with_note(ARRAY_LIST_NOTE);
+}
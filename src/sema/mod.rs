@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use span::Span;
+use parser::{StorageClass, array_type};
+use parser::expression::{Expression, Var};
+use parser::statement::BlockItem;
+use diagnostic::Diagnostic;
+use types::Type;
+use asm::Data;
+
+pub mod program;
+pub mod function;
+pub mod global;
+pub mod initializer;
+pub mod statement;
+pub mod expression;
+pub mod factor;
+
+// The most a function's frame can take up. It's allocated with a 32-bit
+// immediate, after being rounded up to a multiple of 16.
+const MAX_FRAME_SIZE: i64 = i32::MAX as i64 / 16 * 16;
+
+// A variable, told apart from any others with the same name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolId(usize);
+
+#[derive(Debug)]
+pub struct Symbol {
+    #[allow(dead_code)]
+    pub name: String,
+    // Where it was declared
+    pub span: Span,
+    pub ty: Type,
+    pub storage: Storage,
+}
+
+// Where a variable lives while the program runs
+#[derive(Debug, Clone)]
+pub enum Storage {
+    // An offset from the frame pointer
+    Stack(i64),
+    // The C name of a variable in the data or bss section
+    Static(String),
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn add(&mut self, symbol: Symbol) -> SymbolId {
+        self.symbols.push(symbol);
+        return SymbolId(self.symbols.len() - 1);
+    }
+
+    pub fn get(&self, id: SymbolId) -> &Symbol {
+        return &self.symbols[id.0];
+    }
+}
+
+// A checked program, with every variable resolved to a symbol and given
+// storage
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<FunctionDefinition>,
+    pub variables: Vec<StaticVariable>,
+    pub symbols: SymbolTable,
+}
+
+#[derive(Debug)]
+pub struct FunctionDefinition {
+    pub name: String,
+    // Not visible outside of the file, from this or an earlier declaration
+    pub is_static: bool,
+    pub return_type: Type,
+    pub params: Vec<SymbolId>,
+    // Where the caller's address for a struct returned in memory is kept
+    // in the frame
+    pub return_address: Option<i64>,
+    pub body: Vec<BlockItem>,
+    // Bytes of stack for the locals, kept a multiple of 16 so the stack
+    // stays aligned for calls
+    pub frame_size: i64,
+}
+
+// A variable outside of any function, with all of its declarations merged
+#[derive(Debug)]
+pub struct StaticVariable {
+    pub name: String,
+    pub ty: Type,
+    // `Static` if it isn't visible outside of the file, `Extern` if it was
+    // only ever declared `extern`, so its storage is defined elsewhere
+    pub storage_class: Option<StorageClass>,
+    // Without one, a declaration that isn't `extern` is a tentative
+    // definition, and the variable starts out as zero
+    pub initializer: Option<Initializer>,
+}
+
+#[derive(Debug)]
+pub struct Initializer {
+    pub data: Vec<Data>,
+    pub span: Span,
+}
+
+// What a call needs to know about the function it calls
+#[derive(Debug, Clone)]
+pub struct FunctionDeclaration {
+    pub return_type: Type,
+    pub param_types: Vec<Type>,
+    // Whether it takes more arguments after those, as `printf` does
+    pub is_variadic: bool,
+    pub span: Span,
+    pub is_defined: bool,
+    pub is_static: bool,
+}
+
+// Where a global was first declared, and where it is in the program's list
+// of variables
+#[derive(Debug, Clone)]
+pub struct GlobalDeclaration {
+    pub span: Span,
+    pub symbol: SymbolId,
+    pub index: usize,
+}
+
+// Local variables declared in a block
+#[derive(Debug, Default)]
+pub struct Scope {
+    pub vars: HashMap<String, SymbolId>,
+    start_offset: i64,
+}
+
+// Everything declared so far, and where the analysis is in the function
+// being checked
+#[derive(Debug, Default)]
+pub struct Analyzer {
+    pub symbols: SymbolTable,
+    pub diagnostics: Vec<Diagnostic>,
+    pub functions: HashMap<String, FunctionDeclaration>,
+    pub globals: HashMap<String, GlobalDeclaration>,
+    pub variables: Vec<StaticVariable>,
+    // Innermost block last. Slots for a block's variables are handed back
+    // when it ends, so sibling blocks share the same part of the frame.
+    pub scopes: Vec<Scope>,
+    pub current_offset: i64,
+    pub frame_size: i64,
+    // How many loops the statement being checked is inside of
+    pub loop_depth: usize,
+    // What the function being checked returns
+    pub return_type: Option<Type>,
+}
+
+impl Analyzer {
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    // Start on a function's body, in the scope of its parameters
+    pub fn enter_function(&mut self, return_type: &Type) {
+        self.scopes.clear();
+        self.current_offset = 0;
+        self.frame_size = 0;
+        self.loop_depth = 0;
+        self.return_type = Some(return_type.clone());
+        self.enter_scope();
+    }
+
+    // Returns the size of the frame the function needs
+    pub fn exit_function(&mut self) -> i64 {
+        self.exit_scope();
+        return (self.frame_size + 15) / 16 * 16;
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.scopes.push(Scope { start_offset: self.current_offset, ..Default::default() });
+    }
+
+    pub fn exit_scope(&mut self) {
+        let scope = self.scopes.pop().expect("No scope to exit");
+        self.current_offset = scope.start_offset;
+    }
+
+    // The type of a variable declared with `[size]`s after its name. The
+    // sizes are type checked before they're worked out, so they can use
+    // `sizeof` on an expression, like `sizeof(a) / sizeof(a[0])`.
+    pub fn array_type(&mut self, element: &Type, lengths: &mut [Expression]) -> Type {
+        let mut has_error = false;
+        for length in lengths.iter_mut() {
+            has_error = expression::analyze(self, length).is_error() || has_error;
+        }
+        if has_error {
+            return Type::Error;
+        }
+        match array_type(element.clone(), lengths) {
+            Ok(ty) => return ty,
+            Err(diagnostic) => {
+                self.report(diagnostic);
+                return Type::Error;
+            },
+        }
+    }
+
+    // A slot in the frame for a new local variable. A struct's slot is
+    // rounded up to whole eightbytes, so it can be moved to and from
+    // registers 8 bytes at a time. A slot that would make the frame too big
+    // is reported, and isn't taken out of the frame.
+    pub fn allocate(&mut self, ty: &Type, span: &Span) -> Storage {
+        let size = if ty.is_struct() { ty.size().saturating_add(7) / 8 * 8 } else { ty.size() };
+        let offset = self.current_offset.checked_sub(size)
+            .map(|offset| offset - offset.rem_euclid(ty.alignment()))
+            .filter(|offset| -offset <= MAX_FRAME_SIZE);
+        match offset {
+            Some(offset) => self.current_offset = offset,
+            None => {
+                self.report(Diagnostic::error("Size of stack frame is too large", span.clone())
+                    .with_note(format!("locals can take up at most {} bytes", MAX_FRAME_SIZE)));
+                return Storage::Stack(self.current_offset);
+            },
+        }
+        self.frame_size = self.frame_size.max(-self.current_offset);
+        return Storage::Stack(self.current_offset);
+    }
+
+    // Add a local variable to the innermost scope, reporting it if the
+    // scope already has one with that name
+    pub fn declare_local(&mut self, var: &mut Var, ty: &Type, storage: Storage, kind: &str) -> SymbolId {
+        let previous = self.scopes.last()
+            .expect("Var declared outside of a scope")
+            .vars.get(&var.name)
+            .cloned();
+        match previous {
+            Some(previous) => {
+                let previous_span = self.symbols.get(previous).span.clone();
+                self.report(Diagnostic::error(
+                    format!("{} '{}' has already been declared", kind, var.name),
+                    var.span.clone(),
+                ).with_label(previous_span, "previously declared here"));
+            },
+            None => (),
+        }
+
+        let symbol = self.symbols.add(Symbol {
+            name: var.name.clone(),
+            span: var.span.clone(),
+            ty: ty.clone(),
+            storage: storage,
+        });
+        self.scopes.last_mut().expect("Var declared outside of a scope").vars.insert(var.name.clone(), symbol);
+        var.symbol = Some(symbol);
+        return symbol;
+    }
+
+    // The innermost variable called `name`, whether local or global
+    pub fn lookup(&self, name: &str) -> Option<SymbolId> {
+        let local = self.scopes.iter().rev()
+            .filter_map(|scope| scope.vars.get(name))
+            .next()
+            .cloned();
+        return local.or_else(|| self.globals.get(name).map(|global| global.symbol));
+    }
+
+    // Point a use of a variable at its declaration, returning its type
+    pub fn resolve(&mut self, var: &mut Var) -> Type {
+        match self.lookup(&var.name) {
+            Some(symbol) => {
+                var.symbol = Some(symbol);
+                return self.symbols.get(symbol).ty.clone();
+            },
+            None => (),
+        }
+
+        let diagnostic: Diagnostic;
+        match self.functions.get(&var.name) {
+            Some(function) => diagnostic = Diagnostic::error(
+                format!("'{}' is a function, not a variable", var.name),
+                var.span.clone(),
+            ).with_label(function.span.clone(), "declared here"),
+            None => diagnostic = Diagnostic::error(
+                format!("Variable '{}' has not been declared", var.name),
+                var.span.clone(),
+            ),
+        }
+        self.report(diagnostic);
+        return Type::Error;
+    }
+}
@@ -0,0 +1,36 @@
+use sema::function;
+use sema::global;
+use sema::{Analyzer, Program, FunctionDefinition};
+use parser::program::{Program as ParsedProgram, Declaration};
+use diagnostic::Diagnostic;
+
+// Declarations are checked in the order they appear, so a function or
+// global can only be used after it's been declared. Everything that's wrong
+// comes back together, and any warnings come back with the program if
+// nothing is.
+pub fn analyze(program: ParsedProgram) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let mut analyzer: Analyzer = Default::default();
+    let mut functions: Vec<FunctionDefinition> = Vec::new();
+
+    for declaration in program.declarations {
+        match declaration {
+            Declaration::Function(function) => match function::analyze(&mut analyzer, function) {
+                Some(definition) => functions.push(definition),
+                None => (),
+            },
+            Declaration::Variable(global) => global::declare(&mut analyzer, global),
+        }
+    }
+    global::check_sizes(&mut analyzer);
+
+    if analyzer.diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(analyzer.diagnostics);
+    }
+
+    let program = Program {
+        functions: functions,
+        variables: analyzer.variables,
+        symbols: analyzer.symbols,
+    };
+    return Ok((program, analyzer.diagnostics));
+}
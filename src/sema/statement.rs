@@ -0,0 +1,123 @@
+use sema::Analyzer;
+use sema::expression;
+use sema::initializer;
+use parser::statement::{Statement, StatementKind, BlockItem, ForInit, VariableDeclaration};
+use diagnostic::Diagnostic;
+
+pub fn analyze(analyzer: &mut Analyzer, statement: &mut Statement) {
+    match statement.kind {
+        StatementKind::Return(ref mut expression) => {
+            let ty = expression::analyze(analyzer, expression);
+            let return_type = analyzer.return_type.clone().expect("Return outside of a function");
+            if !expression::is_convertible(expression, &return_type) {
+                analyzer.report(Diagnostic::error(
+                    format!("Incompatible types when returning type '{}' but '{}' was expected", ty, return_type),
+                    expression.span.clone(),
+                ));
+            }
+            expression::convert(analyzer, expression, &return_type);
+        },
+        StatementKind::If(ref mut if_statement) => {
+            expression::analyze_condition(analyzer, &mut if_statement.condition);
+            analyze(analyzer, &mut if_statement.statement);
+            match if_statement.else_statement {
+                Some(ref mut else_statement) => analyze(analyzer, else_statement),
+                None => (),
+            }
+        },
+        StatementKind::Compound(ref mut block_items) => {
+            analyzer.enter_scope();
+            analyze_block_items(analyzer, block_items);
+            analyzer.exit_scope();
+        },
+        StatementKind::Expression(ref mut expression) => {
+            expression::analyze(analyzer, expression);
+        },
+        StatementKind::While(ref mut while_statement) | StatementKind::DoWhile(ref mut while_statement) => {
+            expression::analyze_condition(analyzer, &mut while_statement.condition);
+            analyze_loop_body(analyzer, &mut while_statement.statement);
+        },
+        StatementKind::For(ref mut for_statement) => {
+            // The init clause's declaration lives until the loop ends
+            analyzer.enter_scope();
+            match for_statement.init {
+                Some(ForInit::Declaration(ref mut declaration)) => analyze_declaration(analyzer, declaration),
+                Some(ForInit::Expression(ref mut expression)) => {
+                    expression::analyze(analyzer, expression);
+                },
+                None => (),
+            }
+            match for_statement.condition {
+                Some(ref mut condition) => expression::analyze_condition(analyzer, condition),
+                None => (),
+            }
+            match for_statement.post {
+                Some(ref mut post) => {
+                    expression::analyze(analyzer, post);
+                },
+                None => (),
+            }
+            analyze_loop_body(analyzer, &mut for_statement.statement);
+            analyzer.exit_scope();
+        },
+        StatementKind::Break => {
+            check_in_loop(analyzer, statement, "'break'");
+        },
+        StatementKind::Continue => {
+            check_in_loop(analyzer, statement, "'continue'");
+        },
+    }
+}
+
+pub fn analyze_block_items(analyzer: &mut Analyzer, block_items: &mut [BlockItem]) {
+    for block_item in block_items {
+        match *block_item {
+            BlockItem::Statement(ref mut statement) => analyze(analyzer, statement),
+            BlockItem::Declaration(ref mut declaration) => analyze_declaration(analyzer, declaration),
+        }
+    }
+}
+
+// The variable is in scope in its own initialiser, as in C. Its type has to
+// be complete by the time it's declared, so its space can be allocated.
+fn analyze_declaration(analyzer: &mut Analyzer, declaration: &mut VariableDeclaration) {
+    declaration.ty = analyzer.array_type(&declaration.ty, &mut declaration.lengths);
+    if declaration.is_unsized {
+        declaration.ty = initializer::unsized_array_type(
+            analyzer,
+            &declaration.ty,
+            declaration.initializer.as_ref(),
+            &declaration.var,
+        );
+    }
+    let storage = analyzer.allocate(&declaration.ty, &declaration.var.span);
+    analyzer.declare_local(&mut declaration.var, &declaration.ty, storage, "Variable");
+    if !declaration.ty.is_complete() {
+        analyzer.report(Diagnostic::error(
+            format!("Storage size of '{}' isn't known", declaration.var.name),
+            declaration.var.span.clone(),
+        ).with_note(format!("it has incomplete type '{}'", declaration.ty)));
+        return;
+    }
+    match declaration.initializer {
+        Some(ref mut declaration_initializer) => {
+            initializer::analyze(analyzer, declaration_initializer, &declaration.ty);
+        },
+        None => (),
+    }
+}
+
+fn analyze_loop_body(analyzer: &mut Analyzer, statement: &mut Statement) {
+    analyzer.loop_depth += 1;
+    analyze(analyzer, statement);
+    analyzer.loop_depth -= 1;
+}
+
+fn check_in_loop(analyzer: &mut Analyzer, statement: &Statement, keyword: &str) {
+    if analyzer.loop_depth == 0 {
+        analyzer.report(Diagnostic::error(
+            format!("{} statement not within a loop", keyword),
+            statement.span.clone(),
+        ));
+    }
+}
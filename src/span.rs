@@ -0,0 +1,37 @@
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Clone, PartialEq)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    // Span covering everything from the start of `self` to the end of `end`
+    pub fn to(&self, end: &Span) -> Span {
+        let end_offset = end.offset + end.length;
+        return Span {
+            file: self.file.clone(),
+            offset: self.offset,
+            line: self.line,
+            column: self.column,
+            length: end_offset.saturating_sub(self.offset),
+        };
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}+{}", self.file, self.line, self.column, self.length)
+    }
+}
@@ -0,0 +1,64 @@
+// The platform the generated assembly is for. Mach-O and ELF differ in how
+// symbols are named, which sections exist, and the extra annotations the
+// assembler and linker expect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    LinuxGnu,
+    AppleDarwin,
+}
+
+pub const TARGETS: [Target; 2] = [Target::LinuxGnu, Target::AppleDarwin];
+
+impl Target {
+    pub fn host() -> Target {
+        if cfg!(target_os = "macos") {
+            return Target::AppleDarwin;
+        }
+        return Target::LinuxGnu;
+    }
+
+    pub fn from_triple(triple: &str) -> Option<Target> {
+        return TARGETS.iter().find(|target| target.triple() == triple).cloned();
+    }
+
+    pub fn triple(&self) -> &'static str {
+        match self {
+            Target::LinuxGnu => return "x86_64-linux-gnu",
+            Target::AppleDarwin => return "x86_64-apple-darwin",
+        }
+    }
+
+    // The assembly name of a C function or variable
+    pub fn symbol(&self, name: &str) -> String {
+        match self {
+            Target::LinuxGnu => return name.to_string(),
+            Target::AppleDarwin => return format!("_{}", name),
+        }
+    }
+
+    // A label that stays out of the object file's symbol table
+    pub fn local_label(&self, name: &str) -> String {
+        match self {
+            Target::LinuxGnu => return format!(".L{}", name),
+            Target::AppleDarwin => return format!("L{}", name),
+        }
+    }
+
+    // How to call a function, which may be in a shared library
+    pub fn call_target(&self, name: &str) -> String {
+        match self {
+            Target::LinuxGnu => return format!("{}@PLT", name),
+            Target::AppleDarwin => return self.symbol(name),
+        }
+    }
+
+    pub fn is_elf(&self) -> bool {
+        return *self == Target::LinuxGnu;
+    }
+}
+
+impl Default for Target {
+    fn default() -> Target {
+        return Target::host();
+    }
+}
@@ -0,0 +1,413 @@
+use std::fmt;
+use std::ptr;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    // Plain `char` is signed, as on x86-64 Linux and macOS, but is still a
+    // different type to `signed char`
+    Char,
+    SignedChar,
+    UnsignedChar,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    // `long` and `long long` are both 8 bytes, but are still different
+    // types
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    Float,
+    Double,
+    Pointer(Box<Type>),
+    // The element type and the number of elements
+    Array(Box<Type>, i64),
+    // A struct or union, shared with everywhere else its tag is used
+    Struct(Rc<StructType>),
+    // The type of an expression that's already been reported as invalid.
+    // Everything accepts it without reporting anything else, and it never
+    // reaches the generator.
+    Error,
+}
+
+// A struct or union's members and layout. It's incomplete, with no members
+// and no size, until its definition has been seen, which might not be
+// until after pointers to it have been declared, so the definition is
+// filled in later. Two types are only the same if they're the same
+// declaration, even if their members all match.
+pub struct StructType {
+    pub tag: Option<String>,
+    pub is_union: bool,
+    layout: RefCell<Option<Layout>>,
+}
+
+struct Layout {
+    members: Vec<Member>,
+    size: i64,
+    alignment: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    // Bytes from the start of the struct
+    pub offset: i64,
+}
+
+impl StructType {
+    pub fn new(tag: Option<String>, is_union: bool) -> Rc<StructType> {
+        return Rc::new(StructType { tag: tag, is_union: is_union, layout: RefCell::new(None) });
+    }
+
+    pub fn is_complete(&self) -> bool {
+        return self.layout.borrow().is_some();
+    }
+
+    // Complete it with its members, in the order they were declared. Each
+    // member of a struct is put at the next offset that's a multiple of its
+    // alignment, while every member of a union starts at 0. The size is
+    // padded out to a multiple of the strictest alignment, so the members
+    // of every element of an array are aligned too. Returns whether the
+    // size fits in an `i64`. If it doesn't, it's capped, so the struct can
+    // still be used while the rest of the program is checked.
+    pub fn define(&self, members: Vec<(String, Type)>) -> bool {
+        let mut laid_out: Vec<Member> = Vec::new();
+        let mut size: i64 = 0;
+        let mut alignment = 1;
+        let mut fits = true;
+        for (name, ty) in members {
+            let offset = if self.is_union { Some(0) } else { align(size, ty.alignment()) };
+            let end = offset.and_then(|offset| offset.checked_add(ty.size()));
+            fits = fits && end.is_some();
+            size = size.max(end.unwrap_or(i64::MAX));
+            alignment = alignment.max(ty.alignment());
+            laid_out.push(Member { name: name, ty: ty, offset: offset.unwrap_or(i64::MAX) });
+        }
+
+        let padded_size = align(size, alignment);
+        *self.layout.borrow_mut() = Some(Layout {
+            members: laid_out,
+            size: padded_size.unwrap_or(size),
+            alignment: alignment,
+        });
+        return fits && padded_size.is_some();
+    }
+
+    // Make it incomplete again, for when the parser backtracks past its
+    // definition
+    pub fn undefine(&self) {
+        *self.layout.borrow_mut() = None;
+    }
+
+    // Empty while it's incomplete
+    pub fn members(&self) -> Vec<Member> {
+        match *self.layout.borrow() {
+            Some(ref layout) => return layout.members.clone(),
+            None => return Vec::new(),
+        }
+    }
+
+    pub fn member(&self, name: &str) -> Option<Member> {
+        return self.members().into_iter().find(|member| member.name == name);
+    }
+
+    fn size(&self) -> i64 {
+        return self.layout.borrow().as_ref().map_or(0, |layout| layout.size);
+    }
+
+    fn alignment(&self) -> i64 {
+        return self.layout.borrow().as_ref().map_or(1, |layout| layout.alignment);
+    }
+
+    // As it's written in C, e.g. `struct point` or `union <anonymous>`
+    fn name(&self) -> String {
+        let keyword = if self.is_union { "union" } else { "struct" };
+        match self.tag {
+            Some(ref tag) => return format!("{} {}", keyword, tag),
+            None => return format!("{} <anonymous>", keyword),
+        }
+    }
+}
+
+impl PartialEq for StructType {
+    fn eq(&self, other: &StructType) -> bool {
+        return ptr::eq(self, other);
+    }
+}
+
+// Without the members, which can refer back to the struct itself
+impl fmt::Debug for StructType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// The offset rounded up to a multiple of the alignment, if that fits in an
+// `i64`
+fn align(offset: i64, alignment: i64) -> Option<i64> {
+    return Some(offset.checked_add(alignment - 1)? / alignment * alignment);
+}
+
+impl Type {
+    // Bytes a value of the type takes up
+    pub fn size(&self) -> i64 {
+        match self {
+            Type::Char | Type::SignedChar | Type::UnsignedChar => return 1,
+            Type::Short | Type::UnsignedShort => return 2,
+            Type::Int | Type::UnsignedInt => return 4,
+            Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong => return 8,
+            Type::Float => return 4,
+            Type::Double => return 8,
+            Type::Pointer(_) => return 8,
+            Type::Array(element, length) => return element.size() * length,
+            Type::Struct(struct_type) => return struct_type.size(),
+            Type::Error => return 1,
+        }
+    }
+
+    // What its address has to be a multiple of. An array is aligned like
+    // its elements, and a struct like its most strictly aligned member.
+    pub fn alignment(&self) -> i64 {
+        match self {
+            Type::Array(element, _) => return element.alignment(),
+            Type::Struct(struct_type) => return struct_type.alignment(),
+            _ => return self.size(),
+        }
+    }
+
+    // Whether its size is known, which it isn't for a struct that's only
+    // been declared, or an array of them
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Type::Array(element, _) => return element.is_complete(),
+            Type::Struct(struct_type) => return struct_type.is_complete(),
+            _ => return true,
+        }
+    }
+
+    // Integers of every size and signedness
+    pub fn is_integer(&self) -> bool {
+        return self.rank() > 0;
+    }
+
+    pub fn is_floating(&self) -> bool {
+        return *self == Type::Float || *self == Type::Double;
+    }
+
+    // Integers and floating point, which convert to each other implicitly
+    pub fn is_arithmetic(&self) -> bool {
+        return self.is_integer() || self.is_floating();
+    }
+
+    // Whether it's widened by sign rather than zero extension, and compared
+    // and divided as signed. Pointers are unsigned, like addresses.
+    pub fn is_signed(&self) -> bool {
+        match self {
+            Type::Char | Type::SignedChar | Type::Short | Type::Int | Type::Long | Type::LongLong => return true,
+            _ => return false,
+        }
+    }
+
+    // How integer types are ordered by the conversions, with a type and its
+    // unsigned version ranked the same. Anything that isn't an integer is 0.
+    fn rank(&self) -> i32 {
+        match self {
+            Type::Char | Type::SignedChar | Type::UnsignedChar => return 1,
+            Type::Short | Type::UnsignedShort => return 2,
+            Type::Int | Type::UnsignedInt => return 3,
+            Type::Long | Type::UnsignedLong => return 4,
+            Type::LongLong | Type::UnsignedLongLong => return 5,
+            _ => return 0,
+        }
+    }
+
+    // The integer type with the same rank that's unsigned
+    pub fn to_unsigned(&self) -> Type {
+        match self {
+            Type::Char | Type::SignedChar => return Type::UnsignedChar,
+            Type::Short => return Type::UnsignedShort,
+            Type::Int => return Type::UnsignedInt,
+            Type::Long => return Type::UnsignedLong,
+            Type::LongLong => return Type::UnsignedLongLong,
+            _ => return self.clone(),
+        }
+    }
+
+    // The integer promotions: anything ranked below `int` is promoted to
+    // one before arithmetic, as all of its values fit
+    pub fn promote(&self) -> Type {
+        if self.is_integer() && self.rank() < Type::Int.rank() {
+            return Type::Int;
+        }
+        return self.clone();
+    }
+
+    // The usual arithmetic conversions: the type both operands of a binary
+    // operator are converted to. A `double` on either side wins, then a
+    // `float`. Otherwise both are integers, and after promotion the higher
+    // ranked type wins, unless one is signed and the other isn't. Then the
+    // unsigned one wins if it's ranked at least as high, the signed one if
+    // it can hold all of the unsigned one's values, and otherwise the
+    // unsigned version of the signed one.
+    pub fn common_type(&self, other: &Type) -> Type {
+        if *self == Type::Double || *other == Type::Double {
+            return Type::Double;
+        }
+        if *self == Type::Float || *other == Type::Float {
+            return Type::Float;
+        }
+
+        let left = self.promote();
+        let right = other.promote();
+        if left == right {
+            return left;
+        }
+        if left.is_signed() == right.is_signed() {
+            if left.rank() > right.rank() {
+                return left;
+            }
+            return right;
+        }
+
+        let (signed, unsigned) = if left.is_signed() { (left, right) } else { (right, left) };
+        if unsigned.rank() >= signed.rank() {
+            return unsigned;
+        }
+        if signed.size() > unsigned.size() {
+            return signed;
+        }
+        return signed.to_unsigned();
+    }
+
+    // The largest value an integer type can hold
+    pub fn max_value(&self) -> u64 {
+        let bits = 8 * self.size() as u32;
+        if self.is_signed() {
+            return (1 << (bits - 1)) - 1;
+        }
+        return u64::MAX >> (64 - bits);
+    }
+
+    // The value converted to the type: cut down to its size, then sign or
+    // zero extended back to 64 bits. An 8 byte unsigned value over
+    // `i64::MAX` comes out negative, with the same bits.
+    pub fn wrap(&self, value: i64) -> i64 {
+        match (self.size(), self.is_signed()) {
+            (1, true) => return i64::from(value as i8),
+            (1, false) => return i64::from(value as u8),
+            (2, true) => return i64::from(value as i16),
+            (2, false) => return i64::from(value as u16),
+            (4, true) => return i64::from(value as i32),
+            (4, false) => return i64::from(value as u32),
+            _ => return value,
+        }
+    }
+
+    // Arithmetic types and pointers, which can be compared with zero and
+    // used as conditions
+    pub fn is_scalar(&self) -> bool {
+        return self.is_arithmetic() || self.is_pointer();
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        match self {
+            Type::Pointer(_) => return true,
+            _ => return false,
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        match self {
+            Type::Array(_, _) => return true,
+            _ => return false,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        return *self == Type::Error;
+    }
+
+    pub fn is_struct(&self) -> bool {
+        match self {
+            Type::Struct(_) => return true,
+            _ => return false,
+        }
+    }
+
+    // The struct or union it is, if it's one
+    pub fn struct_type(&self) -> Option<&StructType> {
+        match self {
+            Type::Struct(struct_type) => return Some(struct_type),
+            _ => return None,
+        }
+    }
+
+    // The type a pointer points to
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Pointer(pointee) => return Some(pointee),
+            _ => return None,
+        }
+    }
+
+    // The type of a value read from something of this type. An array
+    // can't be read as a whole, so it becomes a pointer to its first
+    // element.
+    pub fn decay(&self) -> Type {
+        match self {
+            Type::Array(element, _) => return Type::Pointer(element.clone()),
+            _ => return self.clone(),
+        }
+    }
+
+    // How a declaration of `inner` with this type would be written, leaving
+    // off the base type. Array brackets bind tighter than `*`, so a pointer
+    // to an array needs parentheses.
+    fn declarator(&self, inner: String) -> String {
+        match self {
+            Type::Pointer(pointee) if pointee.is_array() => return pointee.declarator(format!("(*{})", inner)),
+            Type::Pointer(pointee) => return pointee.declarator(format!("*{}", inner)),
+            Type::Array(element, length) => return element.declarator(format!("{}[{}]", inner, length)),
+            _ => return inner,
+        }
+    }
+
+    // The type the declarator is applied to, e.g. `char` for `char *[3]`
+    fn base_name(&self) -> String {
+        let name: &str;
+        match self {
+            Type::Char => name = "char",
+            Type::SignedChar => name = "signed char",
+            Type::UnsignedChar => name = "unsigned char",
+            Type::Short => name = "short",
+            Type::UnsignedShort => name = "unsigned short",
+            Type::Int => name = "int",
+            Type::UnsignedInt => name = "unsigned int",
+            Type::Long => name = "long",
+            Type::UnsignedLong => name = "unsigned long",
+            Type::LongLong => name = "long long",
+            Type::UnsignedLongLong => name = "unsigned long long",
+            Type::Float => name = "float",
+            Type::Double => name = "double",
+            Type::Pointer(inner) | Type::Array(inner, _) => return inner.base_name(),
+            Type::Struct(struct_type) => return struct_type.name(),
+            Type::Error => name = "<error>",
+        }
+        return name.to_string();
+    }
+}
+
+// As it's written in C, e.g. `int **`, `char[3]` or `int (*)[3]`
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let declarator = self.declarator(String::new());
+        if declarator.is_empty() || declarator.starts_with('[') {
+            return write!(f, "{}{}", self.base_name(), declarator);
+        }
+        return write!(f, "{} {}", self.base_name(), declarator);
+    }
+}